@@ -12,6 +12,7 @@
 #define RESULT_IdxOutOfVtxCnt     (-4)
 #define RESULT_TriBufNotGenerated (-5)
 #define RESULT_BVHNotGenerated    (-6)
+#define RESULT_InvalidArgument    (-7)
 #define IS_RESULT_GOOD(res)       ((res) >= RESULT_Good)
 
 typedef double PyFloat;
@@ -23,6 +24,7 @@ typedef PyInt ID;
 /*
  * Allocate BVH resource with given vertex data.
  * ** The max resource count in static buffer is 8 **
+ * This is thread safe, each resource is locked separately.
  * this means that you can't allocate more than 8 times without * BVHBuildInfo_delete * .
  * @vtxbuf: Vertex buffer.
  * @n: Vertex buffer length, n equals 3 times of vertex count.
@@ -52,6 +54,107 @@ BVHBuildInfo_delete(ID id);
 extern Result
 BVHBuildInfo_add_poly_index(ID id, PyInt * idxbuf, PyInt n);
 
+#define SPLIT_METHOD_Naive        (0)
+#define SPLIT_METHOD_SAH          (1)
+#define SPLIT_METHOD_BinnedSAH    (2)
+#define SPLIT_METHOD_ObjectMedian (3)
+#define SPLIT_METHOD_CentroidMedian (4)
+#define SPLIT_METHOD_Spatial      (5)
+#define SPLIT_METHOD_LBVH         (6)
+#define SPLIT_METHOD_PhysXBVH33   (7)
+#define SPLIT_METHOD_PhysXBVH34   (8)
+
+/*
+ * Select how BVH resource splits nodes while generating BVH.
+ * @method: One of SPLIT_METHOD_*, default is SPLIT_METHOD_Naive.
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_split_method(ID id, PyInt method);
+
+/*
+ * Set bucket count per axis used by SPLIT_METHOD_BinnedSAH.
+ * @num_bins: Bucket count, must be at least 2, default is 16.
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_num_bins(ID id, PyInt num_bins);
+
+/*
+ * Set how many duplicated triangle references SPLIT_METHOD_Spatial may create.
+ * @budget: Ratio of triangle count, default is 0.3.
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_spatial_split_budget(ID id, PyFloat budget);
+
+/*
+ * Set Morton code width used by SPLIT_METHOD_LBVH.
+ * @bits: 30 or 63, default is 30.
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_morton_code_bits(ID id, PyInt bits);
+
+/*
+ * Same as PxBVH33MidphaseDesc::meshSizePerformanceTradeoff, used by SPLIT_METHOD_PhysXBVH33.
+ * @tradeoff: In [0, 1], default is 0.55. Larger value means larger leaves.
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_mesh_size_performance_tradeoff(ID id, PyFloat tradeoff);
+
+/*
+ * Same as PxBVH34MidphaseDesc::numPrimsPerLeaf, used by SPLIT_METHOD_PhysXBVH34.
+ * @num_prims_per_leaf: In [1, 15], default is 4.
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_num_prims_per_leaf(ID id, PyInt num_prims_per_leaf);
+
+/*
+ * Collapse generated binary BVH into a wider tree, like SIMD midphases do.
+ * @node_width: 2, 4 or 8, default is 2 (no collapse).
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_node_width(ID id, PyInt node_width);
+
+/*
+ * Set how many threads BVHBuildInfo_generate_bvh may use to build sibling subtrees,
+ * and how many workers the profile peaks sweep the probe grid with.
+ * Different IDs can also be used from different threads at the same time.
+ * @num_threads: At least 1, default is 1.
+ * RESULT: Returns set result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_num_threads(ID id, PyInt num_threads);
+
+typedef struct {
+	PyFloat node_visit;
+	PyFloat leaf_visit;
+	PyFloat tri_test;
+	PyFloat tri_per_leaf;
+} PyCostModel;
+
+/*
+ * Weights (in microseconds) used by the peak reports. Defaults are
+ * uncalibrated placeholders hand-estimated for the PhysX BVH34 midphase;
+ * fit them against measured timings before reading costs as absolute times.
+ * RESULT: RESULT_InvalidArgument if any weight is negative.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_set_cost_model(ID id, PyCostModel cost_model);
+
 /*
  * BVH resource will do triangle reduction internally.
  * RESULT: Returns add result, if result < 0, it means an error occours.
@@ -68,6 +171,33 @@ BVHBuildInfo_generate_tri_buf(ID id);
 extern Result
 BVHBuildInfo_generate_bvh(ID id);
 
+/*
+ * Time spent by the last BVHBuildInfo_generate_bvh.
+ * RESULT: Returns build time in microseconds.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_build_time_us(ID id);
+
+/*
+ * Sum of overlapping area between sibling nodes, divided by root surface area.
+ * The lower the value is, the less nodes a query has to visit.
+ * @overlap: Output value.
+ * RESULT: Returns query result, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_sibling_overlap(ID id, PyFloat * overlap);
+
+/*
+ * Triangle references held by all leaves. Larger than triangle count
+ * when SPLIT_METHOD_Spatial duplicated some triangles.
+ * RESULT: Returns reference count.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_reference_count(ID id);
+
 /*
  * BVH resource collision simulation profile peak.
  * The higher the value is, the worse performance the assets cause.
@@ -82,4 +212,323 @@ BVHBuildInfo_get_surface_hit_peak(
 	PyFloat block_size_y,
 	PyFloat block_size_z);
 
+typedef struct {
+	PyInt leaves;
+	PyInt nodes_visited;
+	PyInt leaf_tris;
+	PyInt tri_tests;
+	PyFloat cost;
+	PyInt peak_leaves;
+} PyPeakReport;
+
+/*
+ * Same sweeps as the profile peaks above. The counts and cost all come from
+ * the probe with the highest cost under the resource's cost model (ties go
+ * to more leaves). peak_leaves is the leaf peak over the whole sweep and may
+ * come from a different probe.
+ * RESULT: Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_block_overlap_peak_report(
+	ID id,
+	PyFloat block_size,
+	PyPeakReport * report);
+
+extern Result
+BVHBuildInfo_get_surface_hit_peak_report(
+	ID id,
+	PyFloat step,
+	PyVec3 block_size,
+	PyPeakReport * report);
+
+typedef struct {
+	PyInt samples;
+	PyInt empty_samples;
+	PyInt peak;
+	PyFloat mean;
+	PyInt median;
+	PyInt p90;
+	PyInt p99;
+	PyVec3 worst_center;
+	PyVec3 worst_min;
+	PyVec3 worst_max;
+	PyInt histogram_len;
+} PyPeakDistribution;
+
+/*
+ * Same sweeps as the profile peaks above, keeping the leaf-hit count of
+ * every probe. histogram[n] is the number of probes that touched n leaves;
+ * at most `buflen` entries are written and `histogram_len` holds the full
+ * length, which never exceeds leaf count + 1.
+ * `samples` counts every probe; `empty_samples` (== histogram[0]) counts
+ * probes that touched no leaf. mean, median, p90 and p99 only cover the
+ * non-empty probes and are 0 when every probe is empty.
+ * RESULT: Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_block_overlap_distribution(
+	ID id,
+	PyFloat block_size,
+	PyPeakDistribution * dist,
+	PyInt * histogram,
+	PyInt buflen);
+
+extern Result
+BVHBuildInfo_get_surface_hit_distribution(
+	ID id,
+	PyFloat step,
+	PyVec3 block_size,
+	PyPeakDistribution * dist,
+	PyInt * histogram,
+	PyInt buflen);
+
+/*
+ * Same sweeps as the profile peaks above, but counting triangles that
+ * really touch the probe box (exact SAT test) instead of candidate leaves.
+ * RESULT: Returns peak triangle hit count.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_block_overlap_tri_peak(ID id, PyFloat block_size);
+
+extern Result
+BVHBuildInfo_get_surface_hit_tri_peak(
+	ID id,
+	PyFloat step,
+	PyFloat block_size_x,
+	PyFloat block_size_y,
+	PyFloat block_size_z);
+
+/*
+ * Surface hit peak probed with a sphere (projectiles) or an upright
+ * capsule (character controllers) instead of a box. half_height excludes
+ * the hemispheres. Leaves are culled by box, triangles tested exactly.
+ * RESULT: Returns peak leaf count.
+ *         RESULT_InvalidArgument for non-positive radius.
+ */
+extern Result
+BVHBuildInfo_get_sphere_surface_hit_peak(ID id, PyFloat step, PyFloat radius);
+
+extern Result
+BVHBuildInfo_get_capsule_surface_hit_peak(
+	ID id,
+	PyFloat step,
+	PyFloat half_height,
+	PyFloat radius);
+
+typedef struct {
+	PyFloat x, y, z;
+} PyVec3;
+
+typedef struct {
+	PyFloat distance;
+	PyFloat u, v;
+	PyInt indices[3];
+} PyRayHit;
+
+/*
+ * Cast a ray against generated BVH.
+ * @any_hit: 0 returns the closest hit, otherwise returns as soon as anything is hit.
+ * @hit: Output hit, only written when something is hit.
+ * RESULT: Returns 1 on hit and 0 on miss, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_raycast(
+	ID id,
+	PyVec3 origin,
+	PyVec3 direction,
+	PyFloat max_distance,
+	PyInt any_hit,
+	PyRayHit * hit);
+
+/*
+ * Surface hit peak probed with an oriented box. Euler angles are radians,
+ * applied as roll (x), then pitch (y), then yaw (z).
+ * RESULT: Returns peak leaf count.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_obb_surface_hit_peak(
+	ID id,
+	PyFloat step,
+	PyVec3 half_extents,
+	PyFloat roll,
+	PyFloat pitch,
+	PyFloat yaw);
+
+/*
+ * Worst surface hit peak over num_orientations deterministic samples.
+ * RESULT: Returns peak leaf count.
+ *         RESULT_InvalidArgument if num_orientations <= 0.
+ */
+extern Result
+BVHBuildInfo_get_obb_surface_hit_peak_worst(
+	ID id,
+	PyFloat step,
+	PyVec3 half_extents,
+	PyInt num_orientations);
+
+typedef struct {
+	PyFloat distance;
+	PyInt indices[3];
+} PySweepHit;
+
+/*
+ * Continuous sweep of a sphere or an oriented box along direction.
+ * Euler angles are radians, applied as roll (x), pitch (y), yaw (z).
+ * @hit: Output hit, distance is the time of impact along the normalized
+ *       direction, 0 when the shape starts overlapping.
+ * RESULT: Returns 1 on hit and 0 on miss, if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_sweep_sphere(
+	ID id,
+	PyVec3 center,
+	PyFloat radius,
+	PyVec3 direction,
+	PyFloat max_distance,
+	PySweepHit * hit);
+
+extern Result
+BVHBuildInfo_sweep_box(
+	ID id,
+	PyVec3 center,
+	PyVec3 half_extents,
+	PyFloat roll,
+	PyFloat pitch,
+	PyFloat yaw,
+	PyVec3 direction,
+	PyFloat max_distance,
+	PySweepHit * hit);
+
+typedef struct {
+	PyVec3 point;
+	PyFloat distance;
+	PyInt indices[3];
+} PyClosestPoint;
+
+/*
+ * Closest point on the mesh to point, searched within max_distance.
+ * Pass a huge max_distance (e.g. INFINITY) for an unbounded search.
+ * RESULT: Returns 1 when found and 0 when nothing is within max_distance,
+ *         if result < 0, it means an error occours.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_closest_point(
+	ID id,
+	PyVec3 point,
+	PyFloat max_distance,
+	PyClosestPoint * closest);
+
+#define INSIDE_MODE_RayParity     (0)
+#define INSIDE_MODE_WindingNumber (1)
+
+/*
+ * Whether point lies inside the closed mesh.
+ * INSIDE_MODE_RayParity counts ray crossings and needs a watertight mesh.
+ * INSIDE_MODE_WindingNumber tolerates small holes, its acceleration
+ * structure is built on first use and reused until the next build.
+ * RESULT: Returns 1 when inside and 0 when outside, if result < 0, it means an error occours.
+ *         RESULT_InvalidArgument for unknown mode.
+ */
+extern Result
+BVHBuildInfo_point_inside(ID id, PyVec3 point, PyInt mode);
+
+typedef struct {
+	PyInt leaf_pairs;
+	PyInt candidate_tri_pairs;
+	PyInt hit_tri_pairs;
+} PyPairOverlap;
+
+/*
+ * Overlap between two generated BVHs, e.g. a dynamic mesh touching the
+ * static world. other_id is placed into id's space by rotating with the
+ * Euler angles (radians, roll (x), pitch (y), yaw (z)) then translating.
+ * @overlap: Overlapping leaf pairs, triangle pairs tested exactly and
+ *           triangle pairs really intersecting.
+ * RESULT: Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_pair_overlap(
+	ID id,
+	ID other_id,
+	PyVec3 translation,
+	PyFloat roll,
+	PyFloat pitch,
+	PyFloat yaw,
+	PyPairOverlap * overlap);
+
+typedef struct {
+	PyInt lhs[3];
+	PyInt rhs[3];
+	PyInt has_segment;
+	PyVec3 seg0, seg1;
+} PySelfIntersection;
+
+/*
+ * Pairs of intersecting triangles in the mesh, skipping triangles that
+ * share a vertex or an edge. has_segment is 0 for coplanar overlaps.
+ * Call get_self_intersection_count first to size the buffer.
+ * RESULT: Returns number of intersecting pairs.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_get_self_intersection_count(ID id);
+
+extern Result
+BVHBuildInfo_get_self_intersections(
+	ID id,
+	PySelfIntersection * buf,
+	PyInt buflen);
+
+/*
+ * Batched box probes. Box i is centered at centers[i] with size extents[i];
+ * the leaves it overlaps go to leaves[i] and the triangles it actually
+ * intersects go to tris[i]. Both output arrays need `num` elements.
+ * RESULT: RESULT_InvalidArgument for a negative num or a null pointer,
+ *         even when num is 0. Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_probe_batch(
+	ID id,
+	const PyVec3 * centers,
+	const PyVec3 * extents,
+	PyInt num,
+	PyInt * leaves,
+	PyInt * tris);
+
+typedef struct {
+	PyInt visible_leaves;
+	PyInt visible_tris;
+	PyInt nodes_tested;
+	PyInt nodes_inside;
+	PyInt nodes_intersecting;
+	PyInt nodes_outside;
+} PyFrustumCull;
+
+/*
+ * Camera culling stats against a perspective frustum. Subtrees fully
+ * inside the frustum are accepted without testing their descendants.
+ * @fov_y: Vertical field of view in radians.
+ * @aspect: Width / height.
+ * @cull: visible_tris counts distinct triangles, a triangle referenced by
+ *        several visible leaves of a spatial-split tree is counted once.
+ * RESULT: RESULT_InvalidArgument for a degenerate frustum.
+ *         Use `if (IS_RESULT_GOOD(result))`.
+ */
+extern Result
+BVHBuildInfo_frustum_cull(
+	ID id,
+	PyVec3 eye,
+	PyVec3 forward,
+	PyVec3 up,
+	PyFloat fov_y,
+	PyFloat aspect,
+	PyFloat near,
+	PyFloat far,
+	PyFrustumCull * cull);
+
 #endif // _BVHGEN_H_
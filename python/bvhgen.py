@@ -1,6 +1,7 @@
 #encoding=utf8
 import ctypes
 import os
+import sys
 
 
 PATH = os.path.join(os.path.dirname(__file__), "..")
@@ -32,6 +33,170 @@ class PyBVHInfo(ctypes.Structure):
             )
 
 
+class PyRayHit(ctypes.Structure):
+    _fields_ = [
+        ("distance", ctypes.c_double),
+        ("u", ctypes.c_double),
+        ("v", ctypes.c_double),
+        ("indices", ctypes.c_longlong * 3),
+    ]
+
+    def __repr__(self):
+        return "<RayHit distance: {} u: {} v: {} indices: {}>".format(
+            self.distance,
+            self.u,
+            self.v,
+            list(self.indices),
+            )
+
+
+class PySweepHit(ctypes.Structure):
+    _fields_ = [
+        ("distance", ctypes.c_double),
+        ("indices", ctypes.c_longlong * 3),
+    ]
+
+    def __repr__(self):
+        return "<SweepHit distance: {} indices: {}>".format(
+            self.distance,
+            list(self.indices),
+            )
+
+
+
+class PyClosestPoint(ctypes.Structure):
+    _fields_ = [
+        ("point", PyVec3),
+        ("distance", ctypes.c_double),
+        ("indices", ctypes.c_longlong * 3),
+    ]
+
+    def __repr__(self):
+        return "<ClosestPoint point: {} distance: {} indices: {}>".format(
+            self.point,
+            self.distance,
+            list(self.indices),
+            )
+
+
+
+class PyPairOverlap(ctypes.Structure):
+    _fields_ = [
+        ("leaf_pairs", ctypes.c_longlong),
+        ("candidate_tri_pairs", ctypes.c_longlong),
+        ("hit_tri_pairs", ctypes.c_longlong),
+    ]
+
+    def __repr__(self):
+        return "<PairOverlap leaf_pairs: {} candidate_tri_pairs: {} hit_tri_pairs: {}>".format(
+            self.leaf_pairs,
+            self.candidate_tri_pairs,
+            self.hit_tri_pairs,
+            )
+
+
+
+class PySelfIntersection(ctypes.Structure):
+    _fields_ = [
+        ("lhs", ctypes.c_longlong * 3),
+        ("rhs", ctypes.c_longlong * 3),
+        ("has_segment", ctypes.c_longlong),
+        ("seg0", PyVec3),
+        ("seg1", PyVec3),
+    ]
+
+    def __repr__(self):
+        return "<SelfIntersection lhs: {} rhs: {} segment: {}>".format(
+            list(self.lhs),
+            list(self.rhs),
+            (self.seg0, self.seg1) if self.has_segment else None,
+            )
+
+
+
+class PyFrustumCull(ctypes.Structure):
+    _fields_ = [
+        ("visible_leaves", ctypes.c_longlong),
+        ("visible_tris", ctypes.c_longlong),
+        ("nodes_tested", ctypes.c_longlong),
+        ("nodes_inside", ctypes.c_longlong),
+        ("nodes_intersecting", ctypes.c_longlong),
+        ("nodes_outside", ctypes.c_longlong),
+    ]
+
+    def __repr__(self):
+        return "<FrustumCull visible_leaves: {} visible_tris: {} nodes_tested: {} inside: {} intersecting: {} outside: {}>".format(
+            self.visible_leaves,
+            self.visible_tris,
+            self.nodes_tested,
+            self.nodes_inside,
+            self.nodes_intersecting,
+            self.nodes_outside,
+            )
+
+
+
+class PyCostModel(ctypes.Structure):
+    _fields_ = [
+        ("node_visit", ctypes.c_double),
+        ("leaf_visit", ctypes.c_double),
+        ("tri_test", ctypes.c_double),
+        ("tri_per_leaf", ctypes.c_double),
+    ]
+
+
+class PyPeakReport(ctypes.Structure):
+    _fields_ = [
+        ("leaves", ctypes.c_longlong),
+        ("nodes_visited", ctypes.c_longlong),
+        ("leaf_tris", ctypes.c_longlong),
+        ("tri_tests", ctypes.c_longlong),
+        ("cost", ctypes.c_double),
+        ("peak_leaves", ctypes.c_longlong),
+    ]
+
+    def __repr__(self):
+        return "<PeakReport leaves: {} nodes_visited: {} leaf_tris: {} tri_tests: {} cost: {:.3f} us peak_leaves: {}>".format(
+            self.leaves,
+            self.nodes_visited,
+            self.leaf_tris,
+            self.tri_tests,
+            self.cost,
+            self.peak_leaves,
+            )
+
+
+
+class PyPeakDistribution(ctypes.Structure):
+    _fields_ = [
+        ("samples", ctypes.c_longlong),
+        ("empty_samples", ctypes.c_longlong),
+        ("peak", ctypes.c_longlong),
+        ("mean", ctypes.c_double),
+        ("median", ctypes.c_longlong),
+        ("p90", ctypes.c_longlong),
+        ("p99", ctypes.c_longlong),
+        ("worst_center", PyVec3),
+        ("worst_min", PyVec3),
+        ("worst_max", PyVec3),
+        ("histogram_len", ctypes.c_longlong),
+    ]
+
+    def __repr__(self):
+        return "<PeakDistribution samples: {} empty_samples: {} peak: {} mean: {:.2f} median: {} p90: {} p99: {} worst_center: ({:.1f}, {:.1f}, {:.1f})>".format(
+            self.samples,
+            self.empty_samples,
+            self.peak,
+            self.mean,
+            self.median,
+            self.p90,
+            self.p99,
+            self.worst_center.x,
+            self.worst_center.y,
+            self.worst_center.z,
+            )
+
+
 dllpath = os.path.abspath(os.path.join(PATH, "target/release/bvhgen.dll"))
 dll = ctypes.cdll.LoadLibrary(dllpath)
 
@@ -47,6 +212,38 @@ _BVHBuildInfo_add_poly_index = dll.BVHBuildInfo_add_poly_index
 _BVHBuildInfo_add_poly_index.restype = ctypes.c_longlong
 _BVHBuildInfo_add_poly_index.argtypes = (ctypes.c_longlong, ctypes.POINTER(ctypes.c_longlong), ctypes.c_longlong)
 
+_BVHBuildInfo_set_split_method = dll.BVHBuildInfo_set_split_method
+_BVHBuildInfo_set_split_method.restype = ctypes.c_longlong
+_BVHBuildInfo_set_split_method.argtypes = (ctypes.c_longlong, ctypes.c_longlong)
+
+_BVHBuildInfo_set_num_bins = dll.BVHBuildInfo_set_num_bins
+_BVHBuildInfo_set_num_bins.restype = ctypes.c_longlong
+_BVHBuildInfo_set_num_bins.argtypes = (ctypes.c_longlong, ctypes.c_longlong)
+
+_BVHBuildInfo_set_spatial_split_budget = dll.BVHBuildInfo_set_spatial_split_budget
+_BVHBuildInfo_set_spatial_split_budget.restype = ctypes.c_longlong
+_BVHBuildInfo_set_spatial_split_budget.argtypes = (ctypes.c_longlong, ctypes.c_double)
+
+_BVHBuildInfo_set_morton_code_bits = dll.BVHBuildInfo_set_morton_code_bits
+_BVHBuildInfo_set_morton_code_bits.restype = ctypes.c_longlong
+_BVHBuildInfo_set_morton_code_bits.argtypes = (ctypes.c_longlong, ctypes.c_longlong)
+
+_BVHBuildInfo_set_mesh_size_performance_tradeoff = dll.BVHBuildInfo_set_mesh_size_performance_tradeoff
+_BVHBuildInfo_set_mesh_size_performance_tradeoff.restype = ctypes.c_longlong
+_BVHBuildInfo_set_mesh_size_performance_tradeoff.argtypes = (ctypes.c_longlong, ctypes.c_double)
+
+_BVHBuildInfo_set_num_prims_per_leaf = dll.BVHBuildInfo_set_num_prims_per_leaf
+_BVHBuildInfo_set_num_prims_per_leaf.restype = ctypes.c_longlong
+_BVHBuildInfo_set_num_prims_per_leaf.argtypes = (ctypes.c_longlong, ctypes.c_longlong)
+
+_BVHBuildInfo_set_node_width = dll.BVHBuildInfo_set_node_width
+_BVHBuildInfo_set_node_width.restype = ctypes.c_longlong
+_BVHBuildInfo_set_node_width.argtypes = (ctypes.c_longlong, ctypes.c_longlong)
+
+_BVHBuildInfo_set_num_threads = dll.BVHBuildInfo_set_num_threads
+_BVHBuildInfo_set_num_threads.restype = ctypes.c_longlong
+_BVHBuildInfo_set_num_threads.argtypes = (ctypes.c_longlong, ctypes.c_longlong)
+
 _BVHBuildInfo_generate_tri_buf = dll.BVHBuildInfo_generate_tri_buf
 _BVHBuildInfo_generate_tri_buf.restype = ctypes.c_longlong
 _BVHBuildInfo_generate_tri_buf.argtypes = (ctypes.c_longlong,)
@@ -55,6 +252,18 @@ _BVHBuildInfo_generate_bvh = dll.BVHBuildInfo_generate_bvh
 _BVHBuildInfo_generate_bvh.restype = ctypes.c_longlong
 _BVHBuildInfo_generate_bvh.argtypes = (ctypes.c_longlong,)
 
+_BVHBuildInfo_get_build_time_us = dll.BVHBuildInfo_get_build_time_us
+_BVHBuildInfo_get_build_time_us.restype = ctypes.c_longlong
+_BVHBuildInfo_get_build_time_us.argtypes = (ctypes.c_longlong,)
+
+_BVHBuildInfo_get_sibling_overlap = dll.BVHBuildInfo_get_sibling_overlap
+_BVHBuildInfo_get_sibling_overlap.restype = ctypes.c_longlong
+_BVHBuildInfo_get_sibling_overlap.argtypes = (ctypes.c_longlong, ctypes.POINTER(ctypes.c_double))
+
+_BVHBuildInfo_get_reference_count = dll.BVHBuildInfo_get_reference_count
+_BVHBuildInfo_get_reference_count.restype = ctypes.c_longlong
+_BVHBuildInfo_get_reference_count.argtypes = (ctypes.c_longlong,)
+
 _BVHBuildInfo_get_leaf_count = dll.BVHBuildInfo_get_leaf_count
 _BVHBuildInfo_get_leaf_count.restype = ctypes.c_longlong
 _BVHBuildInfo_get_leaf_count.argtypes = (ctypes.c_longlong,)
@@ -71,9 +280,106 @@ _BVHBuildInfo_get_surface_hit_peak = dll.BVHBuildInfo_get_surface_hit_peak
 _BVHBuildInfo_get_surface_hit_peak.restype = ctypes.c_longlong
 _BVHBuildInfo_get_surface_hit_peak.argtypes = (ctypes.c_longlong, ctypes.c_double, ctypes.c_double, ctypes.c_double, ctypes.c_double)
 
+_BVHBuildInfo_get_block_overlap_tri_peak = dll.BVHBuildInfo_get_block_overlap_tri_peak
+_BVHBuildInfo_get_block_overlap_tri_peak.restype = ctypes.c_longlong
+_BVHBuildInfo_get_block_overlap_tri_peak.argtypes = (ctypes.c_longlong, ctypes.c_double)
+
+_BVHBuildInfo_get_surface_hit_tri_peak = dll.BVHBuildInfo_get_surface_hit_tri_peak
+_BVHBuildInfo_get_surface_hit_tri_peak.restype = ctypes.c_longlong
+_BVHBuildInfo_get_surface_hit_tri_peak.argtypes = (ctypes.c_longlong, ctypes.c_double, ctypes.c_double, ctypes.c_double, ctypes.c_double)
+
+_BVHBuildInfo_get_sphere_surface_hit_peak = dll.BVHBuildInfo_get_sphere_surface_hit_peak
+_BVHBuildInfo_get_sphere_surface_hit_peak.restype = ctypes.c_longlong
+_BVHBuildInfo_get_sphere_surface_hit_peak.argtypes = (ctypes.c_longlong, ctypes.c_double, ctypes.c_double)
+
+_BVHBuildInfo_get_capsule_surface_hit_peak = dll.BVHBuildInfo_get_capsule_surface_hit_peak
+_BVHBuildInfo_get_capsule_surface_hit_peak.restype = ctypes.c_longlong
+_BVHBuildInfo_get_capsule_surface_hit_peak.argtypes = (ctypes.c_longlong, ctypes.c_double, ctypes.c_double, ctypes.c_double)
+
+_BVHBuildInfo_get_obb_surface_hit_peak = dll.BVHBuildInfo_get_obb_surface_hit_peak
+_BVHBuildInfo_get_obb_surface_hit_peak.restype = ctypes.c_longlong
+_BVHBuildInfo_get_obb_surface_hit_peak.argtypes = (ctypes.c_longlong, ctypes.c_double, PyVec3, ctypes.c_double, ctypes.c_double, ctypes.c_double)
+
+_BVHBuildInfo_get_obb_surface_hit_peak_worst = dll.BVHBuildInfo_get_obb_surface_hit_peak_worst
+_BVHBuildInfo_get_obb_surface_hit_peak_worst.restype = ctypes.c_longlong
+_BVHBuildInfo_get_obb_surface_hit_peak_worst.argtypes = (ctypes.c_longlong, ctypes.c_double, PyVec3, ctypes.c_longlong)
+
+_BVHBuildInfo_raycast = dll.BVHBuildInfo_raycast
+_BVHBuildInfo_raycast.restype = ctypes.c_longlong
+_BVHBuildInfo_raycast.argtypes = (ctypes.c_longlong, PyVec3, PyVec3, ctypes.c_double, ctypes.c_longlong, ctypes.POINTER(PyRayHit))
+
+_BVHBuildInfo_sweep_sphere = dll.BVHBuildInfo_sweep_sphere
+_BVHBuildInfo_sweep_sphere.restype = ctypes.c_longlong
+_BVHBuildInfo_sweep_sphere.argtypes = (ctypes.c_longlong, PyVec3, ctypes.c_double, PyVec3, ctypes.c_double, ctypes.POINTER(PySweepHit))
+
+_BVHBuildInfo_sweep_box = dll.BVHBuildInfo_sweep_box
+_BVHBuildInfo_sweep_box.restype = ctypes.c_longlong
+_BVHBuildInfo_sweep_box.argtypes = (ctypes.c_longlong, PyVec3, PyVec3, ctypes.c_double, ctypes.c_double, ctypes.c_double, PyVec3, ctypes.c_double, ctypes.POINTER(PySweepHit))
+
+_BVHBuildInfo_closest_point = dll.BVHBuildInfo_closest_point
+_BVHBuildInfo_closest_point.restype = ctypes.c_longlong
+_BVHBuildInfo_closest_point.argtypes = (ctypes.c_longlong, PyVec3, ctypes.c_double, ctypes.POINTER(PyClosestPoint))
+
+_BVHBuildInfo_point_inside = dll.BVHBuildInfo_point_inside
+_BVHBuildInfo_point_inside.restype = ctypes.c_longlong
+_BVHBuildInfo_point_inside.argtypes = (ctypes.c_longlong, PyVec3, ctypes.c_longlong)
+
+_BVHBuildInfo_get_pair_overlap = dll.BVHBuildInfo_get_pair_overlap
+_BVHBuildInfo_get_pair_overlap.restype = ctypes.c_longlong
+_BVHBuildInfo_get_pair_overlap.argtypes = (ctypes.c_longlong, ctypes.c_longlong, PyVec3, ctypes.c_double, ctypes.c_double, ctypes.c_double, ctypes.POINTER(PyPairOverlap))
+
+_BVHBuildInfo_get_self_intersection_count = dll.BVHBuildInfo_get_self_intersection_count
+_BVHBuildInfo_get_self_intersection_count.restype = ctypes.c_longlong
+_BVHBuildInfo_get_self_intersection_count.argtypes = (ctypes.c_longlong,)
+
+_BVHBuildInfo_get_self_intersections = dll.BVHBuildInfo_get_self_intersections
+_BVHBuildInfo_get_self_intersections.restype = ctypes.c_longlong
+_BVHBuildInfo_get_self_intersections.argtypes = (ctypes.c_longlong, ctypes.POINTER(PySelfIntersection), ctypes.c_longlong)
+
+_BVHBuildInfo_frustum_cull = dll.BVHBuildInfo_frustum_cull
+_BVHBuildInfo_frustum_cull.restype = ctypes.c_longlong
+_BVHBuildInfo_frustum_cull.argtypes = (ctypes.c_longlong, PyVec3, PyVec3, PyVec3, ctypes.c_double, ctypes.c_double, ctypes.c_double, ctypes.c_double, ctypes.POINTER(PyFrustumCull))
+
+_BVHBuildInfo_probe_batch = dll.BVHBuildInfo_probe_batch
+_BVHBuildInfo_probe_batch.restype = ctypes.c_longlong
+_BVHBuildInfo_probe_batch.argtypes = (ctypes.c_longlong, ctypes.POINTER(PyVec3), ctypes.POINTER(PyVec3), ctypes.c_longlong, ctypes.POINTER(ctypes.c_longlong), ctypes.POINTER(ctypes.c_longlong))
+
+_BVHBuildInfo_set_cost_model = dll.BVHBuildInfo_set_cost_model
+_BVHBuildInfo_set_cost_model.restype = ctypes.c_longlong
+_BVHBuildInfo_set_cost_model.argtypes = (ctypes.c_longlong, PyCostModel)
+
+_BVHBuildInfo_get_block_overlap_peak_report = dll.BVHBuildInfo_get_block_overlap_peak_report
+_BVHBuildInfo_get_block_overlap_peak_report.restype = ctypes.c_longlong
+_BVHBuildInfo_get_block_overlap_peak_report.argtypes = (ctypes.c_longlong, ctypes.c_double, ctypes.POINTER(PyPeakReport))
+
+_BVHBuildInfo_get_surface_hit_peak_report = dll.BVHBuildInfo_get_surface_hit_peak_report
+_BVHBuildInfo_get_surface_hit_peak_report.restype = ctypes.c_longlong
+_BVHBuildInfo_get_surface_hit_peak_report.argtypes = (ctypes.c_longlong, ctypes.c_double, PyVec3, ctypes.POINTER(PyPeakReport))
+
+_BVHBuildInfo_get_block_overlap_distribution = dll.BVHBuildInfo_get_block_overlap_distribution
+_BVHBuildInfo_get_block_overlap_distribution.restype = ctypes.c_longlong
+_BVHBuildInfo_get_block_overlap_distribution.argtypes = (ctypes.c_longlong, ctypes.c_double, ctypes.POINTER(PyPeakDistribution), ctypes.POINTER(ctypes.c_longlong), ctypes.c_longlong)
+
+_BVHBuildInfo_get_surface_hit_distribution = dll.BVHBuildInfo_get_surface_hit_distribution
+_BVHBuildInfo_get_surface_hit_distribution.restype = ctypes.c_longlong
+_BVHBuildInfo_get_surface_hit_distribution.argtypes = (ctypes.c_longlong, ctypes.c_double, PyVec3, ctypes.POINTER(PyPeakDistribution), ctypes.POINTER(ctypes.c_longlong), ctypes.c_longlong)
+
 
 class BVHBuildInfo:
 
+    SPLIT_METHOD_NAIVE = 0
+    SPLIT_METHOD_SAH = 1
+    SPLIT_METHOD_BINNED_SAH = 2
+    SPLIT_METHOD_OBJECT_MEDIAN = 3
+    SPLIT_METHOD_CENTROID_MEDIAN = 4
+    SPLIT_METHOD_SPATIAL = 5
+    SPLIT_METHOD_LBVH = 6
+    SPLIT_METHOD_PHYSX_BVH33 = 7
+    SPLIT_METHOD_PHYSX_BVH34 = 8
+
+    INSIDE_MODE_RAY_PARITY = 0
+    INSIDE_MODE_WINDING_NUMBER = 1
+
 
     class BVHBuildExc_OutOfResource(RuntimeError):pass
     class BVHBuildExc_ResourceNotFound(RuntimeError):pass
@@ -81,6 +387,7 @@ class BVHBuildInfo:
     class BVHBuildExc_IdxOutOfVtxCnt(RuntimeError):pass
     class BVHBuildExc_TriBufNotGenerated(RuntimeError):pass
     class BVHBuildExc_BVHNotGenerated(RuntimeError):pass
+    class BVHBuildExc_InvalidArgument(RuntimeError):pass
 
 
     @classmethod
@@ -93,6 +400,7 @@ class BVHBuildInfo:
                 cls.BVHBuildExc_IdxOutOfVtxCnt,
                 cls.BVHBuildExc_TriBufNotGenerated,
                 cls.BVHBuildExc_BVHNotGenerated,
+                cls.BVHBuildExc_InvalidArgument,
             )
             absv = abs(code) - 1
             raise lut[absv]
@@ -126,6 +434,52 @@ class BVHBuildInfo:
         self.__class__.checkexc(ret)
 
 
+    def set_split_method(self, method):
+        ret = _BVHBuildInfo_set_split_method(self.bvhid, method)
+        self.__class__.checkexc(ret)
+
+
+    def set_num_bins(self, num_bins):
+        ret = _BVHBuildInfo_set_num_bins(self.bvhid, num_bins)
+        self.__class__.checkexc(ret)
+
+
+    def set_spatial_split_budget(self, budget):
+        ret = _BVHBuildInfo_set_spatial_split_budget(self.bvhid, budget)
+        self.__class__.checkexc(ret)
+
+
+    def set_morton_code_bits(self, bits):
+        ret = _BVHBuildInfo_set_morton_code_bits(self.bvhid, bits)
+        self.__class__.checkexc(ret)
+
+
+    def set_mesh_size_performance_tradeoff(self, tradeoff):
+        ret = _BVHBuildInfo_set_mesh_size_performance_tradeoff(self.bvhid, tradeoff)
+        self.__class__.checkexc(ret)
+
+
+    def set_num_prims_per_leaf(self, num_prims_per_leaf):
+        ret = _BVHBuildInfo_set_num_prims_per_leaf(self.bvhid, num_prims_per_leaf)
+        self.__class__.checkexc(ret)
+
+
+    def set_node_width(self, node_width):
+        ret = _BVHBuildInfo_set_node_width(self.bvhid, node_width)
+        self.__class__.checkexc(ret)
+
+
+    def set_num_threads(self, num_threads):
+        ret = _BVHBuildInfo_set_num_threads(self.bvhid, num_threads)
+        self.__class__.checkexc(ret)
+
+
+    # 权重单位是微秒，默认值按PhysX BVH34估算
+    def set_cost_model(self, node_visit, leaf_visit, tri_test, tri_per_leaf):
+        ret = _BVHBuildInfo_set_cost_model(self.bvhid, PyCostModel(node_visit, leaf_visit, tri_test, tri_per_leaf))
+        self.__class__.checkexc(ret)
+
+
     def build(self):
         ret = _BVHBuildInfo_generate_tri_buf(self.bvhid)
         self.__class__.checkexc(ret)
@@ -143,6 +497,25 @@ class BVHBuildInfo:
         return [ele for ele in arr]
 
 
+    def get_build_time(self):
+        ret = _BVHBuildInfo_get_build_time_us(self.bvhid)
+        self.__class__.checkexc(ret)
+        return ret / 1000.0
+
+
+    def get_sibling_overlap(self):
+        overlap = ctypes.c_double()
+        ret = _BVHBuildInfo_get_sibling_overlap(self.bvhid, ctypes.byref(overlap))
+        self.__class__.checkexc(ret)
+        return overlap.value
+
+
+    def get_reference_count(self):
+        ret = _BVHBuildInfo_get_reference_count(self.bvhid)
+        self.__class__.checkexc(ret)
+        return ret
+
+
     def get_bvh_block_overlap_peak(self, block_size):
         return _BVHBuildInfo_get_block_overlap_peak(self.bvhid, block_size)
 
@@ -151,6 +524,138 @@ class BVHBuildInfo:
         x, y, z = block_size
         return _BVHBuildInfo_get_surface_hit_peak(self.bvhid, step, x, y, z)
 
+    def get_bvh_block_overlap_peak_report(self, block_size):
+        report = PyPeakReport()
+        ret = _BVHBuildInfo_get_block_overlap_peak_report(self.bvhid, block_size, ctypes.byref(report))
+        self.__class__.checkexc(ret)
+        return report
+
+
+    def get_bvh_surface_hit_peak_report(self, step, block_size):
+        report = PyPeakReport()
+        ret = _BVHBuildInfo_get_surface_hit_peak_report(self.bvhid, step, PyVec3(*block_size), ctypes.byref(report))
+        self.__class__.checkexc(ret)
+        return report
+
+
+    # 返回(分布, 直方图)，直方图长度不会超过叶子数+1
+    def get_bvh_block_overlap_distribution(self, block_size):
+        dist = PyPeakDistribution()
+        leaf_count = _BVHBuildInfo_get_leaf_count(self.bvhid)
+        self.__class__.checkexc(leaf_count)
+        buflen = leaf_count + 1
+        histogram = (ctypes.c_longlong * buflen)()
+        ret = _BVHBuildInfo_get_block_overlap_distribution(self.bvhid, block_size, ctypes.byref(dist), histogram, buflen)
+        self.__class__.checkexc(ret)
+        return dist, list(histogram[:dist.histogram_len])
+
+
+    def get_bvh_surface_hit_distribution(self, step, block_size):
+        dist = PyPeakDistribution()
+        leaf_count = _BVHBuildInfo_get_leaf_count(self.bvhid)
+        self.__class__.checkexc(leaf_count)
+        buflen = leaf_count + 1
+        histogram = (ctypes.c_longlong * buflen)()
+        ret = _BVHBuildInfo_get_surface_hit_distribution(self.bvhid, step, PyVec3(*block_size), ctypes.byref(dist), histogram, buflen)
+        self.__class__.checkexc(ret)
+        return dist, list(histogram[:dist.histogram_len])
+
+
+    def get_bvh_block_overlap_tri_peak(self, block_size):
+        ret = _BVHBuildInfo_get_block_overlap_tri_peak(self.bvhid, block_size)
+        self.__class__.checkexc(ret)
+        return ret
+
+
+    def get_bvh_surface_hit_tri_peak(self, step, block_size):
+        x, y, z = block_size
+        ret = _BVHBuildInfo_get_surface_hit_tri_peak(self.bvhid, step, x, y, z)
+        self.__class__.checkexc(ret)
+        return ret
+
+    def get_bvh_sphere_surface_hit_peak(self, step, radius):
+        ret = _BVHBuildInfo_get_sphere_surface_hit_peak(self.bvhid, step, radius)
+        self.__class__.checkexc(ret)
+        return ret
+
+
+    def get_bvh_capsule_surface_hit_peak(self, step, half_height, radius):
+        ret = _BVHBuildInfo_get_capsule_surface_hit_peak(self.bvhid, step, half_height, radius)
+        self.__class__.checkexc(ret)
+        return ret
+
+    def get_bvh_obb_surface_hit_peak(self, step, half_extents, roll=0.0, pitch=0.0, yaw=0.0):
+        ret = _BVHBuildInfo_get_obb_surface_hit_peak(self.bvhid, step, PyVec3(*half_extents), roll, pitch, yaw)
+        self.__class__.checkexc(ret)
+        return ret
+
+
+    def get_bvh_obb_surface_hit_peak_worst(self, step, half_extents, num_orientations=32):
+        ret = _BVHBuildInfo_get_obb_surface_hit_peak_worst(self.bvhid, step, PyVec3(*half_extents), num_orientations)
+        self.__class__.checkexc(ret)
+        return ret
+
+    def raycast(self, origin, direction, max_distance, any_hit=False):
+        hit = PyRayHit()
+        ret = _BVHBuildInfo_raycast(self.bvhid, PyVec3(*origin), PyVec3(*direction), max_distance, 1 if any_hit else 0, ctypes.byref(hit))
+        self.__class__.checkexc(ret)
+        return hit if ret > 0 else None
+
+    def sweep_sphere(self, center, radius, direction, max_distance):
+        hit = PySweepHit()
+        ret = _BVHBuildInfo_sweep_sphere(self.bvhid, PyVec3(*center), radius, PyVec3(*direction), max_distance, ctypes.byref(hit))
+        self.__class__.checkexc(ret)
+        return hit if ret > 0 else None
+
+    def sweep_box(self, center, half_extents, direction, max_distance, roll=0.0, pitch=0.0, yaw=0.0):
+        hit = PySweepHit()
+        ret = _BVHBuildInfo_sweep_box(self.bvhid, PyVec3(*center), PyVec3(*half_extents), roll, pitch, yaw, PyVec3(*direction), max_distance, ctypes.byref(hit))
+        self.__class__.checkexc(ret)
+        return hit if ret > 0 else None
+
+    def closest_point(self, point, max_distance=float("inf")):
+        closest = PyClosestPoint()
+        ret = _BVHBuildInfo_closest_point(self.bvhid, PyVec3(*point), max_distance, ctypes.byref(closest))
+        self.__class__.checkexc(ret)
+        return closest if ret > 0 else None
+
+    def point_inside(self, point, mode=INSIDE_MODE_RAY_PARITY):
+        ret = _BVHBuildInfo_point_inside(self.bvhid, PyVec3(*point), mode)
+        self.__class__.checkexc(ret)
+        return ret > 0
+
+    def get_pair_overlap(self, other, translation=(0.0, 0.0, 0.0), roll=0.0, pitch=0.0, yaw=0.0):
+        overlap = PyPairOverlap()
+        ret = _BVHBuildInfo_get_pair_overlap(self.bvhid, other.bvhid, PyVec3(*translation), roll, pitch, yaw, ctypes.byref(overlap))
+        self.__class__.checkexc(ret)
+        return overlap
+
+    def get_self_intersections(self):
+        cnt = _BVHBuildInfo_get_self_intersection_count(self.bvhid)
+        self.__class__.checkexc(cnt)
+        t = PySelfIntersection * cnt
+        arr = t()
+        ret = _BVHBuildInfo_get_self_intersections(self.bvhid, arr, cnt)
+        self.__class__.checkexc(ret)
+        return [ele for ele in arr]
+
+    def frustum_cull(self, eye, forward, up, fov_y, aspect, near, far):
+        cull = PyFrustumCull()
+        ret = _BVHBuildInfo_frustum_cull(self.bvhid, PyVec3(*eye), PyVec3(*forward), PyVec3(*up), fov_y, aspect, near, far, ctypes.byref(cull))
+        self.__class__.checkexc(ret)
+        return cull
+
+    # boxes是[(center, extent), ...]，返回[(leaves, tris), ...]
+    def probe_batch(self, boxes):
+        num = len(boxes)
+        centers = (PyVec3 * num)(*[PyVec3(*center) for center, _ in boxes])
+        extents = (PyVec3 * num)(*[PyVec3(*extent) for _, extent in boxes])
+        leaves = (ctypes.c_longlong * num)()
+        tris = (ctypes.c_longlong * num)()
+        ret = _BVHBuildInfo_probe_batch(self.bvhid, centers, extents, num, leaves, tris)
+        self.__class__.checkexc(ret)
+        return list(zip(leaves, tris))
+
 
 if __name__ == "__main__":
 
@@ -165,14 +670,40 @@ if __name__ == "__main__":
             bbi = BVHBuildInfo(vertices)
             for idxs in indices:
                 bbi.add_poly_index(idxs)    
+            bbi.set_num_threads(os.cpu_count() or 1)
+            # 大场景预览时用LBVH快速建树：blender --python bvhgen.py -- --preview
+            script_args = sys.argv[sys.argv.index("--") + 1:] if "--" in sys.argv else []
+            if "--preview" in script_args:
+                bbi.set_split_method(BVHBuildInfo.SPLIT_METHOD_LBVH)
             bbi.build()
             allbvh = bbi.get_bvh_leaves()
-            overlap_peak = bbi.get_bvh_block_overlap_peak(30.0)
-            surface_hit_peak = bbi.get_bvh_surface_hit_peak(30.0, (30.0, 30.0, 30.0))
-            print("Num BVH Leaves: {}\nBlock Overlap Peak: {}\nSurface Hit Peak: {}\n".format(
+            build_time = bbi.get_build_time()
+            sibling_overlap = bbi.get_sibling_overlap()
+            reference_count = bbi.get_reference_count()
+            overlap_report = bbi.get_bvh_block_overlap_peak_report(30.0)
+            surface_hit_report = bbi.get_bvh_surface_hit_peak_report(30.0, (30.0, 30.0, 30.0))
+            overlap_tri_peak = bbi.get_bvh_block_overlap_tri_peak(30.0)
+            surface_hit_tri_peak = bbi.get_bvh_surface_hit_tri_peak(30.0, (30.0, 30.0, 30.0))
+            overlap_dist, _ = bbi.get_bvh_block_overlap_distribution(30.0)
+            # 自相交的碰撞网格在PhysX里会导致接触抖动
+            self_intersections = bbi.get_self_intersections()
+            print("Num BVH Leaves: {}\nBuild Time: {:.3f} ms\nSibling Overlap: {:.4f}\nLeaf References: {}\nBlock Overlap Peak: {} leaves / {} tris / {:.3f} us\nBlock Overlap Leaves: mean {:.2f} / p90 {} / p99 {} (non-empty, {} of {} probes empty)\nSurface Hit Peak: {} leaves / {} tris / {:.3f} us\nSelf Intersections: {}\n".format(
                 len(allbvh),
-                overlap_peak,
-                surface_hit_peak,
+                build_time,
+                sibling_overlap,
+                reference_count,
+                overlap_report.peak_leaves,
+                overlap_tri_peak,
+                overlap_report.cost,
+                overlap_dist.mean,
+                overlap_dist.p90,
+                overlap_dist.p99,
+                overlap_dist.empty_samples,
+                overlap_dist.samples,
+                surface_hit_report.peak_leaves,
+                surface_hit_tri_peak,
+                surface_hit_report.cost,
+                len(self_intersections),
                 ))
             del bbi
             if False:
@@ -87,6 +87,33 @@ impl AABB {
         true
     }
 
+    pub fn merge(&self, other: &Self) -> Self {
+        Self {
+            min: Vec3::min(&self.min, &other.min),
+            max: Vec3::max(&self.max, &other.max),
+        }
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        if !self.intersect_with_aabb(other) {
+            return None;
+        }
+        Some(Self {
+            min: Vec3::max(&self.min, &other.min),
+            max: Vec3::min(&self.max, &other.max),
+        })
+    }
+
+    // 点在盒子里时为0
+    pub fn distance_to_point(&self, pt: &Vec3) -> f64 {
+        Vec3::min(&Vec3::max(pt, &self.min), &self.max).distance_to(pt)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let ext = self.extent();
+        2.0 * (ext.x * ext.y + ext.y * ext.z + ext.z * ext.x)
+    }
+
     pub fn extent(&self) -> Vec3 {
         Vec3 {
             x: self.max.x - self.min.x,
@@ -0,0 +1,524 @@
+#![allow(dead_code)]
+
+use crate::flat::FlatNodeRef;
+use crate::prelude::*;
+use crate::tri::{tri_key, TriKey};
+use crate::visit::traverse_node;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::Arc;
+
+// 批量查询只借用节点，不克隆Arc，结果写进调用方给的缓冲区。
+// 三角形去重用的集合整批复用，缓冲区在多批查询之间复用时，只有第一次扩容会分配。
+// shapes和输出缓冲区长度不一致时只处理较短的那部分，多出来的输出保持原样，
+// 返回值是实际处理的查询数
+impl BVHNode {
+    pub fn count_overlapped_leaves<S: Shape>(bvh: &Arc<Self>, shape: &S) -> usize {
+        Self::count_overlapped_leaves_with_stats(bvh, shape, &mut QueryStats::default())
+    }
+
+    pub fn count_overlapped_leaves_with_stats<S: Shape>(
+        bvh: &Arc<Self>,
+        shape: &S,
+        stats: &mut QueryStats,
+    ) -> usize {
+        count_overlapped_leaves(bvh, shape, stats)
+    }
+
+    // 和probe_shape的统计口径一致，累加到probe上
+    pub fn probe_into<S: Shape>(bvh: &Arc<Self>, shape: &S, probe: &mut BVHProbe) {
+        Self::probe_into_with_stats(bvh, shape, probe, &mut QueryStats::default());
+    }
+
+    pub fn probe_into_with_stats<S: Shape>(
+        bvh: &Arc<Self>,
+        shape: &S,
+        probe: &mut BVHProbe,
+        stats: &mut QueryStats,
+    ) {
+        probe_into(bvh, shape, probe, &mut HashSet::new(), stats);
+    }
+
+    pub fn collect_overlapped_leaves<'a, S: Shape>(
+        bvh: &'a Arc<Self>,
+        shape: &S,
+        leaves: &mut Vec<&'a Self>,
+    ) {
+        Self::collect_overlapped_leaves_with_stats(bvh, shape, leaves, &mut QueryStats::default());
+    }
+
+    pub fn collect_overlapped_leaves_with_stats<'a, S: Shape>(
+        bvh: &'a Arc<Self>,
+        shape: &S,
+        leaves: &mut Vec<&'a Self>,
+        stats: &mut QueryStats,
+    ) {
+        collect_overlapped_leaves(bvh, shape, leaves, |leaf| &**leaf, stats);
+    }
+
+    pub fn collect_overlapped_tris<S: Shape>(bvh: &Arc<Self>, shape: &S, tris: &mut Vec<TriIndex>) {
+        Self::collect_overlapped_tris_with_stats(bvh, shape, tris, &mut QueryStats::default());
+    }
+
+    pub fn collect_overlapped_tris_with_stats<S: Shape>(
+        bvh: &Arc<Self>,
+        shape: &S,
+        tris: &mut Vec<TriIndex>,
+        stats: &mut QueryStats,
+    ) {
+        collect_overlapped_tris(bvh, shape, tris, &mut HashSet::new(), stats);
+    }
+
+    // counts[i]是shapes[i]碰到的叶子数
+    pub fn count_overlapped_leaves_batch<S: Shape>(
+        bvh: &Arc<Self>,
+        shapes: &[S],
+        counts: &mut [usize],
+    ) -> usize {
+        Self::count_overlapped_leaves_batch_with_stats(
+            bvh,
+            shapes,
+            counts,
+            &mut QueryStats::default(),
+        )
+    }
+
+    // 批量查询的stats是整批的总和
+    pub fn count_overlapped_leaves_batch_with_stats<S: Shape>(
+        bvh: &Arc<Self>,
+        shapes: &[S],
+        counts: &mut [usize],
+        stats: &mut QueryStats,
+    ) -> usize {
+        count_overlapped_leaves_batch(bvh, shapes, counts, stats)
+    }
+
+    pub fn probe_batch<S: Shape>(bvh: &Arc<Self>, shapes: &[S], probes: &mut [BVHProbe]) -> usize {
+        Self::probe_batch_with_stats(bvh, shapes, probes, &mut QueryStats::default())
+    }
+
+    pub fn probe_batch_with_stats<S: Shape>(
+        bvh: &Arc<Self>,
+        shapes: &[S],
+        probes: &mut [BVHProbe],
+        stats: &mut QueryStats,
+    ) -> usize {
+        probe_batch(bvh, shapes, probes, stats)
+    }
+
+    // 所有查询的叶子依次拼在leaves里，shapes[i]的结果是leaves[ranges[i].clone()]
+    pub fn get_overlapped_leaves_batch<'a, S: Shape>(
+        bvh: &'a Arc<Self>,
+        shapes: &[S],
+        leaves: &mut Vec<&'a Self>,
+        ranges: &mut [Range<usize>],
+    ) -> usize {
+        Self::get_overlapped_leaves_batch_with_stats(
+            bvh,
+            shapes,
+            leaves,
+            ranges,
+            &mut QueryStats::default(),
+        )
+    }
+
+    pub fn get_overlapped_leaves_batch_with_stats<'a, S: Shape>(
+        bvh: &'a Arc<Self>,
+        shapes: &[S],
+        leaves: &mut Vec<&'a Self>,
+        ranges: &mut [Range<usize>],
+        stats: &mut QueryStats,
+    ) -> usize {
+        get_overlapped_leaves_batch(bvh, shapes, leaves, ranges, |leaf| &**leaf, stats)
+    }
+
+    pub fn get_overlapped_tris_batch<S: Shape>(
+        bvh: &Arc<Self>,
+        shapes: &[S],
+        tris: &mut Vec<TriIndex>,
+        ranges: &mut [Range<usize>],
+    ) -> usize {
+        Self::get_overlapped_tris_batch_with_stats(
+            bvh,
+            shapes,
+            tris,
+            ranges,
+            &mut QueryStats::default(),
+        )
+    }
+
+    pub fn get_overlapped_tris_batch_with_stats<S: Shape>(
+        bvh: &Arc<Self>,
+        shapes: &[S],
+        tris: &mut Vec<TriIndex>,
+        ranges: &mut [Range<usize>],
+        stats: &mut QueryStats,
+    ) -> usize {
+        get_overlapped_tris_batch(bvh, shapes, tris, ranges, stats)
+    }
+
+    pub(crate) fn traverse_shape<'a, S, L>(
+        bvh: &'a Arc<Self>,
+        shape: &S,
+        leaf: L,
+        stats: &mut QueryStats,
+    ) -> bool
+    where
+        S: Shape,
+        L: FnMut(&'a Arc<Self>, &mut QueryStats) -> VisitResult,
+    {
+        traverse_shape(bvh, shape, leaf, stats)
+    }
+}
+
+// 和BVHNode的同名查询一样，叶子用节点下标表示
+impl FlatBVH {
+    pub fn count_overlapped_leaves<S: Shape>(&self, shape: &S) -> usize {
+        self.count_overlapped_leaves_with_stats(shape, &mut QueryStats::default())
+    }
+
+    pub fn count_overlapped_leaves_with_stats<S: Shape>(
+        &self,
+        shape: &S,
+        stats: &mut QueryStats,
+    ) -> usize {
+        count_overlapped_leaves(self.node_ref(0), shape, stats)
+    }
+
+    pub fn probe_into<S: Shape>(&self, shape: &S, probe: &mut BVHProbe) {
+        self.probe_into_with_stats(shape, probe, &mut QueryStats::default());
+    }
+
+    pub fn probe_into_with_stats<S: Shape>(
+        &self,
+        shape: &S,
+        probe: &mut BVHProbe,
+        stats: &mut QueryStats,
+    ) {
+        probe_into(self.node_ref(0), shape, probe, &mut HashSet::new(), stats);
+    }
+
+    pub fn collect_overlapped_leaves<S: Shape>(&self, shape: &S, leaves: &mut Vec<usize>) {
+        self.collect_overlapped_leaves_with_stats(shape, leaves, &mut QueryStats::default());
+    }
+
+    pub fn collect_overlapped_leaves_with_stats<S: Shape>(
+        &self,
+        shape: &S,
+        leaves: &mut Vec<usize>,
+        stats: &mut QueryStats,
+    ) {
+        collect_overlapped_leaves(self.node_ref(0), shape, leaves, |leaf| leaf.idx, stats);
+    }
+
+    pub fn collect_overlapped_tris<S: Shape>(&self, shape: &S, tris: &mut Vec<TriIndex>) {
+        self.collect_overlapped_tris_with_stats(shape, tris, &mut QueryStats::default());
+    }
+
+    pub fn collect_overlapped_tris_with_stats<S: Shape>(
+        &self,
+        shape: &S,
+        tris: &mut Vec<TriIndex>,
+        stats: &mut QueryStats,
+    ) {
+        collect_overlapped_tris(self.node_ref(0), shape, tris, &mut HashSet::new(), stats);
+    }
+
+    pub fn count_overlapped_leaves_batch<S: Shape>(
+        &self,
+        shapes: &[S],
+        counts: &mut [usize],
+    ) -> usize {
+        self.count_overlapped_leaves_batch_with_stats(shapes, counts, &mut QueryStats::default())
+    }
+
+    pub fn count_overlapped_leaves_batch_with_stats<S: Shape>(
+        &self,
+        shapes: &[S],
+        counts: &mut [usize],
+        stats: &mut QueryStats,
+    ) -> usize {
+        count_overlapped_leaves_batch(self.node_ref(0), shapes, counts, stats)
+    }
+
+    pub fn probe_batch<S: Shape>(&self, shapes: &[S], probes: &mut [BVHProbe]) -> usize {
+        self.probe_batch_with_stats(shapes, probes, &mut QueryStats::default())
+    }
+
+    pub fn probe_batch_with_stats<S: Shape>(
+        &self,
+        shapes: &[S],
+        probes: &mut [BVHProbe],
+        stats: &mut QueryStats,
+    ) -> usize {
+        probe_batch(self.node_ref(0), shapes, probes, stats)
+    }
+
+    pub fn get_overlapped_leaves_batch<S: Shape>(
+        &self,
+        shapes: &[S],
+        leaves: &mut Vec<usize>,
+        ranges: &mut [Range<usize>],
+    ) -> usize {
+        self.get_overlapped_leaves_batch_with_stats(
+            shapes,
+            leaves,
+            ranges,
+            &mut QueryStats::default(),
+        )
+    }
+
+    pub fn get_overlapped_leaves_batch_with_stats<S: Shape>(
+        &self,
+        shapes: &[S],
+        leaves: &mut Vec<usize>,
+        ranges: &mut [Range<usize>],
+        stats: &mut QueryStats,
+    ) -> usize {
+        get_overlapped_leaves_batch(
+            self.node_ref(0),
+            shapes,
+            leaves,
+            ranges,
+            |leaf: FlatNodeRef| leaf.idx,
+            stats,
+        )
+    }
+
+    pub fn get_overlapped_tris_batch<S: Shape>(
+        &self,
+        shapes: &[S],
+        tris: &mut Vec<TriIndex>,
+        ranges: &mut [Range<usize>],
+    ) -> usize {
+        self.get_overlapped_tris_batch_with_stats(shapes, tris, ranges, &mut QueryStats::default())
+    }
+
+    pub fn get_overlapped_tris_batch_with_stats<S: Shape>(
+        &self,
+        shapes: &[S],
+        tris: &mut Vec<TriIndex>,
+        ranges: &mut [Range<usize>],
+        stats: &mut QueryStats,
+    ) -> usize {
+        get_overlapped_tris_batch(self.node_ref(0), shapes, tris, ranges, stats)
+    }
+}
+
+pub(crate) fn count_overlapped_leaves<'a, N: BVHNodeRef<'a>, S: Shape>(
+    root: N,
+    shape: &S,
+    stats: &mut QueryStats,
+) -> usize {
+    let mut count = 0_usize;
+    traverse_shape(
+        root,
+        shape,
+        |_, _| {
+            count += 1;
+            VisitResult::Continue
+        },
+        stats,
+    );
+    count
+}
+
+// tested记这次查询测过的三角形，同一个三角形的其他引用只计入candidate_tris，不再测试
+pub(crate) fn probe_into<'a, N: BVHNodeRef<'a>, S: Shape>(
+    root: N,
+    shape: &S,
+    probe: &mut BVHProbe,
+    tested: &mut HashSet<TriKey>,
+    stats: &mut QueryStats,
+) {
+    tested.clear();
+    traverse_shape(
+        root,
+        shape,
+        |leaf, stats| {
+            probe.leaves += 1;
+            probe.candidate_tris += leaf.tris().len();
+            for tri_index in leaf.tris() {
+                if !tested.insert(tri_key(tri_index)) {
+                    continue;
+                }
+                stats.tri_tests += 1;
+                if shape.intersect_with_tri(&tri_index.to_tri(leaf.vtx_buf())) {
+                    stats.hits += 1;
+                    probe.hit_tris += 1;
+                }
+            }
+            VisitResult::Continue
+        },
+        stats,
+    );
+}
+
+fn collect_overlapped_leaves<'a, N, S, T, F>(
+    root: N,
+    shape: &S,
+    leaves: &mut Vec<T>,
+    to_leaf: F,
+    stats: &mut QueryStats,
+) where
+    N: BVHNodeRef<'a>,
+    S: Shape,
+    F: Fn(N) -> T,
+{
+    traverse_shape(
+        root,
+        shape,
+        |leaf, _| {
+            leaves.push(to_leaf(leaf));
+            VisitResult::Continue
+        },
+        stats,
+    );
+}
+
+pub(crate) fn collect_overlapped_tris<'a, N: BVHNodeRef<'a>, S: Shape>(
+    root: N,
+    shape: &S,
+    tris: &mut Vec<TriIndex>,
+    tested: &mut HashSet<TriKey>,
+    stats: &mut QueryStats,
+) {
+    tested.clear();
+    traverse_shape(
+        root,
+        shape,
+        |leaf, stats| {
+            for tri_index in leaf.tris() {
+                if !tested.insert(tri_key(tri_index)) {
+                    continue;
+                }
+                stats.tri_tests += 1;
+                if shape.intersect_with_tri(&tri_index.to_tri(leaf.vtx_buf())) {
+                    stats.hits += 1;
+                    tris.push(tri_index.clone());
+                }
+            }
+            VisitResult::Continue
+        },
+        stats,
+    );
+}
+
+fn count_overlapped_leaves_batch<'a, N: BVHNodeRef<'a>, S: Shape>(
+    root: N,
+    shapes: &[S],
+    counts: &mut [usize],
+    stats: &mut QueryStats,
+) -> usize {
+    for (shape, count) in shapes.iter().zip(counts.iter_mut()) {
+        *count = count_overlapped_leaves(root, shape, stats);
+    }
+    shapes.len().min(counts.len())
+}
+
+fn probe_batch<'a, N: BVHNodeRef<'a>, S: Shape>(
+    root: N,
+    shapes: &[S],
+    probes: &mut [BVHProbe],
+    stats: &mut QueryStats,
+) -> usize {
+    let mut tested = HashSet::<TriKey>::new();
+    for (shape, probe) in shapes.iter().zip(probes.iter_mut()) {
+        *probe = BVHProbe::default();
+        probe_into(root, shape, probe, &mut tested, stats);
+    }
+    shapes.len().min(probes.len())
+}
+
+fn get_overlapped_leaves_batch<'a, N, S, T, F>(
+    root: N,
+    shapes: &[S],
+    leaves: &mut Vec<T>,
+    ranges: &mut [Range<usize>],
+    to_leaf: F,
+    stats: &mut QueryStats,
+) -> usize
+where
+    N: BVHNodeRef<'a>,
+    S: Shape,
+    F: Fn(N) -> T,
+{
+    leaves.clear();
+    for (shape, range) in shapes.iter().zip(ranges.iter_mut()) {
+        let start = leaves.len();
+        collect_overlapped_leaves(root, shape, leaves, &to_leaf, stats);
+        *range = start..leaves.len();
+    }
+    shapes.len().min(ranges.len())
+}
+
+fn get_overlapped_tris_batch<'a, N: BVHNodeRef<'a>, S: Shape>(
+    root: N,
+    shapes: &[S],
+    tris: &mut Vec<TriIndex>,
+    ranges: &mut [Range<usize>],
+    stats: &mut QueryStats,
+) -> usize {
+    tris.clear();
+    let mut tested = HashSet::<TriKey>::new();
+    for (shape, range) in shapes.iter().zip(ranges.iter_mut()) {
+        let start = tris.len();
+        collect_overlapped_tris(root, shape, tris, &mut tested, stats);
+        *range = start..tris.len();
+    }
+    shapes.len().min(ranges.len())
+}
+
+// 形状碰不到的子树整个跳过
+pub(crate) fn cull<S: Shape>(shape: &S, aabb: &AABB) -> VisitResult {
+    if shape.intersect_with_aabb(aabb) {
+        VisitResult::Continue
+    } else {
+        VisitResult::Skip
+    }
+}
+
+// 形状查询共用的遍历：节点用cull剪枝并计入包围盒测试，
+// leaf自己累加三角形测试数和命中数
+pub(crate) fn traverse_shape<'a, N, S, L>(
+    root: N,
+    shape: &S,
+    leaf: L,
+    stats: &mut QueryStats,
+) -> bool
+where
+    N: BVHNodeRef<'a>,
+    S: Shape,
+    L: FnMut(N, &mut QueryStats) -> VisitResult,
+{
+    let mut visitor = ShapeVisitor {
+        shape,
+        leaf,
+        stats: QueryStats::default(),
+    };
+    let stopped = traverse_node(root, &mut visitor, stats);
+    *stats += visitor.stats;
+    stopped
+}
+
+struct ShapeVisitor<'s, S, L> {
+    shape: &'s S,
+    leaf: L,
+    stats: QueryStats,
+}
+
+impl<'a, N, S, L> BVHVisitor<'a, N> for ShapeVisitor<'_, S, L>
+where
+    N: BVHNodeRef<'a>,
+    S: Shape,
+    L: FnMut(N, &mut QueryStats) -> VisitResult,
+{
+    fn visit_node(&mut self, node: N) -> VisitResult {
+        self.stats.aabb_tests += 1;
+        cull(self.shape, node.aabb())
+    }
+
+    fn visit_leaf(&mut self, leaf: N) -> VisitResult {
+        (self.leaf)(leaf, &mut self.stats)
+    }
+}
@@ -1,20 +1,54 @@
 #![allow(dead_code)]
 
-use crate::{aabb, prelude::*};
+use crate::{aabb, lbvh, physx, prelude::*, sbvh};
 use std::collections::btree_set::Intersection;
-use std::rc::Rc;
 use std::sync::Arc;
 
 pub mod prelude {
     pub use super::BVHNode;
     pub use super::BVHNodeIntersectionResult;
+    pub use super::BVHProbe;
+    pub use super::BVHSplitMethod;
     pub use super::BVHSubdivideConfig;
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BVHSplitMethod {
+    // 按AABB中心一分为二
+    Naive,
+    // 扫描全部候选切分面，取表面积启发式代价最小的
+    SAH,
+    // 重心分桶后只在桶边界上求表面积启发式代价
+    BinnedSAH,
+    // 按重心排序后从中位数处对半分
+    ObjectMedian,
+    // 按重心包围盒的中心面切分
+    CentroidMedian,
+    // 允许把三角形裁开放进多个叶子的空间切分(SBVH)
+    Spatial,
+    // 按重心莫顿码排序后直接生成层级(LBVH)，建得快但质量一般
+    LBVH,
+    // 模拟PhysX BVH33中段(RTree)：4叉、f32包围盒，叶子大小由meshSizePerformanceTradeoff决定
+    PhysXBVH33,
+    // 模拟PhysX BVH34中段(BV4)：4叉、16位量化包围盒，叶子最多15个三角形
+    PhysXBVH34,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct BVHSubdivideConfig {
     pub num_tris_per_leaf: usize,
     pub max_tris_per_leaf: usize,
+    pub split_method: BVHSplitMethod,
+    pub sah_traversal_cost: f64,
+    pub sah_intersection_cost: f64,
+    pub num_bins: usize,
+    pub spatial_split_budget: f64,
+    pub spatial_split_alpha: f64,
+    pub morton_code: BVHMortonCode,
+    pub mesh_size_performance_tradeoff: f64,
+    pub num_prims_per_leaf: usize,
+    pub node_width: usize,
+    pub num_threads: usize,
 }
 
 impl BVHSubdivideConfig {
@@ -33,26 +67,47 @@ impl Default for BVHSubdivideConfig {
         Self {
             num_tris_per_leaf: 4,
             max_tris_per_leaf: 15,
+            split_method: BVHSplitMethod::Naive,
+            sah_traversal_cost: 1.0,
+            sah_intersection_cost: 1.0,
+            num_bins: 16,
+            spatial_split_budget: 0.3,
+            spatial_split_alpha: 1e-5,
+            morton_code: BVHMortonCode::Bits30,
+            mesh_size_performance_tradeoff: 0.55,
+            num_prims_per_leaf: 4,
+            node_width: 2,
+            num_threads: 1,
         }
     }
 }
 
+// 一次探测的中段候选数和窄相位真实接触数
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BVHProbe {
+    pub leaves: usize,
+    // 碰到的叶子里的三角形引用数，空间切分时同一个三角形可能算多次
+    pub candidate_tris: usize,
+    // 去重后真正接触的三角形数
+    pub hit_tris: usize,
+}
+
 #[derive(Clone)]
 pub struct BVHNode {
-    pub vtx_buf: Rc<Vec<Vec3>>,
+    pub vtx_buf: Arc<Vec<Vec3>>,
     pub idx_buf: Vec<TriIndex>,
     pub aabb: AABB,
-    pub children: Vec<Rc<BVHNode>>,
+    pub children: Vec<Arc<BVHNode>>,
 }
 
 pub enum BVHNodeIntersectionResult {
-    One(Rc<BVHNode>),
-    Multiple(Vec<Rc<BVHNode>>),
+    One(Arc<BVHNode>),
+    Multiple(Vec<Arc<BVHNode>>),
     Zero,
 }
 
 impl BVHNodeIntersectionResult {
-    pub fn to_leaves(res: Self) -> Vec<Rc<BVHNode>> {
+    pub fn to_leaves(res: Self) -> Vec<Arc<BVHNode>> {
         match res {
             BVHNodeIntersectionResult::Zero => vec![],
             BVHNodeIntersectionResult::One(ele) => vec![ele],
@@ -62,36 +117,58 @@ impl BVHNodeIntersectionResult {
 }
 
 impl BVHNode {
-    pub fn get_interseced_leaves(bvh: Rc<Self>, aabb: &AABB) -> BVHNodeIntersectionResult {
-        if bvh.aabb.intersect_with_aabb(aabb) {
-            if bvh.is_leaf() {
-                return BVHNodeIntersectionResult::One(bvh);
-            } else {
-                let mut ret = vec![];
-                for child in bvh.children.iter() {
-                    let intersection = Self::get_interseced_leaves(child.clone(), aabb);
-                    match intersection {
-                        BVHNodeIntersectionResult::One(ele) => {
-                            ret.push(ele);
-                        }
-                        BVHNodeIntersectionResult::Multiple(eles) => {
-                            ret.extend(eles);
-                        }
-                        _ => {}
-                    }
-                }
-                return match ret.len() {
-                    0 => BVHNodeIntersectionResult::Zero,
-                    1 => BVHNodeIntersectionResult::One(ret[0].clone()),
-                    _ => BVHNodeIntersectionResult::Multiple(ret),
-                };
-            }
+    pub fn get_interseced_leaves(bvh: Arc<Self>, aabb: &AABB) -> BVHNodeIntersectionResult {
+        Self::get_interseced_leaves_with_stats(bvh, aabb, &mut QueryStats::default())
+    }
+
+    pub fn get_interseced_leaves_with_stats(
+        bvh: Arc<Self>,
+        aabb: &AABB,
+        stats: &mut QueryStats,
+    ) -> BVHNodeIntersectionResult {
+        let mut ret = Vec::<Arc<Self>>::new();
+        Self::traverse_shape(
+            &bvh,
+            aabb,
+            |leaf, _| {
+                ret.push(leaf.clone());
+                VisitResult::Continue
+            },
+            stats,
+        );
+        match ret.len() {
+            0 => BVHNodeIntersectionResult::Zero,
+            1 => BVHNodeIntersectionResult::One(ret.pop().unwrap()),
+            _ => BVHNodeIntersectionResult::Multiple(ret),
         }
-        BVHNodeIntersectionResult::Zero
     }
 
-    pub fn get_all_nodes(bvh: Rc<Self>) -> Vec<Rc<Self>> {
-        let mut ret = Vec::<Rc<Self>>::new();
+    pub fn get_intersected_tris(bvh: Arc<Self>, aabb: &AABB) -> Vec<TriIndex> {
+        Self::get_intersected_tris_with_stats(bvh, aabb, &mut QueryStats::default())
+    }
+
+    pub fn get_intersected_tris_with_stats(
+        bvh: Arc<Self>,
+        aabb: &AABB,
+        stats: &mut QueryStats,
+    ) -> Vec<TriIndex> {
+        let mut ret = Vec::<TriIndex>::new();
+        Self::collect_overlapped_tris_with_stats(&bvh, aabb, &mut ret, stats);
+        ret
+    }
+
+    pub fn probe(bvh: Arc<Self>, aabb: &AABB) -> BVHProbe {
+        Self::probe_with_stats(bvh, aabb, &mut QueryStats::default())
+    }
+
+    pub fn probe_with_stats(bvh: Arc<Self>, aabb: &AABB, stats: &mut QueryStats) -> BVHProbe {
+        let mut ret = BVHProbe::default();
+        Self::probe_into_with_stats(&bvh, aabb, &mut ret, stats);
+        ret
+    }
+
+    pub fn get_all_nodes(bvh: Arc<Self>) -> Vec<Arc<Self>> {
+        let mut ret = Vec::<Arc<Self>>::new();
         let mut ptr_stack = vec![bvh];
         while !ptr_stack.is_empty() {
             while let Some(ptr) = ptr_stack.pop() {
@@ -104,8 +181,8 @@ impl BVHNode {
         ret
     }
 
-    pub fn get_all_leaves(bvh: Rc<Self>) -> Vec<Rc<Self>> {
-        let mut ret = Vec::<Rc<Self>>::new();
+    pub fn get_all_leaves(bvh: Arc<Self>) -> Vec<Arc<Self>> {
+        let mut ret = Vec::<Arc<Self>>::new();
         let mut ptr_stack = vec![bvh];
         while !ptr_stack.is_empty() {
             while let Some(ptr) = ptr_stack.pop() {
@@ -120,273 +197,171 @@ impl BVHNode {
         ret
     }
 
+    // 兄弟节点包围盒两两相交的面积之和，以根节点表面积归一化
+    pub fn sibling_overlap(bvh: Arc<Self>) -> f64 {
+        let root_area = bvh.aabb.surface_area();
+        let mut overlap = 0.0f64;
+        for node in Self::get_all_nodes(bvh) {
+            for (idx, lhs) in node.children.iter().enumerate() {
+                for rhs in node.children[idx + 1..].iter() {
+                    if let Some(aabb) = lhs.aabb.intersection(&rhs.aabb) {
+                        overlap += aabb.surface_area();
+                    }
+                }
+            }
+        }
+        overlap / root_area
+    }
+
+    // 所有叶子引用的三角形总数，空间切分时会比原始三角形数多
+    pub fn reference_count(bvh: Arc<Self>) -> usize {
+        Self::get_all_leaves(bvh)
+            .iter()
+            .map(|leaf| leaf.idx_buf.len())
+            .sum()
+    }
+
     pub fn directional_hit(
-        bvh: Rc<BVHNode>,
+        bvh: Arc<BVHNode>,
         block_size: &Vec3,
         start: &Vec3,
         end: &Vec3,
         step_into: f64,
         break_on_hit: bool,
     ) -> usize {
-        let mut local_peak = 0_usize;
-        let mut local_pos = *start;
-        let half_aabb_size = *block_size / Vec3::new(2.0, 2.0, 2.0);
-        let dist = end.distance_to(start);
-        let dir = start.direction_to(end);
-        let nchunks = (dist / step_into).ceil() as usize;
-        for _a in 0..nchunks {
-            let min = local_pos - half_aabb_size;
-            let max = local_pos + half_aabb_size;
-            let aabb = AABB::new(&min, &max);
-            let intersection = Self::get_interseced_leaves(bvh.clone(), &aabb);
-            let leaves = BVHNodeIntersectionResult::to_leaves(intersection);
-            local_peak = local_peak.max(leaves.len());
-            if break_on_hit {
-                break;
-            }
-            local_pos.move_towards(&dir, step_into);
-        }
-        local_peak
-    }
-
-    pub fn block_overlap_peak(bvh: Rc<Self>, step: f64) -> usize {
-        // 开始坐标向外括了半格
-        // 再加上directional_hit以该点为中心，内外各半格，刚好向外括了一格
-        let halfstep = step / 2.0;
-        let mut local_aabb = bvh.aabb.clone();
-        local_aabb.expand(&Vec3::new(step, step, step));
-        let mut peak = 0_usize;
-        let mut curx = local_aabb.min;
-        while curx.x < local_aabb.max.x {
-            let mut cury = curx;
-            while cury.y < local_aabb.max.y {
-                let mut point_start = cury;
-                let mut point_end = cury;
-                point_start.z = bvh.aabb.min.z;
-                point_end.z = bvh.aabb.max.z;
-                let local_peak = Self::directional_hit(
-                    bvh.clone(),
-                    &Vec3::new(step, step, step),
-                    &point_start,
-                    &point_end,
-                    halfstep,
-                    false,
-                );
-                peak = peak.max(local_peak);
-                cury.y += halfstep;
-            }
-            curx.x += halfstep;
-        }
-        peak
-    }
-
-    pub fn surface_hit_peak(bvh: Rc<Self>, step: f64, block_size: &Vec3) -> usize {
-        enum Axis {
-            X,
-            Y,
-            Z,
-        }
-
-        let axis_planar_hit =
-            |axis: Axis, bvh: Rc<BVHNode>, block_size: &Vec3, step_into: f64| -> usize {
-                // 开始坐标向外括了半格
-                // 再加上directional_hit以该点为中心，内外各半格，刚好向外括了一格
-                let half_block_size = *block_size / Vec3::new(2.0, 2.0, 2.0);
-                let mut local_aabb = bvh.aabb.clone();
-                local_aabb.expand(block_size);
-                let ext = bvh.aabb.extent();
-                let start = local_aabb.min;
-                let end = local_aabb.max;
-                let mut peak = 0_usize;
-                let mut point1 = start;
-                while match axis {
-                    Axis::X => point1.y < end.y,
-                    Axis::Y => point1.z < end.z,
-                    Axis::Z => point1.x < end.x,
-                } {
-                    let mut point2 = point1;
-                    while match axis {
-                        Axis::X => point2.z < end.z,
-                        Axis::Y => point2.x < end.x,
-                        Axis::Z => point2.y < end.y,
-                    } {
-                        let mut point2_back = point2;
-                        match axis {
-                            Axis::X => {
-                                point2_back.x += ext.x;
-                            }
-                            Axis::Y => {
-                                point2_back.y += ext.y;
-                            }
-                            Axis::Z => {
-                                point2_back.z += ext.z;
-                            }
-                        }
-                        peak = peak.max(Self::directional_hit(
-                            bvh.clone(),
-                            block_size,
-                            &point2,
-                            &point2_back,
-                            step_into,
-                            true,
-                        ));
-                        peak = peak.max(Self::directional_hit(
-                            bvh.clone(),
-                            block_size,
-                            &point2_back,
-                            &point2,
-                            step_into,
-                            true,
-                        ));
-                        match axis {
-                            Axis::X => {
-                                point2.z += half_block_size.z;
-                            }
-                            Axis::Y => {
-                                point2.x += half_block_size.x;
-                            }
-                            Axis::Z => {
-                                point2.y += half_block_size.y;
-                            }
-                        }
-                    }
-                    match axis {
-                        Axis::X => {
-                            point1.y += half_block_size.y;
-                        }
-                        Axis::Y => {
-                            point1.z += half_block_size.z;
-                        }
-                        Axis::Z => {
-                            point1.x += half_block_size.x;
-                        }
-                    }
-                }
-                peak
-            };
+        let leaf_count = |aabb: &AABB| Self::count_overlapped_leaves(&bvh, aabb);
+        directional_hit_with(&leaf_count, block_size, start, end, step_into, break_on_hit)
+    }
+
+    pub fn block_overlap_peak(bvh: Arc<Self>, step: f64) -> usize {
+        Self::block_overlap_peak_parallel(bvh, step, 1)
+    }
+
+    pub fn block_overlap_peak_parallel(bvh: Arc<Self>, step: f64, num_workers: usize) -> usize {
+        let leaf_count = |aabb: &AABB| Self::count_overlapped_leaves(&bvh, aabb);
+        block_overlap_peak_with(&bvh.aabb, &leaf_count, step, num_workers)
+    }
+
+    // 和block_overlap_peak一样的扫描，但统计的是真正和探测盒相交的三角形数
+    pub fn block_overlap_tri_peak(bvh: Arc<Self>, step: f64) -> usize {
+        Self::block_overlap_tri_peak_parallel(bvh, step, 1)
+    }
 
-        let mut peak = 0_usize;
+    pub fn block_overlap_tri_peak_parallel(bvh: Arc<Self>, step: f64, num_workers: usize) -> usize {
+        let hit_count = |aabb: &AABB| {
+            let mut probe = BVHProbe::default();
+            Self::probe_into(&bvh, aabb, &mut probe);
+            probe.hit_tris
+        };
+        block_overlap_peak_with(&bvh.aabb, &hit_count, step, num_workers)
+    }
 
-        peak = peak.max(axis_planar_hit(Axis::X, bvh.clone(), block_size, step));
+    pub fn surface_hit_peak(bvh: Arc<Self>, step: f64, block_size: &Vec3) -> usize {
+        Self::surface_hit_peak_parallel(bvh, step, block_size, 1)
+    }
 
-        peak = peak.max(axis_planar_hit(Axis::Y, bvh.clone(), block_size, step));
+    pub fn surface_hit_peak_parallel(
+        bvh: Arc<Self>,
+        step: f64,
+        block_size: &Vec3,
+        num_workers: usize,
+    ) -> usize {
+        let leaf_count = |aabb: &AABB| Self::count_overlapped_leaves(&bvh, aabb);
+        surface_hit_peak_with(&bvh.aabb, &leaf_count, step, block_size, num_workers)
+    }
 
-        peak = peak.max(axis_planar_hit(Axis::Z, bvh.clone(), block_size, step));
+    pub fn surface_hit_tri_peak(bvh: Arc<Self>, step: f64, block_size: &Vec3) -> usize {
+        Self::surface_hit_tri_peak_parallel(bvh, step, block_size, 1)
+    }
 
-        peak
+    pub fn surface_hit_tri_peak_parallel(
+        bvh: Arc<Self>,
+        step: f64,
+        block_size: &Vec3,
+        num_workers: usize,
+    ) -> usize {
+        let hit_count = |aabb: &AABB| {
+            let mut probe = BVHProbe::default();
+            Self::probe_into(&bvh, aabb, &mut probe);
+            probe.hit_tris
+        };
+        surface_hit_peak_with(&bvh.aabb, &hit_count, step, block_size, num_workers)
     }
 
-    pub fn new(vtx_buf: Rc<Vec<Vec3>>, idx_buf: Vec<TriIndex>) -> Self {
+    pub fn new(vtx_buf: Arc<Vec<Vec3>>, idx_buf: Vec<TriIndex>) -> Self {
         let mut ret = Self {
             vtx_buf,
             idx_buf,
             aabb: AABB::default(),
-            children: Vec::<Rc<BVHNode>>::new(),
+            children: Vec::<Arc<BVHNode>>::new(),
         };
         ret.recalc_aabb();
         ret
     }
 
+    pub fn with_aabb(vtx_buf: Arc<Vec<Vec3>>, idx_buf: Vec<TriIndex>, aabb: AABB) -> Self {
+        let mut ret = Self {
+            vtx_buf,
+            idx_buf,
+            aabb,
+            children: Vec::<Arc<BVHNode>>::new(),
+        };
+        ret.pad_aabb();
+        ret
+    }
+
     pub fn is_leaf(&self) -> bool {
         self.children.is_empty()
     }
 
     pub fn recalc_aabb(&mut self) {
-        let mut points = Vec::<Vec3>::new();
-        for tidx in self.idx_buf.iter() {
-            let tri = tidx.to_tri(self.vtx_buf.clone());
-            points.push(tri.pt0);
-            points.push(tri.pt1);
-            points.push(tri.pt2);
-        }
-        self.aabb = AABB::from_points(&points);
-        let extent = self.aabb.extent();
-        let dist = Vec3::new(
-            if extent.x < 0.1 { 0.1 } else { 0.0 },
-            if extent.y < 0.1 { 0.1 } else { 0.0 },
-            if extent.z < 0.1 { 0.1 } else { 0.0 },
-        );
-        self.aabb.expand(&dist);
+        self.aabb = tris_aabb(&self.vtx_buf, &self.idx_buf);
     }
 
-    pub fn subdivide(&mut self, cfg: BVHSubdivideConfig) {
-        if cfg.can_subsubdivide(self) {
-            let mut valid = true;
-
-            // 先从最大的个轴上一分为二
-            {
-                let axis = self.aabb.largest_axis();
-                let (pos_tri_idx, neg_tri_idx) = local_split(self, axis);
-                if pos_tri_idx.is_empty() || pos_tri_idx.len() == self.idx_buf.len() {
-                    valid = false;
-                }
-                if valid {
-                    let mut child_pos = BVHNode::new(self.vtx_buf.clone(), pos_tri_idx);
-                    child_pos.subdivide(cfg);
-                    let mut child_neg = BVHNode::new(self.vtx_buf.clone(), neg_tri_idx);
-                    child_neg.subdivide(cfg);
-                    self.children.push(Rc::new(child_pos));
-                    self.children.push(Rc::new(child_neg));
-                }
-            }
-
-            // 取最平均的轴
-            if !valid {
-                let mut postris = [0.0f64, 0.0f64, 0.0f64];
-                let axises = [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z];
-                for (idx, axis) in axises.into_iter().enumerate() {
-                    let (pos_tri_idx, _) = local_split(self, axis);
-                    postris[idx] = (pos_tri_idx.len() as f64) / (self.idx_buf.len() as f64);
-                }
-                postris[0] -= 0.5;
-                postris[0] *= postris[0];
-                postris[1] -= 0.5;
-                postris[1] *= postris[1];
-                postris[2] -= 0.5;
-                postris[2] *= postris[2];
-                let mut minidx = 0;
-                if postris[1] < postris[minidx] {
-                    minidx = 1;
-                }
-                if postris[2] < postris[minidx] {
-                    minidx = 2;
-                }
+    fn pad_aabb(&mut self) {
+        pad_aabb(&mut self.aabb);
+    }
 
-                let axis = [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z][minidx];
-                let (pos_tri_idx, neg_tri_idx) = local_split(self, axis);
-                if pos_tri_idx.is_empty() || pos_tri_idx.len() == self.idx_buf.len() {
-                    valid = false;
-                }
-                if valid {
-                    let mut child_pos = BVHNode::new(self.vtx_buf.clone(), pos_tri_idx);
-                    let mut child_neg = BVHNode::new(self.vtx_buf.clone(), neg_tri_idx);
-                    child_pos.subdivide(cfg);
-                    child_neg.subdivide(cfg);
-                    self.children.push(Rc::new(child_pos));
-                    self.children.push(Rc::new(child_neg));
-                }
-            }
+    pub(crate) fn split_input(&self) -> SplitInput<'_> {
+        SplitInput {
+            vtx_buf: &self.vtx_buf,
+            idx_buf: &self.idx_buf,
+            aabb: &self.aabb,
+        }
+    }
 
-            // 还不行就对半分
-            if !valid {
-                let tot = self.idx_buf.len();
-                let sep = tot / 2;
-                let mut pos_tri_idx = Vec::<TriIndex>::new();
-                let mut neg_tri_idx = Vec::<TriIndex>::new();
-                for (counter, idx) in self.idx_buf.iter().enumerate() {
-                    if counter <= sep {
-                        pos_tri_idx.push(idx.clone());
-                    } else {
-                        neg_tri_idx.push(idx.clone());
-                    }
-                }
-                let mut child_pos = BVHNode::new(self.vtx_buf.clone(), pos_tri_idx);
-                let mut child_neg = BVHNode::new(self.vtx_buf.clone(), neg_tri_idx);
+    pub fn subdivide(&mut self, cfg: BVHSubdivideConfig) {
+        match cfg.split_method {
+            BVHSplitMethod::Spatial => return sbvh::subdivide(self, cfg),
+            BVHSplitMethod::LBVH => return lbvh::subdivide(self, cfg),
+            BVHSplitMethod::PhysXBVH33 => return physx::subdivide_bvh33(self, cfg),
+            BVHSplitMethod::PhysXBVH34 => return physx::subdivide_bvh34(self, cfg),
+            _ => {}
+        }
+        if cfg.can_subsubdivide(self) {
+            let (pos_tri_idx, neg_tri_idx) = binary_split(&self.split_input(), &cfg);
+            let mut child_pos = BVHNode::new(self.vtx_buf.clone(), pos_tri_idx);
+            let mut child_neg = BVHNode::new(self.vtx_buf.clone(), neg_tri_idx);
+            if cfg.num_threads > 1 {
+                // 线程预算对半分给两棵子树，兄弟子树在各自的线程里同时建
+                let pos_cfg = BVHSubdivideConfig {
+                    num_threads: cfg.num_threads / 2,
+                    ..cfg
+                };
+                let neg_cfg = BVHSubdivideConfig {
+                    num_threads: cfg.num_threads - cfg.num_threads / 2,
+                    ..cfg
+                };
+                std::thread::scope(|scope| {
+                    scope.spawn(|| child_pos.subdivide(pos_cfg));
+                    child_neg.subdivide(neg_cfg);
+                });
+            } else {
                 child_pos.subdivide(cfg);
                 child_neg.subdivide(cfg);
-                self.children.push(Rc::new(child_pos));
-                self.children.push(Rc::new(child_neg));
             }
+            self.children.push(Arc::new(child_pos));
+            self.children.push(Arc::new(child_neg));
         }
     }
 
@@ -428,12 +403,68 @@ impl BVHNode {
     }
 }
 
-fn local_split(bvh: &BVHNode, axis: AABBSplitAxis) -> (Vec<TriIndex>, Vec<TriIndex>) {
+pub(crate) fn tris_aabb(vtx_buf: &Arc<Vec<Vec3>>, idx_buf: &[TriIndex]) -> AABB {
+    let mut points = Vec::<Vec3>::new();
+    for tidx in idx_buf.iter() {
+        let tri = tidx.to_tri(vtx_buf);
+        points.push(tri.pt0);
+        points.push(tri.pt1);
+        points.push(tri.pt2);
+    }
+    let mut aabb = AABB::from_points(&points);
+    pad_aabb(&mut aabb);
+    aabb
+}
+
+// 三角形贴着坐标平面时包围盒会退化成薄片，给一个最小厚度
+fn pad_aabb(aabb: &mut AABB) {
+    let extent = aabb.extent();
+    let dist = Vec3::new(
+        if extent.x < 0.1 { 0.1 } else { 0.0 },
+        if extent.y < 0.1 { 0.1 } else { 0.0 },
+        if extent.z < 0.1 { 0.1 } else { 0.0 },
+    );
+    aabb.expand(&dist);
+}
+
+// 二分切分只看三角形和包围盒，指针树和FlatBVH的直接构建共用同一套切分
+pub(crate) struct SplitInput<'a> {
+    pub vtx_buf: &'a Arc<Vec<Vec3>>,
+    pub idx_buf: &'a [TriIndex],
+    pub aabb: &'a AABB,
+}
+
+pub(crate) fn is_binary_split(split_method: BVHSplitMethod) -> bool {
+    matches!(
+        split_method,
+        BVHSplitMethod::Naive
+            | BVHSplitMethod::SAH
+            | BVHSplitMethod::BinnedSAH
+            | BVHSplitMethod::ObjectMedian
+            | BVHSplitMethod::CentroidMedian
+    )
+}
+
+pub(crate) fn binary_split(
+    bvh: &SplitInput,
+    cfg: &BVHSubdivideConfig,
+) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    match cfg.split_method {
+        BVHSplitMethod::Naive => naive_split(bvh),
+        BVHSplitMethod::SAH => sah_split(bvh, cfg),
+        BVHSplitMethod::BinnedSAH => binned_sah_split(bvh, cfg),
+        BVHSplitMethod::ObjectMedian => object_median_split(bvh),
+        BVHSplitMethod::CentroidMedian => centroid_median_split(bvh),
+        _ => unreachable!(),
+    }
+}
+
+fn local_split(bvh: &SplitInput, axis: AABBSplitAxis) -> (Vec<TriIndex>, Vec<TriIndex>) {
     let mut pos_tri_idx = Vec::<TriIndex>::new();
     let mut neg_tri_idx = Vec::<TriIndex>::new();
-    let (pos_aabb, neg_aabb) = bvh.aabb.split(axis);
+    let (pos_aabb, _) = bvh.aabb.split(axis);
     for tri_index in bvh.idx_buf.iter() {
-        let tri = tri_index.to_tri(bvh.vtx_buf.clone());
+        let tri = tri_index.to_tri(bvh.vtx_buf);
         let tri_aabb = AABB::from_point3(&tri.pt0, &tri.pt1, &tri.pt2);
 
         // 一定不要让一个三角形同时属于两个aabb
@@ -447,3 +478,558 @@ fn local_split(bvh: &BVHNode, axis: AABBSplitAxis) -> (Vec<TriIndex>, Vec<TriInd
     }
     (pos_tri_idx, neg_tri_idx)
 }
+
+// 按重心归属，三角形的包围盒越界也不会把它挤到另一侧
+fn centroid_split(
+    bvh: &SplitInput,
+    bounds: &[(AABB, Vec3)],
+    axis: AABBSplitAxis,
+    plane: f64,
+) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    let mut pos_tri_idx = Vec::<TriIndex>::new();
+    let mut neg_tri_idx = Vec::<TriIndex>::new();
+    for (tri_index, (_, centroid)) in bvh.idx_buf.iter().zip(bounds.iter()) {
+        if centroid.component(axis) >= plane {
+            pos_tri_idx.push(tri_index.clone());
+        } else {
+            neg_tri_idx.push(tri_index.clone());
+        }
+    }
+    (pos_tri_idx, neg_tri_idx)
+}
+
+fn centroid_aabb(bounds: &[(AABB, Vec3)]) -> AABB {
+    let centroids = bounds.iter().map(|(_, c)| *c).collect::<Vec<Vec3>>();
+    AABB::from_points(&centroids)
+}
+
+fn object_median_split(bvh: &SplitInput) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    let bounds = tri_bounds(bvh);
+    let axis = centroid_aabb(&bounds).largest_axis();
+    let order = sort_by_centroid(&bounds, axis);
+    let sep = order.len() / 2;
+    let pos_tri_idx = order[sep..]
+        .iter()
+        .map(|i| bvh.idx_buf[*i].clone())
+        .collect();
+    let neg_tri_idx = order[..sep]
+        .iter()
+        .map(|i| bvh.idx_buf[*i].clone())
+        .collect();
+    (pos_tri_idx, neg_tri_idx)
+}
+
+fn centroid_median_split(bvh: &SplitInput) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    let bounds = tri_bounds(bvh);
+    let caabb = centroid_aabb(&bounds);
+    let axis = caabb.largest_axis();
+    let (pos_tri_idx, neg_tri_idx) =
+        centroid_split(bvh, &bounds, axis, caabb.center().component(axis));
+
+    // 只有重心全部重合时才会有一侧为空
+    if pos_tri_idx.is_empty() || neg_tri_idx.is_empty() {
+        return half_split(bvh);
+    }
+    (pos_tri_idx, neg_tri_idx)
+}
+
+fn naive_split(bvh: &SplitInput) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    let is_valid = |pos_tri_idx: &Vec<TriIndex>| {
+        !(pos_tri_idx.is_empty() || pos_tri_idx.len() == bvh.idx_buf.len())
+    };
+
+    // 先从最大的个轴上一分为二
+    {
+        let axis = bvh.aabb.largest_axis();
+        let (pos_tri_idx, neg_tri_idx) = local_split(bvh, axis);
+        if is_valid(&pos_tri_idx) {
+            return (pos_tri_idx, neg_tri_idx);
+        }
+    }
+
+    // 取最平均的轴
+    {
+        let mut postris = [0.0f64, 0.0f64, 0.0f64];
+        let axises = [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z];
+        for (idx, axis) in axises.into_iter().enumerate() {
+            let (pos_tri_idx, _) = local_split(bvh, axis);
+            postris[idx] = (pos_tri_idx.len() as f64) / (bvh.idx_buf.len() as f64);
+        }
+        postris[0] -= 0.5;
+        postris[0] *= postris[0];
+        postris[1] -= 0.5;
+        postris[1] *= postris[1];
+        postris[2] -= 0.5;
+        postris[2] *= postris[2];
+        let mut minidx = 0;
+        if postris[1] < postris[minidx] {
+            minidx = 1;
+        }
+        if postris[2] < postris[minidx] {
+            minidx = 2;
+        }
+
+        let axis = axises[minidx];
+        let (pos_tri_idx, neg_tri_idx) = local_split(bvh, axis);
+        if is_valid(&pos_tri_idx) {
+            return (pos_tri_idx, neg_tri_idx);
+        }
+    }
+
+    // 还不行就对半分
+    half_split(bvh)
+}
+
+fn half_split(bvh: &SplitInput) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    let tot = bvh.idx_buf.len();
+    let sep = tot / 2;
+    let mut pos_tri_idx = Vec::<TriIndex>::new();
+    let mut neg_tri_idx = Vec::<TriIndex>::new();
+    for (counter, idx) in bvh.idx_buf.iter().enumerate() {
+        if counter <= sep {
+            pos_tri_idx.push(idx.clone());
+        } else {
+            neg_tri_idx.push(idx.clone());
+        }
+    }
+    (pos_tri_idx, neg_tri_idx)
+}
+
+pub(crate) fn tri_bounds(bvh: &SplitInput) -> Vec<(AABB, Vec3)> {
+    bvh.idx_buf
+        .iter()
+        .map(|tri_index| {
+            let tri = tri_index.to_tri(bvh.vtx_buf);
+            (tri.to_aabb(), tri.centroid())
+        })
+        .collect()
+}
+
+pub(crate) fn sah_cost(
+    cfg: &BVHSubdivideConfig,
+    parent_area: f64,
+    left_area: f64,
+    left_count: usize,
+    right_area: f64,
+    right_count: usize,
+) -> f64 {
+    let weighted = left_area * (left_count as f64) + right_area * (right_count as f64);
+    cfg.sah_traversal_cost + cfg.sah_intersection_cost * weighted / parent_area
+}
+
+fn sort_by_centroid(bounds: &[(AABB, Vec3)], axis: AABBSplitAxis) -> Vec<usize> {
+    let mut order = (0..bounds.len()).collect::<Vec<usize>>();
+    order.sort_by(|a, b| {
+        let ca = bounds[*a].1.component(axis);
+        let cb = bounds[*b].1.component(axis);
+        ca.total_cmp(&cb)
+    });
+    order
+}
+
+fn sah_split(bvh: &SplitInput, cfg: &BVHSubdivideConfig) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    let bounds = tri_bounds(bvh);
+    let ntris = bounds.len();
+    let parent_area = bvh.aabb.surface_area();
+
+    // 每个轴按重心排序后扫描所有的切分位置，取代价最小的那个
+    let mut best: Option<(f64, AABBSplitAxis, usize)> = None;
+    for axis in [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z] {
+        let order = sort_by_centroid(&bounds, axis);
+
+        // 从右往左累计包围盒面积
+        let mut right_areas = vec![0.0f64; ntris];
+        let mut acc = bounds[order[ntris - 1]].0.clone();
+        for i in (1..ntris).rev() {
+            acc = acc.merge(&bounds[order[i]].0);
+            right_areas[i] = acc.surface_area();
+        }
+
+        let mut acc = bounds[order[0]].0.clone();
+        for i in 1..ntris {
+            let cost = sah_cost(
+                cfg,
+                parent_area,
+                acc.surface_area(),
+                i,
+                right_areas[i],
+                ntris - i,
+            );
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, i));
+            }
+            acc = acc.merge(&bounds[order[i]].0);
+        }
+    }
+
+    match best {
+        Some((_, axis, sep)) => {
+            let order = sort_by_centroid(&bounds, axis);
+            let pos_tri_idx = order[..sep]
+                .iter()
+                .map(|i| bvh.idx_buf[*i].clone())
+                .collect();
+            let neg_tri_idx = order[sep..]
+                .iter()
+                .map(|i| bvh.idx_buf[*i].clone())
+                .collect();
+            (pos_tri_idx, neg_tri_idx)
+        }
+        None => half_split(bvh),
+    }
+}
+
+fn binned_sah_split(bvh: &SplitInput, cfg: &BVHSubdivideConfig) -> (Vec<TriIndex>, Vec<TriIndex>) {
+    let bounds = tri_bounds(bvh);
+    let nbins = cfg.num_bins.max(2);
+    let parent_area = bvh.aabb.surface_area();
+    let centroid_aabb = centroid_aabb(&bounds);
+
+    let bin_of = |centroid: &Vec3, axis: AABBSplitAxis| -> usize {
+        let min = centroid_aabb.min.component(axis);
+        let span = centroid_aabb.extent().component(axis);
+        let bin = ((centroid.component(axis) - min) / span * (nbins as f64)) as usize;
+        bin.min(nbins - 1)
+    };
+
+    // 只在桶边界上切分，代价只需要扫描nbins - 1次
+    let mut best: Option<(f64, AABBSplitAxis, usize)> = None;
+    for axis in [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z] {
+        if centroid_aabb.extent().component(axis) <= 0.0 {
+            continue;
+        }
+        let mut bins: Vec<Option<AABB>> = vec![None; nbins];
+        let mut counts = vec![0_usize; nbins];
+        for (tri_aabb, centroid) in bounds.iter() {
+            let bin = bin_of(centroid, axis);
+            counts[bin] += 1;
+            bins[bin] = Some(match &bins[bin] {
+                Some(acc) => acc.merge(tri_aabb),
+                None => tri_aabb.clone(),
+            });
+        }
+
+        let mut right_areas = vec![0.0f64; nbins];
+        let mut right_counts = vec![0_usize; nbins];
+        let mut acc: Option<AABB> = None;
+        let mut count = 0_usize;
+        for b in (1..nbins).rev() {
+            if let Some(bin_aabb) = &bins[b] {
+                acc = Some(match &acc {
+                    Some(acc) => acc.merge(bin_aabb),
+                    None => bin_aabb.clone(),
+                });
+            }
+            count += counts[b];
+            right_areas[b] = acc.as_ref().map_or(0.0, |acc| acc.surface_area());
+            right_counts[b] = count;
+        }
+
+        let mut acc: Option<AABB> = None;
+        let mut count = 0_usize;
+        for b in 1..nbins {
+            if let Some(bin_aabb) = &bins[b - 1] {
+                acc = Some(match &acc {
+                    Some(acc) => acc.merge(bin_aabb),
+                    None => bin_aabb.clone(),
+                });
+            }
+            count += counts[b - 1];
+            if count == 0 || right_counts[b] == 0 {
+                continue;
+            }
+            let left_area = acc.as_ref().map_or(0.0, |acc| acc.surface_area());
+            let cost = sah_cost(
+                cfg,
+                parent_area,
+                left_area,
+                count,
+                right_areas[b],
+                right_counts[b],
+            );
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, b));
+            }
+        }
+    }
+
+    match best {
+        Some((_, axis, sep)) => {
+            let mut pos_tri_idx = Vec::<TriIndex>::new();
+            let mut neg_tri_idx = Vec::<TriIndex>::new();
+            for (tri_index, (_, centroid)) in bvh.idx_buf.iter().zip(bounds.iter()) {
+                if bin_of(centroid, axis) < sep {
+                    pos_tri_idx.push(tri_index.clone());
+                } else {
+                    neg_tri_idx.push(tri_index.clone());
+                }
+            }
+            (pos_tri_idx, neg_tri_idx)
+        }
+        // 重心全部重合时无从分桶
+        None => half_split(bvh),
+    }
+}
+
+// 探测盒沿start到end每隔step_into走一步，leaf_count给出探测盒的采样值(叶子数或代价报告)
+pub(crate) fn directional_hit_with<T: PeakSample>(
+    leaf_count: &(dyn Fn(&AABB) -> T + Sync),
+    block_size: &Vec3,
+    start: &Vec3,
+    end: &Vec3,
+    step_into: f64,
+    break_on_hit: bool,
+) -> T {
+    let mut local_peak = T::default();
+    directional_probes(
+        block_size,
+        start,
+        end,
+        step_into,
+        break_on_hit,
+        &mut |aabb| {
+            local_peak = local_peak.peak(leaf_count(aabb));
+        },
+    );
+    local_peak
+}
+
+// 按directional_hit的步进把每个探测盒交给f
+fn directional_probes(
+    block_size: &Vec3,
+    start: &Vec3,
+    end: &Vec3,
+    step_into: f64,
+    break_on_hit: bool,
+    f: &mut dyn FnMut(&AABB),
+) {
+    let mut local_pos = *start;
+    let half_aabb_size = *block_size / Vec3::new(2.0, 2.0, 2.0);
+    let dist = end.distance_to(start);
+    let dir = start.direction_to(end);
+    let nchunks = (dist / step_into).ceil() as usize;
+    for _a in 0..nchunks {
+        let min = local_pos - half_aabb_size;
+        let max = local_pos + half_aabb_size;
+        let aabb = AABB::new(&min, &max);
+        f(&aabb);
+        if break_on_hit {
+            break;
+        }
+        local_pos.move_towards(&dir, step_into);
+    }
+}
+
+// 把各列分给num_workers个线程，结果仍按列的顺序返回，和串行完全一致
+pub(crate) fn map_columns<C: Sync, T: Send>(
+    columns: &[C],
+    num_workers: usize,
+    f: &(dyn Fn(&C) -> T + Sync),
+) -> Vec<T> {
+    let num_workers = num_workers.clamp(1, columns.len().max(1));
+    if num_workers == 1 {
+        return columns.iter().map(f).collect();
+    }
+    let mut ret = std::thread::scope(|scope| {
+        let handles = (0..num_workers)
+            .map(|worker| {
+                scope.spawn(move || {
+                    columns
+                        .iter()
+                        .enumerate()
+                        .skip(worker)
+                        .step_by(num_workers)
+                        .map(|(idx, column)| (idx, f(column)))
+                        .collect::<Vec<(usize, T)>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect::<Vec<(usize, T)>>()
+    });
+    ret.sort_by_key(|(idx, _)| *idx);
+    ret.into_iter().map(|(_, value)| value).collect()
+}
+
+// 扫描的采样值，peak把两个采样合并成较坏的那个
+pub(crate) trait PeakSample: Copy + Default + Send {
+    fn peak(self, other: Self) -> Self;
+}
+
+impl PeakSample for usize {
+    fn peak(self, other: Self) -> Self {
+        self.max(other)
+    }
+}
+
+pub(crate) fn block_overlap_peak_with<T: PeakSample>(
+    root_aabb: &AABB,
+    leaf_count: &(dyn Fn(&AABB) -> T + Sync),
+    step: f64,
+    num_workers: usize,
+) -> T {
+    block_overlap_scan(root_aabb, step, num_workers, &|peak: &mut T, aabb| {
+        *peak = peak.peak(leaf_count(aabb));
+    })
+    .into_iter()
+    .fold(T::default(), T::peak)
+}
+
+// 每列一个累加器，probe把列里的每个探测盒累加进去，返回值按列的顺序排列
+pub(crate) fn block_overlap_scan<A: Default + Send>(
+    root_aabb: &AABB,
+    step: f64,
+    num_workers: usize,
+    probe: &(dyn Fn(&mut A, &AABB) + Sync),
+) -> Vec<A> {
+    // 开始坐标向外括了半格
+    // 再加上directional_hit以该点为中心，内外各半格，刚好向外括了一格
+    let halfstep = step / 2.0;
+    let mut local_aabb = root_aabb.clone();
+    local_aabb.expand(&Vec3::new(step, step, step));
+
+    // 列的起点按串行的累加方式先算好，分到多个线程后坐标也不会变
+    let mut columns = Vec::<Vec3>::new();
+    let mut curx = local_aabb.min;
+    while curx.x < local_aabb.max.x {
+        columns.push(curx);
+        curx.x += halfstep;
+    }
+
+    let column_scan = |curx: &Vec3| -> A {
+        let mut acc = A::default();
+        let mut cury = *curx;
+        while cury.y < local_aabb.max.y {
+            let mut point_start = cury;
+            let mut point_end = cury;
+            point_start.z = root_aabb.min.z;
+            point_end.z = root_aabb.max.z;
+            directional_probes(
+                &Vec3::new(step, step, step),
+                &point_start,
+                &point_end,
+                halfstep,
+                false,
+                &mut |aabb| probe(&mut acc, aabb),
+            );
+            cury.y += halfstep;
+        }
+        acc
+    };
+
+    map_columns(&columns, num_workers, &column_scan)
+}
+
+pub(crate) fn surface_hit_peak_with<T: PeakSample>(
+    root_aabb: &AABB,
+    leaf_count: &(dyn Fn(&AABB) -> T + Sync),
+    step: f64,
+    block_size: &Vec3,
+    num_workers: usize,
+) -> T {
+    surface_hit_scan(
+        root_aabb,
+        step,
+        block_size,
+        num_workers,
+        &|peak: &mut T, aabb| {
+            *peak = peak.peak(leaf_count(aabb));
+        },
+    )
+    .into_iter()
+    .fold(T::default(), T::peak)
+}
+
+pub(crate) fn surface_hit_scan<A: Default + Send>(
+    root_aabb: &AABB,
+    step: f64,
+    block_size: &Vec3,
+    num_workers: usize,
+    probe: &(dyn Fn(&mut A, &AABB) + Sync),
+) -> Vec<A> {
+    #[derive(Copy, Clone)]
+    enum Axis {
+        X,
+        Y,
+        Z,
+    }
+
+    // 开始坐标向外括了半格
+    // 再加上directional_hit以该点为中心，内外各半格，刚好向外括了一格
+    let half_block_size = *block_size / Vec3::new(2.0, 2.0, 2.0);
+    let mut local_aabb = root_aabb.clone();
+    local_aabb.expand(block_size);
+    let ext = root_aabb.extent();
+    let start = local_aabb.min;
+    let end = local_aabb.max;
+
+    // 三个方向的所有列放在一起分给线程
+    let mut columns = Vec::<(Axis, Vec3)>::new();
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        let mut point1 = start;
+        while match axis {
+            Axis::X => point1.y < end.y,
+            Axis::Y => point1.z < end.z,
+            Axis::Z => point1.x < end.x,
+        } {
+            columns.push((axis, point1));
+            match axis {
+                Axis::X => {
+                    point1.y += half_block_size.y;
+                }
+                Axis::Y => {
+                    point1.z += half_block_size.z;
+                }
+                Axis::Z => {
+                    point1.x += half_block_size.x;
+                }
+            }
+        }
+    }
+
+    let column_scan = |column: &(Axis, Vec3)| -> A {
+        let (axis, point1) = *column;
+        let mut acc = A::default();
+        let mut point2 = point1;
+        while match axis {
+            Axis::X => point2.z < end.z,
+            Axis::Y => point2.x < end.x,
+            Axis::Z => point2.y < end.y,
+        } {
+            let mut point2_back = point2;
+            match axis {
+                Axis::X => {
+                    point2_back.x += ext.x;
+                }
+                Axis::Y => {
+                    point2_back.y += ext.y;
+                }
+                Axis::Z => {
+                    point2_back.z += ext.z;
+                }
+            }
+            directional_probes(block_size, &point2, &point2_back, step, true, &mut |aabb| {
+                probe(&mut acc, aabb)
+            });
+            directional_probes(block_size, &point2_back, &point2, step, true, &mut |aabb| {
+                probe(&mut acc, aabb)
+            });
+            match axis {
+                Axis::X => {
+                    point2.z += half_block_size.z;
+                }
+                Axis::Y => {
+                    point2.x += half_block_size.x;
+                }
+                Axis::Z => {
+                    point2.y += half_block_size.y;
+                }
+            }
+        }
+        acc
+    };
+
+    map_columns(&columns, num_workers, &column_scan)
+}
@@ -1,7 +1,9 @@
 use crate::prelude::*;
 
 use std::os::raw::{c_double, c_longlong};
-use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 type PyFloat = c_double;
 type PyInt = c_longlong;
@@ -15,6 +17,7 @@ enum PyResult {
     IdxOutOfVtxCnt = -4,
     TriBufNotGenerated = -5,
     BVHNotGenerated = -6,
+    InvalidArgument = -7,
 }
 
 #[repr(C)]
@@ -31,36 +34,127 @@ pub struct PyBVHInfo {
     pub ntris: PyInt,
 }
 
+#[repr(C)]
+pub struct PyRayHit {
+    pub distance: PyFloat,
+    pub u: PyFloat,
+    pub v: PyFloat,
+    pub indices: [PyInt; 3],
+}
+
+#[repr(C)]
+pub struct PySweepHit {
+    pub distance: PyFloat,
+    pub indices: [PyInt; 3],
+}
+
+#[repr(C)]
+pub struct PyClosestPoint {
+    pub point: PyVec3,
+    pub distance: PyFloat,
+    pub indices: [PyInt; 3],
+}
+
+#[repr(C)]
+pub struct PyPairOverlap {
+    pub leaf_pairs: PyInt,
+    pub candidate_tri_pairs: PyInt,
+    pub hit_tri_pairs: PyInt,
+}
+
+#[repr(C)]
+pub struct PySelfIntersection {
+    pub lhs: [PyInt; 3],
+    pub rhs: [PyInt; 3],
+    // 共面重叠时为0，seg0/seg1无意义
+    pub has_segment: PyInt,
+    pub seg0: PyVec3,
+    pub seg1: PyVec3,
+}
+
+#[repr(C)]
+pub struct PyFrustumCull {
+    pub visible_leaves: PyInt,
+    pub visible_tris: PyInt,
+    pub nodes_tested: PyInt,
+    pub nodes_inside: PyInt,
+    pub nodes_intersecting: PyInt,
+    pub nodes_outside: PyInt,
+}
+
+#[repr(C)]
+pub struct PyCostModel {
+    pub node_visit: PyFloat,
+    pub leaf_visit: PyFloat,
+    pub tri_test: PyFloat,
+    pub tri_per_leaf: PyFloat,
+}
+
+#[repr(C)]
+pub struct PyPeakReport {
+    pub leaves: PyInt,
+    pub nodes_visited: PyInt,
+    pub leaf_tris: PyInt,
+    pub tri_tests: PyInt,
+    pub cost: PyFloat,
+    pub peak_leaves: PyInt,
+}
+
+#[repr(C)]
+pub struct PyPeakDistribution {
+    pub samples: PyInt,
+    pub empty_samples: PyInt,
+    pub peak: PyInt,
+    pub mean: PyFloat,
+    pub median: PyInt,
+    pub p90: PyInt,
+    pub p99: PyInt,
+    pub worst_center: PyVec3,
+    pub worst_min: PyVec3,
+    pub worst_max: PyVec3,
+    // 直方图的完整长度，缓冲区不够时只写前面一段
+    pub histogram_len: PyInt,
+}
+
 struct BVHBuildInfo {
-    vtx_buf: Rc<Vec<Vec3>>,
+    vtx_buf: Arc<Vec<Vec3>>,
     idx_buf: Vec<IndexedPoly>,
     tri_buf: Vec<IndexedTri>,
-    bvh: Option<Rc<BVHNode>>,
+    subdivide_cfg: BVHSubdivideConfig,
+    bvh: Option<Arc<BVHNode>>,
+    // 第一次按环绕数查询时才建，重新建树后作废
+    winding: Option<Arc<WindingNumberTree>>,
+    // 峰值报告按它把遍历计数折算成微秒
+    cost_model: CostModel,
+    build_time: Duration,
 }
 
 const NUM_BVH_BUILD_RESOUCE: usize = 8;
-static mut BVH_BUILD_RESOURCE: [Option<BVHBuildInfo>; NUM_BVH_BUILD_RESOUCE] =
-    [const { None }; NUM_BVH_BUILD_RESOUCE];
+// 每个槽位单独加锁，不同资源可以在不同线程里同时建树和跑profile
+static BVH_BUILD_RESOURCE: [Mutex<Option<BVHBuildInfo>>; NUM_BVH_BUILD_RESOUCE] =
+    [const { Mutex::new(None) }; NUM_BVH_BUILD_RESOUCE];
 
 impl BVHBuildInfo {
-    fn new(vtx_buf: Rc<Vec<Vec3>>) -> Self {
+    fn new(vtx_buf: Arc<Vec<Vec3>>) -> Self {
         Self {
             vtx_buf,
             idx_buf: Vec::<IndexedPoly>::new(),
             tri_buf: Vec::<IndexedTri>::new(),
+            subdivide_cfg: BVHSubdivideConfig::default(),
             bvh: None,
+            winding: None,
+            cost_model: CostModel::default(),
+            build_time: Duration::ZERO,
         }
     }
 
-    fn alloc(vtx_buf: Rc<Vec<Vec3>>) -> i64 {
-        unsafe {
-            for id in 0..NUM_BVH_BUILD_RESOUCE {
-                let r = &mut BVH_BUILD_RESOURCE[id];
-                if r.is_none() {
-                    let bbi = Self::new(vtx_buf);
-                    BVH_BUILD_RESOURCE[id] = Some(bbi);
-                    return id as i64;
-                }
+    fn alloc(vtx_buf: Arc<Vec<Vec3>>) -> i64 {
+        for (id, slot) in BVH_BUILD_RESOURCE.iter().enumerate() {
+            let mut slot = slot.lock().unwrap();
+            if slot.is_none() {
+                let bbi = Self::new(vtx_buf);
+                *slot = Some(bbi);
+                return id as i64;
             }
         }
         PyResult::OutOfResource as i64
@@ -70,12 +164,12 @@ impl BVHBuildInfo {
         if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
             return PyResult::ResourceNotFound as i64;
         }
-        unsafe {
-            let rc = &BVH_BUILD_RESOURCE[id as usize];
-            if rc.is_none() {
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if slot.is_none() {
                 return PyResult::ResourceNotFound as i64;
             } else {
-                BVH_BUILD_RESOURCE[id as usize] = None;
+                *slot = None;
             }
         }
         PyResult::Good as i64
@@ -85,8 +179,9 @@ impl BVHBuildInfo {
         if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
             return PyResult::ResourceNotFound as i64;
         }
-        unsafe {
-            if let Some(ref mut rc) = BVH_BUILD_RESOURCE[id as usize] {
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
                 for idx in pidx.iter() {
                     if *idx >= rc.vtx_buf.len() {
                         return PyResult::IdxOutOfVtxCnt as i64;
@@ -101,12 +196,195 @@ impl BVHBuildInfo {
         PyResult::Good as i64
     }
 
+    fn set_split_method(id: i64, method: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        let split_method = match method {
+            0 => BVHSplitMethod::Naive,
+            1 => BVHSplitMethod::SAH,
+            2 => BVHSplitMethod::BinnedSAH,
+            3 => BVHSplitMethod::ObjectMedian,
+            4 => BVHSplitMethod::CentroidMedian,
+            5 => BVHSplitMethod::Spatial,
+            6 => BVHSplitMethod::LBVH,
+            7 => BVHSplitMethod::PhysXBVH33,
+            8 => BVHSplitMethod::PhysXBVH34,
+            _ => return PyResult::InvalidArgument as i64,
+        };
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.split_method = split_method;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_num_bins(id: i64, num_bins: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        if num_bins < 2 {
+            return PyResult::InvalidArgument as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.num_bins = num_bins as usize;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_spatial_split_budget(id: i64, budget: f64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        if budget < 0.0 {
+            return PyResult::InvalidArgument as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.spatial_split_budget = budget;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_morton_code_bits(id: i64, bits: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        let morton_code = match bits {
+            30 => BVHMortonCode::Bits30,
+            63 => BVHMortonCode::Bits63,
+            _ => return PyResult::InvalidArgument as i64,
+        };
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.morton_code = morton_code;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_mesh_size_performance_tradeoff(id: i64, tradeoff: f64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        if !(0.0..=1.0).contains(&tradeoff) {
+            return PyResult::InvalidArgument as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.mesh_size_performance_tradeoff = tradeoff;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_num_prims_per_leaf(id: i64, num_prims_per_leaf: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        if !(1..=15).contains(&num_prims_per_leaf) {
+            return PyResult::InvalidArgument as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.num_prims_per_leaf = num_prims_per_leaf as usize;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_node_width(id: i64, node_width: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        if ![2, 4, 8].contains(&node_width) {
+            return PyResult::InvalidArgument as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.node_width = node_width as usize;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_num_threads(id: i64, num_threads: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        if num_threads < 1 {
+            return PyResult::InvalidArgument as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.subdivide_cfg.num_threads = num_threads as usize;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn set_cost_model(id: i64, cost_model: &CostModel) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        let weights = [
+            cost_model.node_visit,
+            cost_model.leaf_visit,
+            cost_model.tri_test,
+            cost_model.tri_per_leaf,
+        ];
+        if weights
+            .iter()
+            .any(|weight| weight.is_nan() || *weight < 0.0)
+        {
+            return PyResult::InvalidArgument as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                rc.cost_model = *cost_model;
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
     fn generate_tri_buf(id: i64) -> i64 {
         if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
             return PyResult::ResourceNotFound as i64;
         }
-        unsafe {
-            if let Some(ref mut rc) = BVH_BUILD_RESOURCE[id as usize] {
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
                 if rc.idx_buf.is_empty() {
                     return PyResult::IdxBufIsEmpty as i64;
                 }
@@ -126,8 +404,9 @@ impl BVHBuildInfo {
         if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
             return PyResult::ResourceNotFound as i64;
         }
-        unsafe {
-            if let Some(ref mut rc) = BVH_BUILD_RESOURCE[id as usize] {
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
                 if rc.idx_buf.is_empty() {
                     return PyResult::IdxBufIsEmpty as i64;
                 }
@@ -139,9 +418,16 @@ impl BVHBuildInfo {
                     .iter()
                     .map(|itri| TriIndex::new(itri.indices[0], itri.indices[1], itri.indices[2]))
                     .collect();
+                let timer = Instant::now();
                 let mut bvh = BVHNode::new(rc.vtx_buf.clone(), tri_index);
-                bvh.subdivide(BVHSubdivideConfig::default());
-                rc.bvh = Some(Rc::new(bvh));
+                bvh.subdivide(rc.subdivide_cfg);
+                let mut bvh = Arc::new(bvh);
+                if rc.subdivide_cfg.node_width > 2 {
+                    bvh = Arc::new(BVHNode::collapse(bvh, rc.subdivide_cfg.node_width));
+                }
+                rc.build_time = timer.elapsed();
+                rc.bvh = Some(bvh);
+                rc.winding = None;
             } else {
                 return PyResult::ResourceNotFound as i64;
             }
@@ -149,16 +435,17 @@ impl BVHBuildInfo {
         PyResult::Good as i64
     }
 
-    fn get_leaves(id: i64, leaves: &mut Vec<Rc<BVHNode>>) -> i64 {
+    fn get_leaves(id: i64, leaves: &mut Vec<Arc<BVHNode>>) -> i64 {
         if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
             return PyResult::ResourceNotFound as i64;
         }
-        unsafe {
-            if let Some(ref rc) = BVH_BUILD_RESOURCE[id as usize] {
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
                 if let Some(ref bvh) = rc.bvh {
                     let mut stack = vec![bvh.clone()];
                     while let Some(node) = stack.pop() {
-                        stack.extend(node.children.iter().map(|ptr| ptr.clone()));
+                        stack.extend(node.children.iter().cloned());
                         if node.is_leaf() {
                             leaves.push(node);
                         }
@@ -173,16 +460,78 @@ impl BVHBuildInfo {
         leaves.len() as i64
     }
 
+    fn get_build_time_us(id: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if rc.bvh.is_none() {
+                    return PyResult::BVHNotGenerated as i64;
+                }
+                rc.build_time.as_micros() as i64
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
+    }
+
+    fn get_sibling_overlap(id: i64, overlap: &mut f64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    *overlap = BVHNode::sibling_overlap(bvh.clone());
+                } else {
+                    return PyResult::BVHNotGenerated as i64;
+                }
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn get_reference_count(id: i64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    BVHNode::reference_count(bvh.clone()) as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
+    }
+
     fn get_leaf_count(id: i64) -> i64 {
-        let mut leaves = Vec::<Rc<BVHNode>>::new();
+        let mut leaves = Vec::<Arc<BVHNode>>::new();
         Self::get_leaves(id, &mut leaves)
     }
 
     fn get_block_overlap_peak(id: i64, block_size: f64) -> i64 {
-        unsafe {
-            if let Some(ref rc) = BVH_BUILD_RESOURCE[id as usize] {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
                 if let Some(ref bvh) = rc.bvh {
-                    BVHNode::block_overlap_peak(bvh.clone(), block_size) as i64
+                    BVHNode::block_overlap_peak_parallel(
+                        bvh.clone(),
+                        block_size,
+                        rc.subdivide_cfg.num_threads,
+                    ) as i64
                 } else {
                     PyResult::BVHNotGenerated as i64
                 }
@@ -193,10 +542,19 @@ impl BVHBuildInfo {
     }
 
     fn get_surface_hit_peak(id: i64, step: f64, block_size: &Vec3) -> i64 {
-        unsafe {
-            if let Some(ref rc) = BVH_BUILD_RESOURCE[id as usize] {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
                 if let Some(ref bvh) = rc.bvh {
-                    BVHNode::surface_hit_peak(bvh.clone(), step, block_size) as i64
+                    BVHNode::surface_hit_peak_parallel(
+                        bvh.clone(),
+                        step,
+                        block_size,
+                        rc.subdivide_cfg.num_threads,
+                    ) as i64
                 } else {
                     PyResult::BVHNotGenerated as i64
                 }
@@ -205,63 +563,475 @@ impl BVHBuildInfo {
             }
         }
     }
-}
 
-#[no_mangle]
-pub extern "C" fn BVHBuildInfo_create(verts: *const PyFloat, nverts: PyInt) -> PyInt {
-    unsafe {
-        let data = std::slice::from_raw_parts(verts, (nverts * 3) as usize);
-        let mut vtx_buf = Vec::<Vec3>::new();
-        for i in 0..nverts {
-            let idx = (i * 3) as usize;
-            let pt0 = data[idx];
-            let pt1 = data[idx + 1];
-            let pt2 = data[idx + 2];
-            vtx_buf.push(Vec3::new(pt0, pt1, pt2));
+    fn get_block_overlap_tri_peak(id: i64, block_size: f64) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    BVHNode::block_overlap_tri_peak_parallel(
+                        bvh.clone(),
+                        block_size,
+                        rc.subdivide_cfg.num_threads,
+                    ) as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
         }
-        let id = BVHBuildInfo::alloc(Rc::new(vtx_buf));
-        id as PyInt
     }
-}
-
-#[no_mangle]
-pub extern "C" fn BVHBuildInfo_delete(id: PyInt) -> PyInt {
-    BVHBuildInfo::dealloc(id)
-}
 
-#[no_mangle]
-pub extern "C" fn BVHBuildInfo_add_poly_index(
-    id: PyInt,
-    indices: *const PyInt,
-    nverts: PyInt,
-) -> PyInt {
-    unsafe {
-        let data = std::slice::from_raw_parts(indices, nverts as usize)
-            .iter()
-            .map(|idx| *idx as usize)
-            .collect::<Vec<usize>>();
-        BVHBuildInfo::add_poly_index(id, data)
+    fn get_surface_hit_tri_peak(id: i64, step: f64, block_size: &Vec3) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    BVHNode::surface_hit_tri_peak_parallel(
+                        bvh.clone(),
+                        step,
+                        block_size,
+                        rc.subdivide_cfg.num_threads,
+                    ) as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
     }
-}
-
-#[no_mangle]
-pub extern "C" fn BVHBuildInfo_generate_tri_buf(id: PyInt) -> PyInt {
-    BVHBuildInfo::generate_tri_buf(id)
-}
 
-#[no_mangle]
-pub extern "C" fn BVHBuildInfo_generate_bvh(id: PyInt) -> PyInt {
-    BVHBuildInfo::generate_bvh(id)
-}
+    fn get_sphere_surface_hit_peak(id: i64, step: f64, sphere: &Sphere) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    BVHNode::surface_shape_hit_peak_parallel(
+                        bvh.clone(),
+                        sphere,
+                        step,
+                        rc.subdivide_cfg.num_threads,
+                    ) as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
+    }
 
-#[no_mangle]
+    fn get_capsule_surface_hit_peak(id: i64, step: f64, capsule: &Capsule) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    BVHNode::surface_shape_hit_peak_parallel(
+                        bvh.clone(),
+                        capsule,
+                        step,
+                        rc.subdivide_cfg.num_threads,
+                    ) as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
+    }
+
+    fn get_obb_surface_hit_peak(id: i64, step: f64, obb: &OBB) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    BVHNode::surface_shape_hit_peak_parallel(
+                        bvh.clone(),
+                        obb,
+                        step,
+                        rc.subdivide_cfg.num_threads,
+                    ) as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
+    }
+
+    fn get_obb_surface_hit_peak_worst(
+        id: i64,
+        step: f64,
+        half_extents: &Vec3,
+        num_orientations: usize,
+    ) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    BVHNode::surface_obb_hit_peak_worst(
+                        bvh.clone(),
+                        half_extents,
+                        step,
+                        num_orientations,
+                        rc.subdivide_cfg.num_threads,
+                    )
+                    .0 as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
+    }
+
+    fn sweep(
+        id: i64,
+        sweep_fn: &dyn Fn(Arc<BVHNode>) -> Option<SweepHit>,
+        hit: &mut Option<SweepHit>,
+    ) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    *hit = sweep_fn(bvh.clone());
+                } else {
+                    return PyResult::BVHNotGenerated as i64;
+                }
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn closest_point(
+        id: i64,
+        pt: &Vec3,
+        max_distance: f64,
+        closest: &mut Option<ClosestPoint>,
+    ) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    *closest = BVHNode::closest_point(bvh.clone(), pt, max_distance);
+                } else {
+                    return PyResult::BVHNotGenerated as i64;
+                }
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn point_inside(id: i64, pt: &Vec3, mode: PointInsideMode) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let mut slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref mut rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    let inside = match mode {
+                        PointInsideMode::RayParity => BVHNode::point_inside(bvh.clone(), pt),
+                        PointInsideMode::WindingNumber => rc
+                            .winding
+                            .get_or_insert_with(|| Arc::new(WindingNumberTree::new(bvh.clone())))
+                            .point_inside(pt),
+                    };
+                    inside as i64
+                } else {
+                    PyResult::BVHNotGenerated as i64
+                }
+            } else {
+                PyResult::ResourceNotFound as i64
+            }
+        }
+    }
+
+    // 只在取树时短暂加锁，两个id相同也不会自己锁死自己
+    fn clone_bvh(id: i64) -> Result<Arc<BVHNode>, i64> {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return Err(PyResult::ResourceNotFound as i64);
+        }
+        let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+        if let Some(ref rc) = *slot {
+            if let Some(ref bvh) = rc.bvh {
+                Ok(bvh.clone())
+            } else {
+                Err(PyResult::BVHNotGenerated as i64)
+            }
+        } else {
+            Err(PyResult::ResourceNotFound as i64)
+        }
+    }
+
+    fn get_pair_overlap(
+        id: i64,
+        other_id: i64,
+        transform: &Transform,
+        probe: &mut BVHProbe,
+    ) -> i64 {
+        let lhs = match Self::clone_bvh(id) {
+            Ok(bvh) => bvh,
+            Err(err) => return err,
+        };
+        let rhs = match Self::clone_bvh(other_id) {
+            Ok(bvh) => bvh,
+            Err(err) => return err,
+        };
+        *probe = BVHNode::probe_pair(lhs, rhs, Some(transform));
+        PyResult::Good as i64
+    }
+
+    fn get_peak_report(
+        id: i64,
+        peak: &dyn Fn(Arc<BVHNode>, &CostModel, usize) -> PeakReport,
+        report: &mut PeakReport,
+    ) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    *report = peak(bvh.clone(), &rc.cost_model, rc.subdivide_cfg.num_threads);
+                } else {
+                    return PyResult::BVHNotGenerated as i64;
+                }
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn get_distribution(
+        id: i64,
+        scan: &dyn Fn(Arc<BVHNode>, usize) -> PeakDistribution,
+        dist: &mut PeakDistribution,
+    ) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    *dist = scan(bvh.clone(), rc.subdivide_cfg.num_threads);
+                } else {
+                    return PyResult::BVHNotGenerated as i64;
+                }
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+
+    fn get_self_intersections(id: i64, hits: &mut Vec<SelfIntersection>) -> i64 {
+        match Self::clone_bvh(id) {
+            Ok(bvh) => {
+                *hits = BVHNode::get_self_intersections(bvh);
+                hits.len() as i64
+            }
+            Err(err) => err,
+        }
+    }
+
+    fn raycast(id: i64, ray: &Ray, any_hit: bool, hit: &mut Option<RayHit>) -> i64 {
+        if (id as usize) >= NUM_BVH_BUILD_RESOUCE {
+            return PyResult::ResourceNotFound as i64;
+        }
+        {
+            let slot = BVH_BUILD_RESOURCE[id as usize].lock().unwrap();
+            if let Some(ref rc) = *slot {
+                if let Some(ref bvh) = rc.bvh {
+                    *hit = if any_hit {
+                        BVHNode::raycast_any(bvh.clone(), ray)
+                    } else {
+                        BVHNode::raycast_closest(bvh.clone(), ray)
+                    };
+                } else {
+                    return PyResult::BVHNotGenerated as i64;
+                }
+            } else {
+                return PyResult::ResourceNotFound as i64;
+            }
+        }
+        PyResult::Good as i64
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_create(verts: *const PyFloat, nverts: PyInt) -> PyInt {
+    unsafe {
+        let data = std::slice::from_raw_parts(verts, (nverts * 3) as usize);
+        let mut vtx_buf = Vec::<Vec3>::new();
+        for i in 0..nverts {
+            let idx = (i * 3) as usize;
+            let pt0 = data[idx];
+            let pt1 = data[idx + 1];
+            let pt2 = data[idx + 2];
+            vtx_buf.push(Vec3::new(pt0, pt1, pt2));
+        }
+        let id = BVHBuildInfo::alloc(Arc::new(vtx_buf));
+        id as PyInt
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_delete(id: PyInt) -> PyInt {
+    BVHBuildInfo::dealloc(id)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_add_poly_index(
+    id: PyInt,
+    indices: *const PyInt,
+    nverts: PyInt,
+) -> PyInt {
+    unsafe {
+        let data = std::slice::from_raw_parts(indices, nverts as usize)
+            .iter()
+            .map(|idx| *idx as usize)
+            .collect::<Vec<usize>>();
+        BVHBuildInfo::add_poly_index(id, data)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_split_method(id: PyInt, method: PyInt) -> PyInt {
+    BVHBuildInfo::set_split_method(id, method)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_num_bins(id: PyInt, num_bins: PyInt) -> PyInt {
+    BVHBuildInfo::set_num_bins(id, num_bins)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_spatial_split_budget(id: PyInt, budget: PyFloat) -> PyInt {
+    BVHBuildInfo::set_spatial_split_budget(id, budget)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_morton_code_bits(id: PyInt, bits: PyInt) -> PyInt {
+    BVHBuildInfo::set_morton_code_bits(id, bits)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_mesh_size_performance_tradeoff(
+    id: PyInt,
+    tradeoff: PyFloat,
+) -> PyInt {
+    BVHBuildInfo::set_mesh_size_performance_tradeoff(id, tradeoff)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_num_prims_per_leaf(
+    id: PyInt,
+    num_prims_per_leaf: PyInt,
+) -> PyInt {
+    BVHBuildInfo::set_num_prims_per_leaf(id, num_prims_per_leaf)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_node_width(id: PyInt, node_width: PyInt) -> PyInt {
+    BVHBuildInfo::set_node_width(id, node_width)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_num_threads(id: PyInt, num_threads: PyInt) -> PyInt {
+    BVHBuildInfo::set_num_threads(id, num_threads)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_set_cost_model(id: PyInt, cost_model: PyCostModel) -> PyInt {
+    BVHBuildInfo::set_cost_model(
+        id,
+        &CostModel {
+            node_visit: cost_model.node_visit,
+            leaf_visit: cost_model.leaf_visit,
+            tri_test: cost_model.tri_test,
+            tri_per_leaf: cost_model.tri_per_leaf,
+        },
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_generate_tri_buf(id: PyInt) -> PyInt {
+    BVHBuildInfo::generate_tri_buf(id)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_generate_bvh(id: PyInt) -> PyInt {
+    BVHBuildInfo::generate_bvh(id)
+}
+
+#[no_mangle]
 pub extern "C" fn BVHBuildInfo_get_leaf_count(id: PyInt) -> PyInt {
     BVHBuildInfo::get_leaf_count(id)
 }
 
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_build_time_us(id: PyInt) -> PyInt {
+    BVHBuildInfo::get_build_time_us(id)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_sibling_overlap(id: PyInt, overlap: *mut PyFloat) -> PyInt {
+    let mut value = 0.0f64;
+    let ret = BVHBuildInfo::get_sibling_overlap(id, &mut value);
+    if ret < 0 {
+        return ret as PyInt;
+    }
+    unsafe {
+        std::ptr::write(overlap, value);
+    }
+    PyResult::Good as PyInt
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_reference_count(id: PyInt) -> PyInt {
+    BVHBuildInfo::get_reference_count(id)
+}
+
 #[no_mangle]
 pub extern "C" fn BVHBuildInfo_get_leaves(id: PyInt, buf: *mut PyBVHInfo, buflen: PyInt) -> PyInt {
-    let mut leaves = Vec::<Rc<BVHNode>>::new();
+    let mut leaves = Vec::<Arc<BVHNode>>::new();
     let nleaves = BVHBuildInfo::get_leaves(id, &mut leaves);
     if nleaves < 0 {
         return nleaves as PyInt;
@@ -311,3 +1081,561 @@ pub extern "C" fn BVHBuildInfo_get_surface_hit_peak(
         &Vec3::new(block_size_x, block_size_y, block_size_z),
     )
 }
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_block_overlap_peak_report(
+    id: PyInt,
+    block_size: PyFloat,
+    report: *mut PyPeakReport,
+) -> PyInt {
+    let mut result = PeakReport::default();
+    let ret = BVHBuildInfo::get_peak_report(
+        id,
+        &|bvh, cost_model, num_workers| {
+            BVHNode::block_overlap_peak_report_parallel(bvh, block_size, cost_model, num_workers)
+        },
+        &mut result,
+    );
+    write_peak_report(report, &result);
+    ret
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_surface_hit_peak_report(
+    id: PyInt,
+    step: PyFloat,
+    block_size: PyVec3,
+    report: *mut PyPeakReport,
+) -> PyInt {
+    let block_size = Vec3::new(block_size.x, block_size.y, block_size.z);
+    let mut result = PeakReport::default();
+    let ret = BVHBuildInfo::get_peak_report(
+        id,
+        &|bvh, cost_model, num_workers| {
+            BVHNode::surface_hit_peak_report_parallel(
+                bvh,
+                step,
+                &block_size,
+                cost_model,
+                num_workers,
+            )
+        },
+        &mut result,
+    );
+    write_peak_report(report, &result);
+    ret
+}
+
+fn write_peak_report(report: *mut PyPeakReport, result: &PeakReport) {
+    let pyreport = PyPeakReport {
+        leaves: result.leaves as PyInt,
+        nodes_visited: result.nodes_visited as PyInt,
+        leaf_tris: result.leaf_tris as PyInt,
+        tri_tests: result.tri_tests as PyInt,
+        cost: result.cost,
+        peak_leaves: result.peak_leaves as PyInt,
+    };
+    unsafe {
+        std::ptr::write(report, pyreport);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_block_overlap_distribution(
+    id: PyInt,
+    block_size: PyFloat,
+    dist: *mut PyPeakDistribution,
+    histogram: *mut PyInt,
+    buflen: PyInt,
+) -> PyInt {
+    let mut result = PeakDistribution::default();
+    let ret = BVHBuildInfo::get_distribution(
+        id,
+        &|bvh, num_workers| {
+            BVHNode::block_overlap_distribution_parallel(bvh, block_size, num_workers)
+        },
+        &mut result,
+    );
+    write_distribution(dist, histogram, buflen, &result);
+    ret
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_surface_hit_distribution(
+    id: PyInt,
+    step: PyFloat,
+    block_size: PyVec3,
+    dist: *mut PyPeakDistribution,
+    histogram: *mut PyInt,
+    buflen: PyInt,
+) -> PyInt {
+    let block_size = Vec3::new(block_size.x, block_size.y, block_size.z);
+    let mut result = PeakDistribution::default();
+    let ret = BVHBuildInfo::get_distribution(
+        id,
+        &|bvh, num_workers| {
+            BVHNode::surface_hit_distribution_parallel(bvh, step, &block_size, num_workers)
+        },
+        &mut result,
+    );
+    write_distribution(dist, histogram, buflen, &result);
+    ret
+}
+
+fn write_distribution(
+    dist: *mut PyPeakDistribution,
+    histogram: *mut PyInt,
+    buflen: PyInt,
+    result: &PeakDistribution,
+) {
+    let to_pyvec3 = |v: &Vec3| PyVec3 {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+    };
+    let pydist = PyPeakDistribution {
+        samples: result.samples as PyInt,
+        empty_samples: result.empty_samples as PyInt,
+        peak: result.peak as PyInt,
+        mean: result.mean,
+        median: result.median as PyInt,
+        p90: result.p90 as PyInt,
+        p99: result.p99 as PyInt,
+        worst_center: to_pyvec3(&result.worst_center),
+        worst_min: to_pyvec3(&result.worst_aabb.min),
+        worst_max: to_pyvec3(&result.worst_aabb.max),
+        histogram_len: result.histogram.len() as PyInt,
+    };
+    unsafe {
+        std::ptr::write(dist, pydist);
+        for (idx, num) in result.histogram.iter().enumerate() {
+            if idx >= (buflen.max(0) as usize) {
+                break;
+            }
+            std::ptr::write(histogram.wrapping_add(idx), *num as PyInt);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_block_overlap_tri_peak(id: PyInt, block_size: PyFloat) -> PyInt {
+    BVHBuildInfo::get_block_overlap_tri_peak(id, block_size)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_surface_hit_tri_peak(
+    id: PyInt,
+    step: PyFloat,
+    block_size_x: PyFloat,
+    block_size_y: PyFloat,
+    block_size_z: PyFloat,
+) -> PyInt {
+    BVHBuildInfo::get_surface_hit_tri_peak(
+        id,
+        step,
+        &Vec3::new(block_size_x, block_size_y, block_size_z),
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_sphere_surface_hit_peak(
+    id: PyInt,
+    step: PyFloat,
+    radius: PyFloat,
+) -> PyInt {
+    if radius <= 0.0 {
+        return PyResult::InvalidArgument as PyInt;
+    }
+    BVHBuildInfo::get_sphere_surface_hit_peak(id, step, &Sphere::new(&Vec3::default(), radius))
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_capsule_surface_hit_peak(
+    id: PyInt,
+    step: PyFloat,
+    half_height: PyFloat,
+    radius: PyFloat,
+) -> PyInt {
+    if radius <= 0.0 || half_height < 0.0 {
+        return PyResult::InvalidArgument as PyInt;
+    }
+    BVHBuildInfo::get_capsule_surface_hit_peak(
+        id,
+        step,
+        &Capsule::upright(&Vec3::default(), half_height, radius),
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_obb_surface_hit_peak(
+    id: PyInt,
+    step: PyFloat,
+    half_extents: PyVec3,
+    roll: PyFloat,
+    pitch: PyFloat,
+    yaw: PyFloat,
+) -> PyInt {
+    let obb = OBB::from_euler(
+        &Vec3::default(),
+        &Vec3::new(half_extents.x, half_extents.y, half_extents.z),
+        roll,
+        pitch,
+        yaw,
+    );
+    BVHBuildInfo::get_obb_surface_hit_peak(id, step, &obb)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_obb_surface_hit_peak_worst(
+    id: PyInt,
+    step: PyFloat,
+    half_extents: PyVec3,
+    num_orientations: PyInt,
+) -> PyInt {
+    if num_orientations <= 0 {
+        return PyResult::InvalidArgument as PyInt;
+    }
+    BVHBuildInfo::get_obb_surface_hit_peak_worst(
+        id,
+        step,
+        &Vec3::new(half_extents.x, half_extents.y, half_extents.z),
+        num_orientations as usize,
+    )
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_raycast(
+    id: PyInt,
+    origin: PyVec3,
+    direction: PyVec3,
+    max_distance: PyFloat,
+    any_hit: PyInt,
+    hit: *mut PyRayHit,
+) -> PyInt {
+    let ray = Ray::new(
+        &Vec3::new(origin.x, origin.y, origin.z),
+        &Vec3::new(direction.x, direction.y, direction.z),
+        max_distance,
+    );
+    let mut result = None;
+    let ret = BVHBuildInfo::raycast(id, &ray, any_hit != 0, &mut result);
+    if ret < 0 {
+        return ret as PyInt;
+    }
+    match result {
+        Some(result) => {
+            let pyhit = PyRayHit {
+                distance: result.distance,
+                u: result.u,
+                v: result.v,
+                indices: [
+                    result.tri_index.pt0 as PyInt,
+                    result.tri_index.pt1 as PyInt,
+                    result.tri_index.pt2 as PyInt,
+                ],
+            };
+            unsafe {
+                std::ptr::write(hit, pyhit);
+            }
+            1
+        }
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_closest_point(
+    id: PyInt,
+    point: PyVec3,
+    max_distance: PyFloat,
+    closest: *mut PyClosestPoint,
+) -> PyInt {
+    let mut result = None;
+    let ret = BVHBuildInfo::closest_point(
+        id,
+        &Vec3::new(point.x, point.y, point.z),
+        max_distance,
+        &mut result,
+    );
+    if ret < 0 {
+        return ret as PyInt;
+    }
+    match result {
+        Some(result) => {
+            let pyclosest = PyClosestPoint {
+                point: PyVec3 {
+                    x: result.point.x,
+                    y: result.point.y,
+                    z: result.point.z,
+                },
+                distance: result.distance,
+                indices: [
+                    result.tri_index.pt0 as PyInt,
+                    result.tri_index.pt1 as PyInt,
+                    result.tri_index.pt2 as PyInt,
+                ],
+            };
+            unsafe {
+                std::ptr::write(closest, pyclosest);
+            }
+            1
+        }
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_point_inside(id: PyInt, point: PyVec3, mode: PyInt) -> PyInt {
+    let mode = match mode {
+        0 => PointInsideMode::RayParity,
+        1 => PointInsideMode::WindingNumber,
+        _ => {
+            return PyResult::InvalidArgument as PyInt;
+        }
+    };
+    BVHBuildInfo::point_inside(id, &Vec3::new(point.x, point.y, point.z), mode)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_pair_overlap(
+    id: PyInt,
+    other_id: PyInt,
+    translation: PyVec3,
+    roll: PyFloat,
+    pitch: PyFloat,
+    yaw: PyFloat,
+    overlap: *mut PyPairOverlap,
+) -> PyInt {
+    let transform = Transform::from_euler(
+        &Vec3::new(translation.x, translation.y, translation.z),
+        roll,
+        pitch,
+        yaw,
+    );
+    let mut probe = BVHProbe::default();
+    let ret = BVHBuildInfo::get_pair_overlap(id, other_id, &transform, &mut probe);
+    if ret < 0 {
+        return ret as PyInt;
+    }
+    let pyoverlap = PyPairOverlap {
+        leaf_pairs: probe.leaves as PyInt,
+        candidate_tri_pairs: probe.candidate_tris as PyInt,
+        hit_tri_pairs: probe.hit_tris as PyInt,
+    };
+    unsafe {
+        std::ptr::write(overlap, pyoverlap);
+    }
+    PyResult::Good as PyInt
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_self_intersection_count(id: PyInt) -> PyInt {
+    let mut hits = Vec::<SelfIntersection>::new();
+    BVHBuildInfo::get_self_intersections(id, &mut hits)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_get_self_intersections(
+    id: PyInt,
+    buf: *mut PySelfIntersection,
+    buflen: PyInt,
+) -> PyInt {
+    let mut hits = Vec::<SelfIntersection>::new();
+    let nhits = BVHBuildInfo::get_self_intersections(id, &mut hits);
+    if nhits < 0 {
+        return nhits as PyInt;
+    }
+    let to_pyvec3 = |v: &Vec3| PyVec3 {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+    };
+    for (idx, hit) in hits.iter().enumerate() {
+        if idx >= (buflen as usize) {
+            break;
+        }
+        let (seg0, seg1) = hit.segment.unwrap_or_default();
+        let pyhit = PySelfIntersection {
+            lhs: [
+                hit.lhs.pt0 as PyInt,
+                hit.lhs.pt1 as PyInt,
+                hit.lhs.pt2 as PyInt,
+            ],
+            rhs: [
+                hit.rhs.pt0 as PyInt,
+                hit.rhs.pt1 as PyInt,
+                hit.rhs.pt2 as PyInt,
+            ],
+            has_segment: hit.segment.is_some() as PyInt,
+            seg0: to_pyvec3(&seg0),
+            seg1: to_pyvec3(&seg1),
+        };
+        unsafe {
+            std::ptr::write(buf.wrapping_add(idx), pyhit);
+        }
+    }
+    nhits as PyInt
+}
+
+// 探测盒由centers和extents给出，每个盒子碰到的叶子数和三角形数写进leaves/tris，
+// 整批只锁一次资源，三角形去重用的集合整批复用
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_probe_batch(
+    id: PyInt,
+    centers: *const PyVec3,
+    extents: *const PyVec3,
+    num: PyInt,
+    leaves: *mut PyInt,
+    tris: *mut PyInt,
+) -> PyInt {
+    // num为0时from_raw_parts也不接受空指针
+    if num < 0 || centers.is_null() || extents.is_null() || leaves.is_null() || tris.is_null() {
+        return PyResult::InvalidArgument as PyInt;
+    }
+    let bvh = match BVHBuildInfo::clone_bvh(id) {
+        Ok(bvh) => bvh,
+        Err(err) => return err as PyInt,
+    };
+    let (centers, extents) = unsafe {
+        (
+            std::slice::from_raw_parts(centers, num as usize),
+            std::slice::from_raw_parts(extents, num as usize),
+        )
+    };
+    let aabbs = centers
+        .iter()
+        .zip(extents.iter())
+        .map(|(center, extent)| {
+            let center = Vec3::new(center.x, center.y, center.z);
+            let half = Vec3::new(extent.x, extent.y, extent.z) / Vec3::new(2.0, 2.0, 2.0);
+            AABB::new(&(center - half), &(center + half))
+        })
+        .collect::<Vec<AABB>>();
+    let mut probes = vec![BVHProbe::default(); aabbs.len()];
+    BVHNode::probe_batch(&bvh, &aabbs, &mut probes);
+    for (idx, probe) in probes.iter().enumerate() {
+        unsafe {
+            std::ptr::write(leaves.wrapping_add(idx), probe.leaves as PyInt);
+            std::ptr::write(tris.wrapping_add(idx), probe.hit_tris as PyInt);
+        }
+    }
+    PyResult::Good as PyInt
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_frustum_cull(
+    id: PyInt,
+    eye: PyVec3,
+    forward: PyVec3,
+    up: PyVec3,
+    fov_y: PyFloat,
+    aspect: PyFloat,
+    near: PyFloat,
+    far: PyFloat,
+    cull: *mut PyFrustumCull,
+) -> PyInt {
+    if fov_y <= 0.0 || aspect <= 0.0 || near < 0.0 || far <= near {
+        return PyResult::InvalidArgument as PyInt;
+    }
+    let bvh = match BVHBuildInfo::clone_bvh(id) {
+        Ok(bvh) => bvh,
+        Err(err) => return err as PyInt,
+    };
+    let frustum = Frustum::perspective(
+        &Vec3::new(eye.x, eye.y, eye.z),
+        &Vec3::new(forward.x, forward.y, forward.z),
+        &Vec3::new(up.x, up.y, up.z),
+        fov_y,
+        aspect,
+        near,
+        far,
+    );
+    let result = BVHNode::frustum_cull(bvh, &frustum);
+    let pycull = PyFrustumCull {
+        visible_leaves: result.leaves.len() as PyInt,
+        visible_tris: result.visible_tris() as PyInt,
+        nodes_tested: result.nodes_tested as PyInt,
+        nodes_inside: result.nodes_inside as PyInt,
+        nodes_intersecting: result.nodes_intersecting as PyInt,
+        nodes_outside: result.nodes_outside as PyInt,
+    };
+    unsafe {
+        std::ptr::write(cull, pycull);
+    }
+    PyResult::Good as PyInt
+}
+
+fn write_sweep_hit(ret: i64, result: Option<SweepHit>, hit: *mut PySweepHit) -> PyInt {
+    if ret < 0 {
+        return ret as PyInt;
+    }
+    match result {
+        Some(result) => {
+            let pyhit = PySweepHit {
+                distance: result.distance,
+                indices: [
+                    result.tri_index.pt0 as PyInt,
+                    result.tri_index.pt1 as PyInt,
+                    result.tri_index.pt2 as PyInt,
+                ],
+            };
+            unsafe {
+                std::ptr::write(hit, pyhit);
+            }
+            1
+        }
+        None => 0,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_sweep_sphere(
+    id: PyInt,
+    center: PyVec3,
+    radius: PyFloat,
+    direction: PyVec3,
+    max_distance: PyFloat,
+    hit: *mut PySweepHit,
+) -> PyInt {
+    if radius <= 0.0 {
+        return PyResult::InvalidArgument as PyInt;
+    }
+    let sphere = Sphere::new(&Vec3::new(center.x, center.y, center.z), radius);
+    let direction = Vec3::new(direction.x, direction.y, direction.z);
+    let mut result = None;
+    let ret = BVHBuildInfo::sweep(
+        id,
+        &|bvh| BVHNode::sweep_sphere(bvh, &sphere, &direction, max_distance),
+        &mut result,
+    );
+    write_sweep_hit(ret, result, hit)
+}
+
+#[no_mangle]
+pub extern "C" fn BVHBuildInfo_sweep_box(
+    id: PyInt,
+    center: PyVec3,
+    half_extents: PyVec3,
+    roll: PyFloat,
+    pitch: PyFloat,
+    yaw: PyFloat,
+    direction: PyVec3,
+    max_distance: PyFloat,
+    hit: *mut PySweepHit,
+) -> PyInt {
+    let obb = OBB::from_euler(
+        &Vec3::new(center.x, center.y, center.z),
+        &Vec3::new(half_extents.x, half_extents.y, half_extents.z),
+        roll,
+        pitch,
+        yaw,
+    );
+    let direction = Vec3::new(direction.x, direction.y, direction.z);
+    let mut result = None;
+    let ret = BVHBuildInfo::sweep(
+        id,
+        &|bvh| BVHNode::sweep_box(bvh, &obb, &direction, max_distance),
+        &mut result,
+    );
+    write_sweep_hit(ret, result, hit)
+}
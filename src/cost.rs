@@ -0,0 +1,291 @@
+#![allow(dead_code)]
+
+use crate::batch::probe_into;
+use crate::bvh::{
+    block_overlap_peak_with, directional_hit_with, surface_hit_peak_with, PeakSample,
+};
+use crate::prelude::*;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::CostModel;
+    pub use super::PeakReport;
+}
+
+// 各项遍历开销的权重，单位微秒
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CostModel {
+    pub node_visit: f64,
+    pub leaf_visit: f64,
+    pub tri_test: f64,
+    // 叶子里每个三角形的取数/解码开销，不管有没有真正测试
+    pub tri_per_leaf: f64,
+}
+
+impl CostModel {
+    // 未标定的占位值，是按PhysX BVH34(BV4)中段的结构手估的，不是实测结果：
+    // 4叉量化节点解码加SIMD包围盒测试约6ns，三角形和盒子的SAT约35ns。
+    // 只适合比较同一场景的不同建树方式，要当绝对耗时用，先用fit在目标机器上标定
+    pub fn physx_bvh34() -> Self {
+        Self {
+            node_visit: 0.006,
+            leaf_visit: 0.012,
+            tri_test: 0.035,
+            tri_per_leaf: 0.004,
+        }
+    }
+
+    // 同样是未标定的占位值。BVH33(RTree)的包围盒是f32不用解码，但每页更大，单个节点略慢
+    pub fn physx_bvh33() -> Self {
+        Self {
+            node_visit: 0.008,
+            leaf_visit: 0.010,
+            tri_test: 0.035,
+            tri_per_leaf: 0.003,
+        }
+    }
+
+    // 标定：对同一批探测盒分别记下PhysX overlap的实测耗时(微秒)和probe_with_stats的计数，
+    // 对四项计数做最小二乘拟合。某一项计数和前面几项线性相关时(比如没有空间切分的树上
+    // probe的tri_tests总等于leaf_tris)，这一项分不出单独的权重，记为0，由前面的项吸收。
+    // 没有非零样本时返回None；拟合结果不保证非负，计时噪声大时需要更多样本
+    pub fn fit(samples: &[(QueryStats, f64)]) -> Option<Self> {
+        let features = |stats: &QueryStats| {
+            [
+                stats.nodes_visited as f64,
+                stats.leaves as f64,
+                stats.tri_tests as f64,
+                stats.leaf_tris as f64,
+            ]
+        };
+
+        // 正规方程 XᵀX w = Xᵀy
+        let mut ata = [[0.0f64; 4]; 4];
+        let mut atb = [0.0f64; 4];
+        for (stats, micros) in samples.iter() {
+            let x = features(stats);
+            for row in 0..4 {
+                for col in 0..4 {
+                    ata[row][col] += x[row] * x[col];
+                }
+                atb[row] += x[row] * micros;
+            }
+        }
+        let scale = (0..4).map(|idx| ata[idx][idx]).fold(0.0f64, f64::max);
+        if scale <= 0.0 {
+            return None;
+        }
+
+        // XᵀX对称半正定，按列顺序消元不用换行。消到某列时主元几乎为0，
+        // 说明这一列能由前面的列表示，把它换成w = 0的方程
+        for col in 0..4 {
+            if ata[col][col] <= scale * 1e-12 {
+                ata[col] = [0.0; 4];
+                ata[col][col] = 1.0;
+                atb[col] = 0.0;
+                for row in ata.iter_mut().skip(col + 1) {
+                    row[col] = 0.0;
+                }
+                continue;
+            }
+            let pivot_row = ata[col];
+            for row in col + 1..4 {
+                let factor = ata[row][col] / pivot_row[col];
+                for (value, pivot) in ata[row].iter_mut().zip(pivot_row.iter()).skip(col) {
+                    *value -= factor * pivot;
+                }
+                atb[row] -= factor * atb[col];
+            }
+        }
+        let mut weights = [0.0f64; 4];
+        for row in (0..4).rev() {
+            let rest = (row + 1..4).map(|k| ata[row][k] * weights[k]).sum::<f64>();
+            weights[row] = (atb[row] - rest) / ata[row][row];
+        }
+        Some(Self {
+            node_visit: weights[0],
+            leaf_visit: weights[1],
+            tri_test: weights[2],
+            tri_per_leaf: weights[3],
+        })
+    }
+
+    pub fn cost(&self, stats: &QueryStats) -> f64 {
+        stats.nodes_visited as f64 * self.node_visit
+            + stats.leaves as f64 * self.leaf_visit
+            + stats.tri_tests as f64 * self.tri_test
+            + stats.leaf_tris as f64 * self.tri_per_leaf
+    }
+}
+
+// PhysX 4.0以后默认的中段是BVH34
+impl Default for CostModel {
+    fn default() -> Self {
+        Self::physx_bvh34()
+    }
+}
+
+// 扫描中代价最高的那个探测盒的计数和代价，代价相同时取叶子多的，
+// 所以cost总能由同一行的计数按cost_model重新算出来。
+// peak_leaves是整个扫描的叶子数峰值，不一定来自同一个探测盒
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PeakReport {
+    pub leaves: usize,
+    pub nodes_visited: usize,
+    pub leaf_tris: usize,
+    pub tri_tests: usize,
+    pub cost: f64,
+    pub peak_leaves: usize,
+}
+
+impl PeakReport {
+    // 这个探测盒参与代价计算的那几项计数
+    pub fn stats(&self) -> QueryStats {
+        QueryStats {
+            nodes_visited: self.nodes_visited,
+            leaves: self.leaves,
+            leaf_tris: self.leaf_tris,
+            tri_tests: self.tri_tests,
+            ..QueryStats::default()
+        }
+    }
+}
+
+impl PeakSample for PeakReport {
+    fn peak(self, other: Self) -> Self {
+        let worst =
+            if other.cost > self.cost || (other.cost == self.cost && other.leaves > self.leaves) {
+                other
+            } else {
+                self
+            };
+        Self {
+            peak_leaves: self.peak_leaves.max(other.peak_leaves),
+            ..worst
+        }
+    }
+}
+
+impl BVHNode {
+    // 和directional_hit同样的步进，每个探测盒做一次完整的probe
+    pub fn directional_hit_report(
+        bvh: Arc<Self>,
+        block_size: &Vec3,
+        start: &Vec3,
+        end: &Vec3,
+        step_into: f64,
+        break_on_hit: bool,
+        cost_model: &CostModel,
+    ) -> PeakReport {
+        let report = |aabb: &AABB| probe_report(&bvh, aabb, cost_model);
+        directional_hit_with(&report, block_size, start, end, step_into, break_on_hit)
+    }
+
+    // 和block_overlap_peak同样的扫描，同时给出原始计数和按cost_model折算的代价
+    pub fn block_overlap_peak_report(
+        bvh: Arc<Self>,
+        step: f64,
+        cost_model: &CostModel,
+    ) -> PeakReport {
+        Self::block_overlap_peak_report_parallel(bvh, step, cost_model, 1)
+    }
+
+    pub fn block_overlap_peak_report_parallel(
+        bvh: Arc<Self>,
+        step: f64,
+        cost_model: &CostModel,
+        num_workers: usize,
+    ) -> PeakReport {
+        let report = |aabb: &AABB| probe_report(&bvh, aabb, cost_model);
+        block_overlap_peak_with(&bvh.aabb, &report, step, num_workers)
+    }
+
+    pub fn surface_hit_peak_report(
+        bvh: Arc<Self>,
+        step: f64,
+        block_size: &Vec3,
+        cost_model: &CostModel,
+    ) -> PeakReport {
+        Self::surface_hit_peak_report_parallel(bvh, step, block_size, cost_model, 1)
+    }
+
+    pub fn surface_hit_peak_report_parallel(
+        bvh: Arc<Self>,
+        step: f64,
+        block_size: &Vec3,
+        cost_model: &CostModel,
+        num_workers: usize,
+    ) -> PeakReport {
+        let report = |aabb: &AABB| probe_report(&bvh, aabb, cost_model);
+        surface_hit_peak_with(&bvh.aabb, &report, step, block_size, num_workers)
+    }
+}
+
+impl FlatBVH {
+    pub fn directional_hit_report(
+        &self,
+        block_size: &Vec3,
+        start: &Vec3,
+        end: &Vec3,
+        step_into: f64,
+        break_on_hit: bool,
+        cost_model: &CostModel,
+    ) -> PeakReport {
+        let report = |aabb: &AABB| probe_report(self.node_ref(0), aabb, cost_model);
+        directional_hit_with(&report, block_size, start, end, step_into, break_on_hit)
+    }
+
+    pub fn block_overlap_peak_report(&self, step: f64, cost_model: &CostModel) -> PeakReport {
+        self.block_overlap_peak_report_parallel(step, cost_model, 1)
+    }
+
+    pub fn block_overlap_peak_report_parallel(
+        &self,
+        step: f64,
+        cost_model: &CostModel,
+        num_workers: usize,
+    ) -> PeakReport {
+        let report = |aabb: &AABB| probe_report(self.node_ref(0), aabb, cost_model);
+        block_overlap_peak_with(&self.root().aabb, &report, step, num_workers)
+    }
+
+    pub fn surface_hit_peak_report(
+        &self,
+        step: f64,
+        block_size: &Vec3,
+        cost_model: &CostModel,
+    ) -> PeakReport {
+        self.surface_hit_peak_report_parallel(step, block_size, cost_model, 1)
+    }
+
+    pub fn surface_hit_peak_report_parallel(
+        &self,
+        step: f64,
+        block_size: &Vec3,
+        cost_model: &CostModel,
+        num_workers: usize,
+    ) -> PeakReport {
+        let report = |aabb: &AABB| probe_report(self.node_ref(0), aabb, cost_model);
+        surface_hit_peak_with(&self.root().aabb, &report, step, block_size, num_workers)
+    }
+}
+
+fn probe_report<'a, N: BVHNodeRef<'a>>(root: N, aabb: &AABB, cost_model: &CostModel) -> PeakReport {
+    let mut stats = QueryStats::default();
+    probe_into(
+        root,
+        aabb,
+        &mut BVHProbe::default(),
+        &mut HashSet::new(),
+        &mut stats,
+    );
+    PeakReport {
+        leaves: stats.leaves,
+        nodes_visited: stats.nodes_visited,
+        leaf_tris: stats.leaf_tris,
+        tri_tests: stats.tri_tests,
+        cost: cost_model.cost(&stats),
+        peak_leaves: stats.leaves,
+    }
+}
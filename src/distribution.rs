@@ -0,0 +1,203 @@
+#![allow(dead_code)]
+
+use crate::bvh::{block_overlap_scan, surface_hit_scan};
+use crate::prelude::*;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::PeakDistribution;
+}
+
+// 扫描中所有探测盒碰到的叶子数的分布，用来区分单个热点和整体偏贵的网格。
+// 包围盒外圈和空旷处的探测盒一个叶子都碰不到，数量只取决于场景的形状，
+// 所以单独记在empty_samples里，mean和各分位数只统计碰到叶子的探测盒
+#[derive(Clone, Debug, Default)]
+pub struct PeakDistribution {
+    // 包括空探测盒在内的总数
+    pub samples: usize,
+    // 就是histogram[0]
+    pub empty_samples: usize,
+    pub peak: usize,
+    pub mean: f64,
+    pub median: usize,
+    pub p90: usize,
+    pub p99: usize,
+    // histogram[n]是碰到n个叶子的探测盒数
+    pub histogram: Vec<usize>,
+    // 按列的顺序第一个达到峰值的探测盒
+    pub worst_center: Vec3,
+    pub worst_aabb: AABB,
+}
+
+// 单列的累加结果，叶子数是小整数，直方图就能给出精确的分位数
+#[derive(Default)]
+struct ColumnHistogram {
+    histogram: Vec<usize>,
+    worst: Option<(usize, AABB)>,
+}
+
+impl ColumnHistogram {
+    fn add(&mut self, count: usize, aabb: &AABB) {
+        if self.histogram.len() <= count {
+            self.histogram.resize(count + 1, 0);
+        }
+        self.histogram[count] += 1;
+        if self.worst.as_ref().is_none_or(|(worst, _)| count > *worst) {
+            self.worst = Some((count, aabb.clone()));
+        }
+    }
+}
+
+impl PeakDistribution {
+    // 按列的顺序合并，峰值相同时保留先出现的探测盒，结果和线程数无关
+    fn from_columns(columns: Vec<ColumnHistogram>) -> Self {
+        let mut histogram = Vec::<usize>::new();
+        let mut worst: Option<(usize, AABB)> = None;
+        for column in columns {
+            if histogram.len() < column.histogram.len() {
+                histogram.resize(column.histogram.len(), 0);
+            }
+            for (count, num) in column.histogram.iter().enumerate() {
+                histogram[count] += num;
+            }
+            if let Some((count, aabb)) = column.worst {
+                if worst.as_ref().is_none_or(|(worst, _)| count > *worst) {
+                    worst = Some((count, aabb));
+                }
+            }
+        }
+
+        let samples = histogram.iter().sum::<usize>();
+        let empty_samples = histogram.first().copied().unwrap_or(0);
+        let total = histogram
+            .iter()
+            .enumerate()
+            .map(|(count, num)| count * num)
+            .sum::<usize>();
+        let mut ret = Self {
+            samples,
+            empty_samples,
+            mean: if samples > empty_samples {
+                total as f64 / (samples - empty_samples) as f64
+            } else {
+                0.0
+            },
+            histogram,
+            ..Self::default()
+        };
+        if let Some((peak, aabb)) = worst {
+            ret.peak = peak;
+            ret.worst_center = aabb.center();
+            ret.worst_aabb = aabb;
+        }
+        ret.median = ret.percentile(0.5);
+        ret.p90 = ret.percentile(0.9);
+        ret.p99 = ret.percentile(0.99);
+        ret
+    }
+
+    // 最近秩法：碰到叶子的探测盒里至少p比例的叶子数不超过返回值，全是空探测盒时返回0
+    pub fn percentile(&self, p: f64) -> usize {
+        let nonempty = self.histogram.iter().skip(1).sum::<usize>();
+        if nonempty == 0 {
+            return 0;
+        }
+        let rank = ((p.clamp(0.0, 1.0) * nonempty as f64).ceil() as usize).max(1);
+        let mut acc = 0_usize;
+        for (count, num) in self.histogram.iter().enumerate().skip(1) {
+            acc += num;
+            if acc >= rank {
+                return count;
+            }
+        }
+        self.peak
+    }
+}
+
+impl BVHNode {
+    // 和block_overlap_peak同样的扫描，保留每个探测盒的叶子数而不只是最大值
+    pub fn block_overlap_distribution(bvh: Arc<Self>, step: f64) -> PeakDistribution {
+        Self::block_overlap_distribution_parallel(bvh, step, 1)
+    }
+
+    pub fn block_overlap_distribution_parallel(
+        bvh: Arc<Self>,
+        step: f64,
+        num_workers: usize,
+    ) -> PeakDistribution {
+        let probe = |column: &mut ColumnHistogram, aabb: &AABB| {
+            column.add(Self::count_overlapped_leaves(&bvh, aabb), aabb);
+        };
+        PeakDistribution::from_columns(block_overlap_scan(&bvh.aabb, step, num_workers, &probe))
+    }
+
+    pub fn surface_hit_distribution(
+        bvh: Arc<Self>,
+        step: f64,
+        block_size: &Vec3,
+    ) -> PeakDistribution {
+        Self::surface_hit_distribution_parallel(bvh, step, block_size, 1)
+    }
+
+    pub fn surface_hit_distribution_parallel(
+        bvh: Arc<Self>,
+        step: f64,
+        block_size: &Vec3,
+        num_workers: usize,
+    ) -> PeakDistribution {
+        let probe = |column: &mut ColumnHistogram, aabb: &AABB| {
+            column.add(Self::count_overlapped_leaves(&bvh, aabb), aabb);
+        };
+        PeakDistribution::from_columns(surface_hit_scan(
+            &bvh.aabb,
+            step,
+            block_size,
+            num_workers,
+            &probe,
+        ))
+    }
+}
+
+impl FlatBVH {
+    pub fn block_overlap_distribution(&self, step: f64) -> PeakDistribution {
+        self.block_overlap_distribution_parallel(step, 1)
+    }
+
+    pub fn block_overlap_distribution_parallel(
+        &self,
+        step: f64,
+        num_workers: usize,
+    ) -> PeakDistribution {
+        let probe = |column: &mut ColumnHistogram, aabb: &AABB| {
+            column.add(self.count_overlapped_leaves(aabb), aabb);
+        };
+        PeakDistribution::from_columns(block_overlap_scan(
+            &self.root().aabb,
+            step,
+            num_workers,
+            &probe,
+        ))
+    }
+
+    pub fn surface_hit_distribution(&self, step: f64, block_size: &Vec3) -> PeakDistribution {
+        self.surface_hit_distribution_parallel(step, block_size, 1)
+    }
+
+    pub fn surface_hit_distribution_parallel(
+        &self,
+        step: f64,
+        block_size: &Vec3,
+        num_workers: usize,
+    ) -> PeakDistribution {
+        let probe = |column: &mut ColumnHistogram, aabb: &AABB| {
+            column.add(self.count_overlapped_leaves(aabb), aabb);
+        };
+        PeakDistribution::from_columns(surface_hit_scan(
+            &self.root().aabb,
+            step,
+            block_size,
+            num_workers,
+            &probe,
+        ))
+    }
+}
@@ -0,0 +1,501 @@
+#![allow(dead_code)]
+
+use crate::bvh::{
+    binary_split, block_overlap_peak_with, directional_hit_with, is_binary_split,
+    surface_hit_peak_with, tris_aabb, SplitInput,
+};
+use crate::visit::{traverse_node, traverse_node_ordered};
+use crate::{lbvh, prelude::*};
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::FlatBVH;
+    pub use super::FlatBVHNode;
+}
+
+// 同一个节点的孩子在nodes里连续存放，三角形区间按深度优先排列，
+// 所以内部节点的区间刚好是它所有叶子区间拼起来
+#[derive(Clone, Debug)]
+pub struct FlatBVHNode {
+    pub aabb: AABB,
+    pub first_child: usize,
+    pub num_children: usize,
+    pub first_tri: usize,
+    pub num_tris: usize,
+}
+
+impl FlatBVHNode {
+    pub fn is_leaf(&self) -> bool {
+        self.num_children == 0
+    }
+
+    pub fn children(&self) -> std::ops::Range<usize> {
+        self.first_child..self.first_child + self.num_children
+    }
+
+    pub fn tris(&self) -> std::ops::Range<usize> {
+        self.first_tri..self.first_tri + self.num_tris
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FlatBVH {
+    pub vtx_buf: Arc<Vec<Vec3>>,
+    pub nodes: Vec<FlatBVHNode>,
+    pub tris: Vec<TriIndex>,
+}
+
+// 查询内部用的节点句柄，对外的结果仍然是节点下标
+#[derive(Copy, Clone)]
+pub(crate) struct FlatNodeRef<'a> {
+    pub bvh: &'a FlatBVH,
+    pub idx: usize,
+}
+
+impl<'a> BVHNodeRef<'a> for FlatNodeRef<'a> {
+    fn aabb(self) -> &'a AABB {
+        &self.bvh.nodes[self.idx].aabb
+    }
+
+    fn is_leaf(self) -> bool {
+        self.bvh.nodes[self.idx].is_leaf()
+    }
+
+    fn tris(self) -> &'a [TriIndex] {
+        self.bvh.node_tris(self.idx)
+    }
+
+    fn vtx_buf(self) -> &'a [Vec3] {
+        &self.bvh.vtx_buf
+    }
+
+    fn children(self) -> impl DoubleEndedIterator<Item = Self> {
+        let bvh = self.bvh;
+        bvh.nodes[self.idx]
+            .children()
+            .map(move |idx| Self { bvh, idx })
+    }
+
+    fn same_node(self, other: Self) -> bool {
+        self.idx == other.idx
+    }
+}
+
+// 把下标访问者接到通用遍历上
+struct IndexVisitor<'v, V>(&'v mut V);
+
+impl<'a, V: BVHVisitor<'a, usize>> BVHVisitor<'a, FlatNodeRef<'a>> for IndexVisitor<'_, V> {
+    fn visit_node(&mut self, node: FlatNodeRef<'a>) -> VisitResult {
+        self.0.visit_node(node.idx)
+    }
+
+    fn visit_leaf(&mut self, leaf: FlatNodeRef<'a>) -> VisitResult {
+        self.0.visit_leaf(leaf.idx)
+    }
+
+    fn priority(&mut self, node: FlatNodeRef<'a>) -> Option<f64> {
+        self.0.priority(node.idx)
+    }
+
+    fn visit_ordered(&mut self, node: FlatNodeRef<'a>, priority: f64) -> VisitResult {
+        self.0.visit_ordered(node.idx, priority)
+    }
+}
+
+impl FlatBVH {
+    pub fn from_tree(bvh: Arc<BVHNode>) -> Self {
+        let mut ret = Self {
+            vtx_buf: bvh.vtx_buf.clone(),
+            nodes: vec![Self::placeholder()],
+            tris: Vec::<TriIndex>::new(),
+        };
+        ret.flatten(&bvh, 0);
+        ret
+    }
+
+    // 二分切分和LBVH直接在全局三角形数组上原地划分，不建指针树。
+    // 空间切分会复制引用，PhysX的4叉树要先建二叉树再合并，这两类仍然经过指针树
+    pub fn build(vtx_buf: Arc<Vec<Vec3>>, idx_buf: Vec<TriIndex>, cfg: BVHSubdivideConfig) -> Self {
+        if idx_buf.is_empty() {
+            return Self::from_tree(Arc::new(BVHNode::new(vtx_buf, idx_buf)));
+        }
+        if is_binary_split(cfg.split_method) {
+            let num_tris = idx_buf.len();
+            let mut ret = Self {
+                vtx_buf,
+                nodes: vec![Self::placeholder()],
+                tris: idx_buf,
+            };
+            ret.emit_binary(0, num_tris, 0, &cfg);
+            return ret;
+        }
+        if cfg.split_method != BVHSplitMethod::LBVH {
+            let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+            bvh.subdivide(cfg);
+            return Self::from_tree(Arc::new(bvh));
+        }
+        let sorted = lbvh::sort_by_morton_code(&vtx_buf, &idx_buf, &cfg);
+        let mut ret = Self {
+            vtx_buf,
+            nodes: vec![Self::placeholder()],
+            tris: sorted
+                .iter()
+                .map(|(_, tri_index)| tri_index.clone())
+                .collect(),
+        };
+        ret.emit_lbvh(&sorted, 0, 0, &cfg);
+        ret
+    }
+
+    fn placeholder() -> FlatBVHNode {
+        FlatBVHNode {
+            aabb: AABB::default(),
+            first_child: 0,
+            num_children: 0,
+            first_tri: 0,
+            num_tris: 0,
+        }
+    }
+
+    fn flatten(&mut self, node: &BVHNode, idx: usize) {
+        let first_tri = self.tris.len();
+        let first_child = self.nodes.len();
+        if node.is_leaf() {
+            self.tris.extend(node.idx_buf.iter().cloned());
+        } else {
+            for _ in node.children.iter() {
+                self.nodes.push(Self::placeholder());
+            }
+            for (offset, child) in node.children.iter().enumerate() {
+                self.flatten(child, first_child + offset);
+            }
+        }
+        self.nodes[idx] = FlatBVHNode {
+            aabb: node.aabb.clone(),
+            first_child,
+            num_children: node.children.len(),
+            first_tri,
+            num_tris: self.tris.len() - first_tri,
+        };
+    }
+
+    fn emit_lbvh(
+        &mut self,
+        sorted: &[(u64, TriIndex)],
+        first_tri: usize,
+        idx: usize,
+        cfg: &BVHSubdivideConfig,
+    ) {
+        let first_child = self.nodes.len();
+        if sorted.len() <= cfg.num_tris_per_leaf {
+            self.nodes[idx] = FlatBVHNode {
+                aabb: tris_aabb(
+                    &self.vtx_buf,
+                    &self.tris[first_tri..first_tri + sorted.len()],
+                ),
+                first_child,
+                num_children: 0,
+                first_tri,
+                num_tris: sorted.len(),
+            };
+            return;
+        }
+        let sep = lbvh::find_split(sorted);
+        self.nodes.push(Self::placeholder());
+        self.nodes.push(Self::placeholder());
+        self.emit_lbvh(&sorted[..sep], first_tri, first_child, cfg);
+        self.emit_lbvh(&sorted[sep..], first_tri + sep, first_child + 1, cfg);
+        self.nodes[idx] = FlatBVHNode {
+            aabb: self.nodes[first_child]
+                .aabb
+                .merge(&self.nodes[first_child + 1].aabb),
+            first_child,
+            num_children: 2,
+            first_tri,
+            num_tris: sorted.len(),
+        };
+    }
+
+    // 和BVHNode::subdivide的切分一致，pos一侧排在区间前面
+    fn emit_binary(
+        &mut self,
+        first_tri: usize,
+        num_tris: usize,
+        idx: usize,
+        cfg: &BVHSubdivideConfig,
+    ) {
+        let range = first_tri..first_tri + num_tris;
+        let aabb = tris_aabb(&self.vtx_buf, &self.tris[range.clone()]);
+        let first_child = self.nodes.len();
+        if num_tris <= cfg.num_tris_per_leaf {
+            self.nodes[idx] = FlatBVHNode {
+                aabb,
+                first_child,
+                num_children: 0,
+                first_tri,
+                num_tris,
+            };
+            return;
+        }
+        let (pos_tri_idx, neg_tri_idx) = binary_split(
+            &SplitInput {
+                vtx_buf: &self.vtx_buf,
+                idx_buf: &self.tris[range.clone()],
+                aabb: &aabb,
+            },
+            cfg,
+        );
+        let sep = pos_tri_idx.len();
+        self.tris[first_tri..first_tri + sep].clone_from_slice(&pos_tri_idx);
+        self.tris[first_tri + sep..range.end].clone_from_slice(&neg_tri_idx);
+
+        self.nodes.push(Self::placeholder());
+        self.nodes.push(Self::placeholder());
+        self.emit_binary(first_tri, sep, first_child, cfg);
+        self.emit_binary(first_tri + sep, num_tris - sep, first_child + 1, cfg);
+        self.nodes[idx] = FlatBVHNode {
+            aabb,
+            first_child,
+            num_children: 2,
+            first_tri,
+            num_tris,
+        };
+    }
+
+    pub fn root(&self) -> &FlatBVHNode {
+        &self.nodes[0]
+    }
+
+    pub fn node_tris(&self, idx: usize) -> &[TriIndex] {
+        &self.tris[self.nodes[idx].tris()]
+    }
+
+    // 和BVHNode::traverse一样的深度优先和VisitResult语义，交给访问者的是节点下标
+    pub fn traverse<'a, V: BVHVisitor<'a, usize>>(&'a self, visitor: &mut V) -> bool {
+        self.traverse_with_stats(visitor, &mut QueryStats::default())
+    }
+
+    // 计数口径和BVHNode::traverse_with_stats一样
+    pub fn traverse_with_stats<'a, V: BVHVisitor<'a, usize>>(
+        &'a self,
+        visitor: &mut V,
+        stats: &mut QueryStats,
+    ) -> bool {
+        traverse_node(self.node_ref(0), &mut IndexVisitor(visitor), stats)
+    }
+
+    // 和BVHNode::traverse_ordered一样priority小的先访问
+    pub fn traverse_ordered<'a, V: BVHVisitor<'a, usize>>(&'a self, visitor: &mut V) -> bool {
+        self.traverse_ordered_with_stats(visitor, &mut QueryStats::default())
+    }
+
+    pub fn traverse_ordered_with_stats<'a, V: BVHVisitor<'a, usize>>(
+        &'a self,
+        visitor: &mut V,
+        stats: &mut QueryStats,
+    ) -> bool {
+        traverse_node_ordered(self.node_ref(0), &mut IndexVisitor(visitor), stats)
+    }
+
+    pub(crate) fn node_ref(&self, idx: usize) -> FlatNodeRef<'_> {
+        FlatNodeRef { bvh: self, idx }
+    }
+
+    // 和BVHNode::traverse_shape一样，剪枝的包围盒测试记进stats
+    fn traverse_aabb<L: FnMut(usize) -> VisitResult>(
+        &self,
+        aabb: &AABB,
+        leaf: L,
+        stats: &mut QueryStats,
+    ) {
+        let mut aabb_tests = 0_usize;
+        self.traverse_with_stats(
+            &mut FnVisitor::new(
+                |idx: usize| {
+                    aabb_tests += 1;
+                    if self.nodes[idx].aabb.intersect_with_aabb(aabb) {
+                        VisitResult::Continue
+                    } else {
+                        VisitResult::Skip
+                    }
+                },
+                leaf,
+            ),
+            stats,
+        );
+        stats.aabb_tests += aabb_tests;
+    }
+
+    pub fn get_interseced_leaves(&self, aabb: &AABB) -> Vec<usize> {
+        self.get_interseced_leaves_with_stats(aabb, &mut QueryStats::default())
+    }
+
+    pub fn get_interseced_leaves_with_stats(
+        &self,
+        aabb: &AABB,
+        stats: &mut QueryStats,
+    ) -> Vec<usize> {
+        let mut ret = Vec::<usize>::new();
+        self.traverse_aabb(
+            aabb,
+            |idx| {
+                ret.push(idx);
+                VisitResult::Continue
+            },
+            stats,
+        );
+        ret
+    }
+
+    // 不分配的计数版本，峰值扫描每个探测盒都要调用一次
+    pub fn count_interseced_leaves(&self, aabb: &AABB) -> usize {
+        self.count_interseced_leaves_with_stats(aabb, &mut QueryStats::default())
+    }
+
+    pub fn count_interseced_leaves_with_stats(&self, aabb: &AABB, stats: &mut QueryStats) -> usize {
+        let mut count = 0_usize;
+        self.traverse_aabb(
+            aabb,
+            |_| {
+                count += 1;
+                VisitResult::Continue
+            },
+            stats,
+        );
+        count
+    }
+
+    // 和BVHNode的批量查询一样只处理较短的一方，返回实际处理的查询数
+    pub fn count_interseced_leaves_batch(&self, aabbs: &[AABB], counts: &mut [usize]) -> usize {
+        self.count_interseced_leaves_batch_with_stats(aabbs, counts, &mut QueryStats::default())
+    }
+
+    pub fn count_interseced_leaves_batch_with_stats(
+        &self,
+        aabbs: &[AABB],
+        counts: &mut [usize],
+        stats: &mut QueryStats,
+    ) -> usize {
+        for (aabb, count) in aabbs.iter().zip(counts.iter_mut()) {
+            *count = self.count_interseced_leaves_with_stats(aabb, stats);
+        }
+        aabbs.len().min(counts.len())
+    }
+
+    pub fn get_intersected_tris(&self, aabb: &AABB) -> Vec<TriIndex> {
+        self.get_intersected_tris_with_stats(aabb, &mut QueryStats::default())
+    }
+
+    pub fn get_intersected_tris_with_stats(
+        &self,
+        aabb: &AABB,
+        stats: &mut QueryStats,
+    ) -> Vec<TriIndex> {
+        let mut ret = Vec::<TriIndex>::new();
+        self.collect_overlapped_tris_with_stats(aabb, &mut ret, stats);
+        ret
+    }
+
+    pub fn probe(&self, aabb: &AABB) -> BVHProbe {
+        self.probe_with_stats(aabb, &mut QueryStats::default())
+    }
+
+    pub fn probe_with_stats(&self, aabb: &AABB, stats: &mut QueryStats) -> BVHProbe {
+        let mut ret = BVHProbe::default();
+        self.probe_into_with_stats(aabb, &mut ret, stats);
+        ret
+    }
+
+    pub fn get_all_nodes(&self) -> Vec<usize> {
+        (0..self.nodes.len()).collect()
+    }
+
+    pub fn get_all_leaves(&self) -> Vec<usize> {
+        (0..self.nodes.len())
+            .filter(|idx| self.nodes[*idx].is_leaf())
+            .collect()
+    }
+
+    pub fn sibling_overlap(&self) -> f64 {
+        let root_area = self.root().aabb.surface_area();
+        let mut overlap = 0.0f64;
+        for node in self.nodes.iter() {
+            for lhs in node.children() {
+                for rhs in lhs + 1..node.first_child + node.num_children {
+                    if let Some(aabb) = self.nodes[lhs].aabb.intersection(&self.nodes[rhs].aabb) {
+                        overlap += aabb.surface_area();
+                    }
+                }
+            }
+        }
+        overlap / root_area
+    }
+
+    pub fn reference_count(&self) -> usize {
+        self.tris.len()
+    }
+
+    pub fn directional_hit(
+        &self,
+        block_size: &Vec3,
+        start: &Vec3,
+        end: &Vec3,
+        step_into: f64,
+        break_on_hit: bool,
+    ) -> usize {
+        let leaf_count = |aabb: &AABB| self.count_interseced_leaves(aabb);
+        directional_hit_with(&leaf_count, block_size, start, end, step_into, break_on_hit)
+    }
+
+    pub fn block_overlap_peak(&self, step: f64) -> usize {
+        self.block_overlap_peak_parallel(step, 1)
+    }
+
+    pub fn block_overlap_peak_parallel(&self, step: f64, num_workers: usize) -> usize {
+        let leaf_count = |aabb: &AABB| self.count_interseced_leaves(aabb);
+        block_overlap_peak_with(&self.root().aabb, &leaf_count, step, num_workers)
+    }
+
+    pub fn block_overlap_tri_peak(&self, step: f64) -> usize {
+        self.block_overlap_tri_peak_parallel(step, 1)
+    }
+
+    pub fn block_overlap_tri_peak_parallel(&self, step: f64, num_workers: usize) -> usize {
+        let hit_count = |aabb: &AABB| self.probe(aabb).hit_tris;
+        block_overlap_peak_with(&self.root().aabb, &hit_count, step, num_workers)
+    }
+
+    pub fn surface_hit_peak(&self, step: f64, block_size: &Vec3) -> usize {
+        self.surface_hit_peak_parallel(step, block_size, 1)
+    }
+
+    pub fn surface_hit_peak_parallel(
+        &self,
+        step: f64,
+        block_size: &Vec3,
+        num_workers: usize,
+    ) -> usize {
+        let leaf_count = |aabb: &AABB| self.count_interseced_leaves(aabb);
+        surface_hit_peak_with(
+            &self.root().aabb,
+            &leaf_count,
+            step,
+            block_size,
+            num_workers,
+        )
+    }
+
+    pub fn surface_hit_tri_peak(&self, step: f64, block_size: &Vec3) -> usize {
+        self.surface_hit_tri_peak_parallel(step, block_size, 1)
+    }
+
+    pub fn surface_hit_tri_peak_parallel(
+        &self,
+        step: f64,
+        block_size: &Vec3,
+        num_workers: usize,
+    ) -> usize {
+        let hit_count = |aabb: &AABB| self.probe(aabb).hit_tris;
+        surface_hit_peak_with(&self.root().aabb, &hit_count, step, block_size, num_workers)
+    }
+}
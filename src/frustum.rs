@@ -0,0 +1,260 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use crate::tri::{tri_key, TriKey};
+use crate::visit::{for_each_leaf, traverse_node};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::Frustum;
+    pub use super::FrustumClass;
+    pub use super::FrustumCullResult;
+    pub use super::Plane;
+}
+
+// normal·pt + d >= 0 的一侧是内部
+#[derive(Copy, Clone, Debug)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub d: f64,
+}
+
+impl Plane {
+    pub fn new(normal: &Vec3, d: f64) -> Self {
+        Self { normal: *normal, d }
+    }
+
+    // 过pt且inside在内侧的平面
+    pub fn from_point(normal: &Vec3, pt: &Vec3, inside: &Vec3) -> Self {
+        let mut normal = *normal;
+        normal.normalize();
+        let mut ret = Self::new(&normal, -normal.dot(pt));
+        if ret.distance(inside) < 0.0 {
+            ret = Self::new(&(Vec3::default() - normal), normal.dot(pt));
+        }
+        ret
+    }
+
+    pub fn distance(&self, pt: &Vec3) -> f64 {
+        self.normal.dot(pt) + self.d
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrustumClass {
+    Inside,
+    Outside,
+    Intersecting,
+}
+
+// 顺序是near, far, left, right, bottom, top
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    pub fn new(planes: &[Plane; 6]) -> Self {
+        Self { planes: *planes }
+    }
+
+    // fov_y是竖直方向的全角(弧度)，aspect是宽/高
+    pub fn perspective(
+        eye: &Vec3,
+        forward: &Vec3,
+        up: &Vec3,
+        fov_y: f64,
+        aspect: f64,
+        near: f64,
+        far: f64,
+    ) -> Self {
+        let scale = |v: Vec3, t: f64| v * Vec3::new(t, t, t);
+        let mut forward = *forward;
+        forward.normalize();
+        let mut right = forward.cross(up);
+        right.normalize();
+        let up = right.cross(&forward);
+        let half_y = (fov_y / 2.0).tan();
+        let half_x = half_y * aspect;
+
+        // 视线方向上的一点一定在视锥内，用它确定每个平面的朝向
+        let inside = *eye + scale(forward, (near + far) / 2.0);
+        let near_pt = *eye + scale(forward, near);
+        let far_pt = *eye + scale(forward, far);
+        let left_dir = forward - scale(right, half_x);
+        let right_dir = forward + scale(right, half_x);
+        let bottom_dir = forward - scale(up, half_y);
+        let top_dir = forward + scale(up, half_y);
+        Self::new(&[
+            Plane::from_point(&forward, &near_pt, &inside),
+            Plane::from_point(&forward, &far_pt, &inside),
+            Plane::from_point(&up.cross(&left_dir), eye, &inside),
+            Plane::from_point(&up.cross(&right_dir), eye, &inside),
+            Plane::from_point(&right.cross(&bottom_dir), eye, &inside),
+            Plane::from_point(&right.cross(&top_dir), eye, &inside),
+        ])
+    }
+
+    // 每个平面只看离它最远和最近的两个角点
+    pub fn classify_aabb(&self, aabb: &AABB) -> FrustumClass {
+        let mut ret = FrustumClass::Inside;
+        for plane in self.planes.iter() {
+            let n = plane.normal;
+            let positive = Vec3::new(
+                if n.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if n.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if n.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            if plane.distance(&positive) < 0.0 {
+                return FrustumClass::Outside;
+            }
+            let negative = Vec3::new(
+                if n.x >= 0.0 { aabb.min.x } else { aabb.max.x },
+                if n.y >= 0.0 { aabb.min.y } else { aabb.max.y },
+                if n.z >= 0.0 { aabb.min.z } else { aabb.max.z },
+            );
+            if plane.distance(&negative) < 0.0 {
+                ret = FrustumClass::Intersecting;
+            }
+        }
+        ret
+    }
+
+    pub fn point_inside(&self, pt: &Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.distance(pt) >= 0.0)
+    }
+}
+
+// BVHNode的结果里叶子是节点，FlatBVH的结果里是节点下标
+#[derive(Clone)]
+pub struct FrustumCullResult<L = Arc<BVHNode>> {
+    pub leaves: Vec<L>,
+    // 真正做了分类测试的节点，完全在内部的子树不算
+    pub nodes_tested: usize,
+    pub nodes_inside: usize,
+    pub nodes_intersecting: usize,
+    pub nodes_outside: usize,
+}
+
+impl<L> Default for FrustumCullResult<L> {
+    fn default() -> Self {
+        Self {
+            leaves: Vec::<L>::new(),
+            nodes_tested: 0,
+            nodes_inside: 0,
+            nodes_intersecting: 0,
+            nodes_outside: 0,
+        }
+    }
+}
+
+impl<L> FrustumCullResult<L> {
+    fn map_leaves<T, F: FnMut(L) -> T>(self, f: F) -> FrustumCullResult<T> {
+        FrustumCullResult {
+            leaves: self.leaves.into_iter().map(f).collect(),
+            nodes_tested: self.nodes_tested,
+            nodes_inside: self.nodes_inside,
+            nodes_intersecting: self.nodes_intersecting,
+            nodes_outside: self.nodes_outside,
+        }
+    }
+}
+
+impl FrustumCullResult {
+    // 可见叶子里去重后的三角形数，空间切分时同一个三角形可能在多个可见叶子里
+    pub fn visible_tris(&self) -> usize {
+        unique_tris(self.leaves.iter().flat_map(|leaf| leaf.idx_buf.iter()))
+    }
+}
+
+fn unique_tris<'t, I: Iterator<Item = &'t TriIndex>>(tris: I) -> usize {
+    tris.map(tri_key).collect::<HashSet<TriKey>>().len()
+}
+
+impl BVHNode {
+    pub fn frustum_cull(bvh: Arc<Self>, frustum: &Frustum) -> FrustumCullResult {
+        Self::frustum_cull_with_stats(bvh, frustum, &mut QueryStats::default())
+    }
+
+    // 完全在内部的子树不遍历，它的叶子直接算进leaves和leaf_tris
+    pub fn frustum_cull_with_stats(
+        bvh: Arc<Self>,
+        frustum: &Frustum,
+        stats: &mut QueryStats,
+    ) -> FrustumCullResult {
+        frustum_cull(&bvh, frustum, stats).map_leaves(|leaf| leaf.clone())
+    }
+}
+
+impl FlatBVH {
+    pub fn frustum_cull(&self, frustum: &Frustum) -> FrustumCullResult<usize> {
+        self.frustum_cull_with_stats(frustum, &mut QueryStats::default())
+    }
+
+    pub fn frustum_cull_with_stats(
+        &self,
+        frustum: &Frustum,
+        stats: &mut QueryStats,
+    ) -> FrustumCullResult<usize> {
+        frustum_cull(self.node_ref(0), frustum, stats).map_leaves(|leaf| leaf.idx)
+    }
+
+    // 和FrustumCullResult::visible_tris一样去重
+    pub fn visible_tris(&self, result: &FrustumCullResult<usize>) -> usize {
+        unique_tris(result.leaves.iter().flat_map(|idx| self.node_tris(*idx)))
+    }
+}
+
+fn frustum_cull<'a, N: BVHNodeRef<'a>>(
+    root: N,
+    frustum: &Frustum,
+    stats: &mut QueryStats,
+) -> FrustumCullResult<N> {
+    let mut visitor = FrustumVisitor {
+        frustum,
+        result: FrustumCullResult::default(),
+        stats: QueryStats::default(),
+    };
+    traverse_node(root, &mut visitor, stats);
+    *stats += visitor.stats;
+    visitor.result
+}
+
+struct FrustumVisitor<'f, N> {
+    frustum: &'f Frustum,
+    result: FrustumCullResult<N>,
+    stats: QueryStats,
+}
+
+impl<'a, N: BVHNodeRef<'a>> BVHVisitor<'a, N> for FrustumVisitor<'_, N> {
+    fn visit_node(&mut self, node: N) -> VisitResult {
+        self.result.nodes_tested += 1;
+        self.stats.aabb_tests += 1;
+        match self.frustum.classify_aabb(node.aabb()) {
+            FrustumClass::Outside => {
+                self.result.nodes_outside += 1;
+                VisitResult::Skip
+            }
+            FrustumClass::Inside => {
+                // 整棵子树都可见，不用再测
+                self.result.nodes_inside += 1;
+                for_each_leaf(node, &mut |leaf: N| {
+                    self.stats.leaves += 1;
+                    self.stats.leaf_tris += leaf.tris().len();
+                    self.result.leaves.push(leaf);
+                });
+                VisitResult::Skip
+            }
+            FrustumClass::Intersecting => {
+                self.result.nodes_intersecting += 1;
+                VisitResult::Continue
+            }
+        }
+    }
+
+    fn visit_leaf(&mut self, leaf: N) -> VisitResult {
+        self.result.leaves.push(leaf);
+        VisitResult::Continue
+    }
+}
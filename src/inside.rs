@@ -0,0 +1,239 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use crate::tri::{tri_key, TriKey};
+use crate::visit::traverse_node;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::PointInsideMode;
+    pub use super::WindingNumberTree;
+}
+
+// 节点包围球离查询点超过半径的这么多倍时，用偶极子近似代替逐个三角形求立体角
+pub const WINDING_NUMBER_BETA: f64 = 2.0;
+
+// 导出接口按它选择实现，环绕数的WindingNumberTree由调用方缓存
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PointInsideMode {
+    // 射线穿过网格的次数为奇数则在内部，要求网格封闭
+    RayParity,
+    // 广义环绕数大于0.5则在内部，网格有小缺口也能给出合理结果
+    WindingNumber,
+}
+
+// 三角形对pt张的立体角，Van Oosterom-Strackee公式
+fn solid_angle(tri: &Tri, pt: &Vec3) -> f64 {
+    let a = tri.pt0 - *pt;
+    let b = tri.pt1 - *pt;
+    let c = tri.pt2 - *pt;
+    let la = a.length();
+    let lb = b.length();
+    let lc = c.length();
+    let numerator = a.dot(&b.cross(&c));
+    let denominator = la * lb * lc + a.dot(&b) * lc + b.dot(&c) * la + c.dot(&a) * lb;
+    2.0 * numerator.atan2(denominator)
+}
+
+// 每个节点预先算好面积加权的法线和中心，远处的节点整体当作一个偶极子
+pub struct WindingNumberTree {
+    vtx_buf: Arc<Vec<Vec3>>,
+    tris: Vec<TriIndex>,
+    center: Vec3,
+    radius: f64,
+    area: f64,
+    area_normal: Vec3,
+    children: Vec<WindingNumberTree>,
+}
+
+impl WindingNumberTree {
+    pub fn new(bvh: Arc<BVHNode>) -> Self {
+        let mut seen = HashSet::<TriKey>::new();
+        Self::build(&bvh, &bvh.vtx_buf, &mut seen)
+    }
+
+    pub fn from_flat(bvh: &FlatBVH) -> Self {
+        let mut seen = HashSet::<TriKey>::new();
+        Self::build(bvh.node_ref(0), &bvh.vtx_buf, &mut seen)
+    }
+
+    fn build<'a, N: BVHNodeRef<'a>>(
+        node: N,
+        vtx_buf: &Arc<Vec<Vec3>>,
+        seen: &mut HashSet<TriKey>,
+    ) -> Self {
+        let mut ret = Self {
+            vtx_buf: vtx_buf.clone(),
+            tris: Vec::<TriIndex>::new(),
+            center: node.aabb().center(),
+            radius: 0.0,
+            area: 0.0,
+            area_normal: Vec3::default(),
+            children: Vec::<WindingNumberTree>::new(),
+        };
+
+        let mut area = 0.0f64;
+        let mut weighted_center = Vec3::default();
+        if node.is_leaf() {
+            for tri_index in node.tris() {
+                if !seen.insert(tri_key(tri_index)) {
+                    continue;
+                }
+                let tri = tri_index.to_tri(vtx_buf);
+                let normal =
+                    (tri.pt1 - tri.pt0).cross(&(tri.pt2 - tri.pt0)) / Vec3::new(2.0, 2.0, 2.0);
+                let tri_area = normal.length();
+                area += tri_area;
+                weighted_center += tri.centroid() * Vec3::new(tri_area, tri_area, tri_area);
+                ret.area_normal += normal;
+                ret.tris.push(tri_index.clone());
+            }
+        } else {
+            for child in node.children() {
+                let child = Self::build(child, vtx_buf, seen);
+                let child_area = child.area;
+                area += child_area;
+                weighted_center += child.center * Vec3::new(child_area, child_area, child_area);
+                ret.area_normal += child.area_normal;
+                ret.children.push(child);
+            }
+        }
+        ret.area = area;
+        if area > 0.0 {
+            ret.center = weighted_center / Vec3::new(area, area, area);
+        }
+
+        // 包围球要包住整个节点包围盒
+        let min = node.aabb().min;
+        let max = node.aabb().max;
+        for corner in [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ] {
+            ret.radius = ret.radius.max(corner.distance_to(&ret.center));
+        }
+        ret
+    }
+
+    // 封闭网格内部为1，外部为0
+    pub fn winding_number(&self, pt: &Vec3) -> f64 {
+        self.solid_angle(pt, &mut QueryStats::default()) / (4.0 * std::f64::consts::PI)
+    }
+
+    pub fn point_inside(&self, pt: &Vec3) -> bool {
+        self.point_inside_with_stats(pt, &mut QueryStats::default())
+    }
+
+    // 节点用包围球判断远近而不测包围盒，aabb_tests和hits都是0；
+    // tri_tests是逐个三角形求立体角的次数
+    pub fn point_inside_with_stats(&self, pt: &Vec3, stats: &mut QueryStats) -> bool {
+        self.solid_angle(pt, stats) / (4.0 * std::f64::consts::PI) > 0.5
+    }
+
+    fn solid_angle(&self, pt: &Vec3, stats: &mut QueryStats) -> f64 {
+        stats.nodes_visited += 1;
+        let offset = self.center - *pt;
+        let dist = offset.length();
+        if dist > WINDING_NUMBER_BETA * self.radius {
+            return self.area_normal.dot(&offset) / (dist * dist * dist);
+        }
+        if self.children.is_empty() {
+            stats.leaves += 1;
+            stats.leaf_tris += self.tris.len();
+            stats.tri_tests += self.tris.len();
+            self.tris
+                .iter()
+                .map(|tri_index| solid_angle(&tri_index.to_tri(&self.vtx_buf), pt))
+                .sum()
+        } else {
+            self.children
+                .iter()
+                .map(|child| child.solid_angle(pt, stats))
+                .sum()
+        }
+    }
+}
+
+impl BVHNode {
+    // 射线奇偶判断，不需要额外的结构。环绕数要先建WindingNumberTree，
+    // 建树是O(n)的，对同一个网格做大量查询时建一次再复用它的point_inside
+    pub fn point_inside(bvh: Arc<Self>, pt: &Vec3) -> bool {
+        Self::point_inside_with_stats(bvh, pt, &mut QueryStats::default())
+    }
+
+    pub fn point_inside_with_stats(bvh: Arc<Self>, pt: &Vec3, stats: &mut QueryStats) -> bool {
+        ray_parity_inside(&bvh, pt, stats)
+    }
+}
+
+impl FlatBVH {
+    // 环绕数用WindingNumberTree::from_flat建树
+    pub fn point_inside(&self, pt: &Vec3) -> bool {
+        self.point_inside_with_stats(pt, &mut QueryStats::default())
+    }
+
+    pub fn point_inside_with_stats(&self, pt: &Vec3, stats: &mut QueryStats) -> bool {
+        ray_parity_inside(self.node_ref(0), pt, stats)
+    }
+}
+
+// 射线刚好擦过边或顶点时奇偶会算错，取三个不相关方向投票
+fn ray_parity_inside<'a, N: BVHNodeRef<'a>>(root: N, pt: &Vec3, stats: &mut QueryStats) -> bool {
+    let sqrt2 = 2.0f64.sqrt();
+    let sqrt3 = 3.0f64.sqrt();
+    let directions = [
+        Vec3::new(1.0, sqrt2, sqrt3),
+        Vec3::new(-sqrt3, 1.0, sqrt2),
+        Vec3::new(sqrt2, -sqrt3, 1.0),
+    ];
+    let votes = directions
+        .iter()
+        .filter(|direction| {
+            let ray = Ray::new(pt, direction, f64::INFINITY);
+            ray_crossings(root, &ray, stats) % 2 == 1
+        })
+        .count();
+    votes >= 2
+}
+
+fn ray_crossings<'a, N: BVHNodeRef<'a>>(root: N, ray: &Ray, stats: &mut QueryStats) -> usize {
+    let mut hits = HashSet::<TriKey>::new();
+    let mut aabb_tests = 0_usize;
+    let mut tri_tests = 0_usize;
+    let mut tri_hits = 0_usize;
+    traverse_node(
+        root,
+        &mut FnVisitor::new(
+            |node: N| {
+                aabb_tests += 1;
+                match ray.intersect_aabb(node.aabb(), ray.max_distance) {
+                    Some(_) => VisitResult::Continue,
+                    None => VisitResult::Skip,
+                }
+            },
+            |leaf: N| {
+                for tri_index in leaf.tris() {
+                    let tri = tri_index.to_tri(leaf.vtx_buf());
+                    tri_tests += 1;
+                    if ray.intersect_tri(&tri, ray.max_distance).is_some() {
+                        tri_hits += 1;
+                        hits.insert(tri_key(tri_index));
+                    }
+                }
+                VisitResult::Continue
+            },
+        ),
+        stats,
+    );
+    stats.aabb_tests += aabb_tests;
+    stats.tri_tests += tri_tests;
+    stats.hits += tri_hits;
+    hits.len()
+}
@@ -0,0 +1,110 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::BVHMortonCode;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BVHMortonCode {
+    // 每轴10位
+    Bits30,
+    // 每轴21位
+    Bits63,
+}
+
+impl BVHMortonCode {
+    fn bits_per_axis(&self) -> u32 {
+        match self {
+            BVHMortonCode::Bits30 => 10,
+            BVHMortonCode::Bits63 => 21,
+        }
+    }
+
+    fn encode(&self, x: u64, y: u64, z: u64) -> u64 {
+        let mut code = 0_u64;
+        for bit in 0..self.bits_per_axis() {
+            code |= ((x >> bit) & 1) << (bit * 3 + 2);
+            code |= ((y >> bit) & 1) << (bit * 3 + 1);
+            code |= ((z >> bit) & 1) << (bit * 3);
+        }
+        code
+    }
+}
+
+pub(crate) fn subdivide(bvh: &mut BVHNode, cfg: BVHSubdivideConfig) {
+    if bvh.idx_buf.is_empty() {
+        return;
+    }
+    let sorted = sort_by_morton_code(&bvh.vtx_buf, &bvh.idx_buf, &cfg);
+    *bvh = emit(&bvh.vtx_buf, &sorted, &cfg);
+}
+
+pub(crate) fn sort_by_morton_code(
+    vtx_buf: &Arc<Vec<Vec3>>,
+    idx_buf: &[TriIndex],
+    cfg: &BVHSubdivideConfig,
+) -> Vec<(u64, TriIndex)> {
+    let centroids = idx_buf
+        .iter()
+        .map(|tri_index| tri_index.to_tri(vtx_buf).centroid())
+        .collect::<Vec<Vec3>>();
+    let centroid_aabb = AABB::from_points(&centroids);
+    let min = centroid_aabb.min;
+    let ext = centroid_aabb.extent();
+
+    // 重心归一化到[0, 1]后量化，再交错成莫顿码
+    let scale = ((1_u64 << cfg.morton_code.bits_per_axis()) - 1) as f64;
+    let quantize = |value: f64, lo: f64, span: f64| -> u64 {
+        if span <= 0.0 {
+            return 0;
+        }
+        (((value - lo) / span).clamp(0.0, 1.0) * scale) as u64
+    };
+    let mut sorted = centroids
+        .iter()
+        .zip(idx_buf.iter())
+        .map(|(c, tri_index)| {
+            let code = cfg.morton_code.encode(
+                quantize(c.x, min.x, ext.x),
+                quantize(c.y, min.y, ext.y),
+                quantize(c.z, min.z, ext.z),
+            );
+            (code, tri_index.clone())
+        })
+        .collect::<Vec<(u64, TriIndex)>>();
+    sorted.sort_by_key(|(code, _)| *code);
+    sorted
+}
+
+fn emit(vtx_buf: &Arc<Vec<Vec3>>, sorted: &[(u64, TriIndex)], cfg: &BVHSubdivideConfig) -> BVHNode {
+    let idx_buf = sorted
+        .iter()
+        .map(|(_, tri_index)| tri_index.clone())
+        .collect::<Vec<TriIndex>>();
+    if sorted.len() <= cfg.num_tris_per_leaf {
+        return BVHNode::new(vtx_buf.clone(), idx_buf);
+    }
+
+    let sep = find_split(sorted);
+    let child_pos = emit(vtx_buf, &sorted[..sep], cfg);
+    let child_neg = emit(vtx_buf, &sorted[sep..], cfg);
+    let aabb = child_pos.aabb.merge(&child_neg.aabb);
+    let mut node = BVHNode::with_aabb(vtx_buf.clone(), idx_buf, aabb);
+    node.children.push(Arc::new(child_pos));
+    node.children.push(Arc::new(child_neg));
+    node
+}
+
+// 在最高的不同位上切开，找到该位为1的第一个位置
+pub(crate) fn find_split(sorted: &[(u64, TriIndex)]) -> usize {
+    let first = sorted[0].0;
+    let last = sorted[sorted.len() - 1].0;
+    if first == last {
+        return sorted.len() / 2;
+    }
+    let bit = 63 - (first ^ last).leading_zeros();
+    sorted.partition_point(|(code, _)| (code >> bit) & 1 == 0)
+}
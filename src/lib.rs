@@ -2,28 +2,89 @@
 #![allow(unused_imports)]
 
 mod aabb;
+mod batch;
 mod bvh;
 mod cexport;
+mod cost;
+mod distribution;
+mod flat;
+mod frustum;
+mod inside;
+mod lbvh;
+mod nearest;
+mod obb;
+mod pair;
+mod physx;
 mod poly;
+mod ray;
+mod sbvh;
+mod selfhit;
+mod shape;
+mod stats;
+mod sweep;
+mod transform;
 mod tri;
 mod vec3;
+mod visit;
+mod wide;
 
 pub mod prelude {
     pub use super::aabb::prelude::*;
     pub use super::bvh::prelude::*;
+    pub use super::cost::prelude::*;
+    pub use super::distribution::prelude::*;
+    pub use super::flat::prelude::*;
+    pub use super::frustum::prelude::*;
+    pub use super::inside::prelude::*;
+    pub use super::lbvh::prelude::*;
+    pub use super::nearest::prelude::*;
+    pub use super::obb::prelude::*;
+    pub use super::physx::prelude::*;
     pub use super::poly::prelude::*;
+    pub use super::ray::prelude::*;
+    pub use super::selfhit::prelude::*;
+    pub use super::shape::prelude::*;
+    pub use super::stats::prelude::*;
+    pub use super::sweep::prelude::*;
+    pub use super::transform::prelude::*;
     pub use super::tri::prelude::*;
     pub use super::vec3::prelude::*;
+    pub use super::visit::prelude::*;
 }
 
 #[cfg(test)]
 mod tests {
+    use super::prelude::*;
     use std::ops::Index;
+    use std::sync::Arc;
+
+    fn random_mesh(ntris: usize) -> (Arc<Vec<Vec3>>, Vec<TriIndex>) {
+        let mut vtx_buf = Vec::<Vec3>::new();
+        let mut idx_buf = Vec::<TriIndex>::new();
+        for idx in 0..ntris {
+            let x0 = rand::random_range(-100.0f64..100.0f64);
+            let y0 = rand::random_range(-100.0f64..100.0f64);
+            let z0 = rand::random_range(-100.0f64..100.0f64);
+            vtx_buf.push(Vec3::new(x0, y0, z0));
+            vtx_buf.push(Vec3::new(
+                x0 + rand::random_range(-20.0..20.0),
+                y0 + rand::random_range(-20.0..20.0),
+                z0 + rand::random_range(-20.0..20.0),
+            ));
+            vtx_buf.push(Vec3::new(
+                x0 + rand::random_range(-20.0..20.0),
+                y0 + rand::random_range(-20.0..20.0),
+                z0 + rand::random_range(-20.0..20.0),
+            ));
+            idx_buf.push(TriIndex::new(idx * 3, idx * 3 + 1, idx * 3 + 2));
+        }
+        (Arc::new(vtx_buf), idx_buf)
+    }
 
     #[test]
     fn test_bvh_subdivide() {
         use super::prelude::*;
-        use std::rc::Rc;
+        use std::sync::Arc;
 
         let mut vtx_buf = Vec::<Vec3>::new();
         let mut idx_buf = Vec::<TriIndex>::new();
@@ -43,12 +104,12 @@ mod tests {
             vtx_buf.push(Vec3::new(x2, y2, z2));
             idx_buf.push(tri_idx);
         }
-        let mut bvh = BVHNode::new(Rc::new(vtx_buf), idx_buf);
+        let mut bvh = BVHNode::new(Arc::new(vtx_buf), idx_buf);
         {
             bvh.subdivide(BVHSubdivideConfig::default());
             //bvh.print_leaves();
         }
-        let rbvh = Rc::new(bvh);
+        let rbvh = Arc::new(bvh);
         let aabb = AABB::new(
             &Vec3::new(-20.0, -20.0, -20.0),
             &Vec3::new(20.0, 20.0, 20.0),
@@ -60,12 +121,1686 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bvh_subdivide_sah() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+        let cfg = BVHSubdivideConfig {
+            split_method: BVHSplitMethod::SAH,
+            ..BVHSubdivideConfig::default()
+        };
+        bvh.subdivide(cfg);
+        let leaves = BVHNode::get_all_leaves(Arc::new(bvh));
+        let ntris: usize = leaves.iter().map(|leaf| leaf.idx_buf.len()).sum();
+        assert_eq!(ntris, 5000);
+        assert!(leaves
+            .iter()
+            .all(|leaf| leaf.idx_buf.len() <= cfg.num_tris_per_leaf));
+    }
+
+    #[test]
+    fn test_bvh_subdivide_binned_sah() {
+        let (vtx_buf, idx_buf) = random_mesh(20000);
+        let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+        let cfg = BVHSubdivideConfig {
+            split_method: BVHSplitMethod::BinnedSAH,
+            num_bins: 8,
+            ..BVHSubdivideConfig::default()
+        };
+        bvh.subdivide(cfg);
+        let leaves = BVHNode::get_all_leaves(Arc::new(bvh));
+        let ntris: usize = leaves.iter().map(|leaf| leaf.idx_buf.len()).sum();
+        assert_eq!(ntris, 20000);
+        assert!(leaves
+            .iter()
+            .all(|leaf| leaf.idx_buf.len() <= cfg.num_tris_per_leaf));
+    }
+
+    #[test]
+    fn test_bvh_subdivide_centroid_partition() {
+        for split_method in [BVHSplitMethod::ObjectMedian, BVHSplitMethod::CentroidMedian] {
+            let (vtx_buf, idx_buf) = random_mesh(5000);
+            let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+            let cfg = BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            };
+            bvh.subdivide(cfg);
+            let leaves = BVHNode::get_all_leaves(Arc::new(bvh));
+            let ntris: usize = leaves.iter().map(|leaf| leaf.idx_buf.len()).sum();
+            assert_eq!(ntris, 5000);
+        }
+    }
+
+    #[test]
+    fn test_bvh_subdivide_spatial() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut sbvh = BVHNode::new(vtx_buf, idx_buf);
+        let cfg = BVHSubdivideConfig {
+            split_method: BVHSplitMethod::Spatial,
+            ..BVHSubdivideConfig::default()
+        };
+        sbvh.subdivide(cfg);
+        let sbvh = Arc::new(sbvh);
+        let nrefs = BVHNode::reference_count(sbvh.clone());
+        assert!(nrefs >= 5000);
+        assert!(nrefs <= 5000 + (5000.0 * cfg.spatial_split_budget) as usize);
+
+        // 细长的斜三角形包围盒很大，对象划分的兄弟节点大量重叠，空间切分应该能明显减少
+        let mut vtx_buf = Vec::<Vec3>::new();
+        let mut idx_buf = Vec::<TriIndex>::new();
+        for idx in 0..2000 {
+            let start = Vec3::new(
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+            );
+            let end = Vec3::new(
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+            );
+            vtx_buf.push(start);
+            vtx_buf.push(end);
+            vtx_buf.push(end + Vec3::new(0.5, 0.5, 0.0));
+            idx_buf.push(TriIndex::new(idx * 3, idx * 3 + 1, idx * 3 + 2));
+        }
+        let vtx_buf = Arc::new(vtx_buf);
+        let mut sah = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        sah.subdivide(BVHSubdivideConfig {
+            split_method: BVHSplitMethod::SAH,
+            ..BVHSubdivideConfig::default()
+        });
+        let mut sbvh = BVHNode::new(vtx_buf, idx_buf);
+        sbvh.subdivide(cfg);
+        let sah_overlap = BVHNode::sibling_overlap(Arc::new(sah));
+        let sbvh_overlap = BVHNode::sibling_overlap(Arc::new(sbvh));
+        assert!(sbvh_overlap < sah_overlap);
+    }
+
+    #[test]
+    fn test_bvh_subdivide_lbvh() {
+        for morton_code in [BVHMortonCode::Bits30, BVHMortonCode::Bits63] {
+            let (vtx_buf, idx_buf) = random_mesh(20000);
+            let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+            let cfg = BVHSubdivideConfig {
+                split_method: BVHSplitMethod::LBVH,
+                morton_code,
+                ..BVHSubdivideConfig::default()
+            };
+            bvh.subdivide(cfg);
+            let bvh = Arc::new(bvh);
+            let leaves = BVHNode::get_all_leaves(bvh.clone());
+            let ntris: usize = leaves.iter().map(|leaf| leaf.idx_buf.len()).sum();
+            assert_eq!(ntris, 20000);
+            assert!(leaves
+                .iter()
+                .all(|leaf| leaf.idx_buf.len() <= cfg.num_tris_per_leaf));
+            let aabb = AABB::new(
+                &Vec3::new(-20.0, -20.0, -20.0),
+                &Vec3::new(20.0, 20.0, 20.0),
+            );
+            let intersection = BVHNode::get_interseced_leaves(bvh, &aabb);
+            assert!(!BVHNodeIntersectionResult::to_leaves(intersection).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_bvh_subdivide_physx() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        for split_method in [BVHSplitMethod::PhysXBVH33, BVHSplitMethod::PhysXBVH34] {
+            let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+            bvh.subdivide(BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            });
+            let bvh = Arc::new(bvh);
+            for node in BVHNode::get_all_nodes(bvh.clone()) {
+                assert!(node.children.len() <= 4);
+                for child in node.children.iter() {
+                    assert!(node.aabb.min.x <= child.aabb.min.x);
+                    assert!(node.aabb.max.x >= child.aabb.max.x);
+                }
+                if node.is_leaf() {
+                    assert!(node.idx_buf.len() <= 15);
+                }
+            }
+            let pages = RTreePage::build_pages(bvh.clone());
+            let ninternal = BVHNode::get_all_nodes(bvh.clone())
+                .iter()
+                .filter(|node| !node.is_leaf())
+                .count();
+            assert_eq!(pages.len(), ninternal);
+        }
+    }
+
+    #[test]
+    fn test_bvh_collapse() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+        bvh.subdivide(BVHSubdivideConfig {
+            split_method: BVHSplitMethod::SAH,
+            ..BVHSubdivideConfig::default()
+        });
+        let binary = Arc::new(bvh);
+        let aabb = AABB::new(
+            &Vec3::new(-20.0, -20.0, -20.0),
+            &Vec3::new(20.0, 20.0, 20.0),
+        );
+        let binary_hits = BVHNodeIntersectionResult::to_leaves(BVHNode::get_interseced_leaves(
+            binary.clone(),
+            &aabb,
+        ));
+        for width in [4, 8] {
+            let wide = Arc::new(BVHNode::collapse(binary.clone(), width));
+            assert!(BVHNode::max_width(wide.clone()) <= width);
+            assert!(
+                BVHNode::get_all_nodes(wide.clone()).len()
+                    < BVHNode::get_all_nodes(binary.clone()).len()
+            );
+            assert_eq!(
+                BVHNode::get_all_leaves(wide.clone()).len(),
+                BVHNode::get_all_leaves(binary.clone()).len()
+            );
+            let wide_hits = BVHNodeIntersectionResult::to_leaves(BVHNode::get_interseced_leaves(
+                wide.clone(),
+                &aabb,
+            ));
+            assert_eq!(wide_hits.len(), binary_hits.len());
+            println!(
+                "BVH{} sibling overlap: {}",
+                width,
+                BVHNode::sibling_overlap(wide)
+            );
+        }
+    }
+
+    #[test]
+    fn test_flat_bvh() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let aabb = AABB::new(
+            &Vec3::new(-20.0, -20.0, -20.0),
+            &Vec3::new(20.0, 20.0, 20.0),
+        );
+        for split_method in [
+            BVHSplitMethod::Naive,
+            BVHSplitMethod::SAH,
+            BVHSplitMethod::BinnedSAH,
+            BVHSplitMethod::LBVH,
+        ] {
+            let cfg = BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            };
+            let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+            bvh.subdivide(cfg);
+            let bvh = Arc::new(bvh);
+            let flat = FlatBVH::from_tree(bvh.clone());
+            let direct = FlatBVH::build(vtx_buf.clone(), idx_buf.clone(), cfg);
+            let order = |flat: &FlatBVH| {
+                flat.tris
+                    .iter()
+                    .map(|tri| (tri.pt0, tri.pt1, tri.pt2))
+                    .collect::<Vec<(usize, usize, usize)>>()
+            };
+            assert_eq!(order(&direct), order(&flat));
+            for flat in [flat, direct] {
+                assert_eq!(flat.tris.len(), 2000);
+                assert_eq!(flat.root().num_tris, 2000);
+                assert_eq!(flat.nodes.len(), BVHNode::get_all_nodes(bvh.clone()).len());
+                assert_eq!(
+                    flat.get_all_leaves().len(),
+                    BVHNode::get_all_leaves(bvh.clone()).len()
+                );
+                let hits = BVHNodeIntersectionResult::to_leaves(BVHNode::get_interseced_leaves(
+                    bvh.clone(),
+                    &aabb,
+                ));
+                assert_eq!(flat.get_interseced_leaves(&aabb).len(), hits.len());
+                assert_eq!(
+                    flat.block_overlap_peak(40.0),
+                    BVHNode::block_overlap_peak(bvh.clone(), 40.0)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_flat_queries() {
+        // FlatBVH和展平前的指针树走同一份遍历，结果和统计都应该逐项相同
+        let key = |tri_index: &TriIndex| (tri_index.pt0, tri_index.pt1, tri_index.pt2);
+        let keys = |tris: &[TriIndex]| tris.iter().map(key).collect::<Vec<(usize, usize, usize)>>();
+        let random_pt = || {
+            Vec3::new(
+                rand::random_range(-120.0..120.0),
+                rand::random_range(-120.0..120.0),
+                rand::random_range(-120.0..120.0),
+            )
+        };
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let (other_vtx, other_idx) = random_mesh(500);
+        for split_method in [BVHSplitMethod::Naive, BVHSplitMethod::Spatial] {
+            let cfg = BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            };
+            let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+            bvh.subdivide(cfg);
+            let bvh = Arc::new(bvh);
+            let flat = FlatBVH::from_tree(bvh.clone());
+            let mut other = BVHNode::new(other_vtx.clone(), other_idx.clone());
+            other.subdivide(cfg);
+            let other = Arc::new(other);
+            let other_flat = FlatBVH::from_tree(other.clone());
+
+            for _ in 0..50 {
+                let (mut lhs, mut rhs) = (QueryStats::default(), QueryStats::default());
+                let pt = random_pt();
+                let mut direction = random_pt();
+                direction.normalize();
+                let ray = Ray::new(&pt, &direction, 300.0);
+                let hit = BVHNode::raycast_closest_with_stats(bvh.clone(), &ray, &mut lhs)
+                    .map(|hit| (key(&hit.tri_index), hit.distance));
+                let flat_hit = flat
+                    .raycast_closest_with_stats(&ray, &mut rhs)
+                    .map(|hit| (key(&hit.tri_index), hit.distance));
+                assert_eq!(hit, flat_hit);
+                assert_eq!(
+                    BVHNode::raycast_any_with_stats(bvh.clone(), &ray, &mut lhs).is_some(),
+                    flat.raycast_any_with_stats(&ray, &mut rhs).is_some()
+                );
+
+                let closest = BVHNode::closest_point_with_stats(bvh.clone(), &pt, 50.0, &mut lhs)
+                    .map(|closest| (key(&closest.tri_index), closest.distance));
+                let flat_closest = flat
+                    .closest_point_with_stats(&pt, 50.0, &mut rhs)
+                    .map(|closest| (key(&closest.tri_index), closest.distance));
+                assert_eq!(closest, flat_closest);
+
+                let sphere = Sphere::new(&pt, 8.0);
+                let capsule =
+                    Capsule::new(&pt, &(pt + direction * Vec3::new(15.0, 15.0, 15.0)), 4.0);
+                let obb = OBB::from_euler(&pt, &Vec3::new(10.0, 4.0, 6.0), 0.3, 0.7, 1.1);
+                let sweep =
+                    |hit: Option<SweepHit>| hit.map(|hit| (key(&hit.tri_index), hit.distance));
+                assert_eq!(
+                    sweep(BVHNode::sweep_sphere_with_stats(
+                        bvh.clone(),
+                        &sphere,
+                        &direction,
+                        100.0,
+                        &mut lhs
+                    )),
+                    sweep(flat.sweep_sphere_with_stats(&sphere, &direction, 100.0, &mut rhs))
+                );
+                assert_eq!(
+                    sweep(BVHNode::sweep_box_with_stats(
+                        bvh.clone(),
+                        &obb,
+                        &direction,
+                        100.0,
+                        &mut lhs
+                    )),
+                    sweep(flat.sweep_box_with_stats(&obb, &direction, 100.0, &mut rhs))
+                );
+
+                let aabb = sphere.to_aabb();
+                assert_eq!(
+                    keys(&BVHNode::get_intersected_tris_with_stats(
+                        bvh.clone(),
+                        &aabb,
+                        &mut lhs
+                    )),
+                    keys(&flat.get_intersected_tris_with_stats(&aabb, &mut rhs))
+                );
+                assert_eq!(
+                    BVHNode::probe_with_stats(bvh.clone(), &aabb, &mut lhs),
+                    flat.probe_with_stats(&aabb, &mut rhs)
+                );
+                assert_eq!(
+                    BVHNode::probe_shape_with_stats(bvh.clone(), &capsule, &mut lhs),
+                    flat.probe_shape_with_stats(&capsule, &mut rhs)
+                );
+                assert_eq!(
+                    keys(&BVHNode::get_overlapped_tris_with_stats(
+                        bvh.clone(),
+                        &obb,
+                        &mut lhs
+                    )),
+                    keys(&flat.get_overlapped_tris_with_stats(&obb, &mut rhs))
+                );
+                let leaves =
+                    BVHNode::get_overlapped_leaves_with_stats(bvh.clone(), &sphere, &mut lhs)
+                        .iter()
+                        .map(|leaf| keys(&leaf.idx_buf))
+                        .collect::<Vec<Vec<(usize, usize, usize)>>>();
+                let flat_leaves = flat
+                    .get_overlapped_leaves_with_stats(&sphere, &mut rhs)
+                    .iter()
+                    .map(|idx| keys(flat.node_tris(*idx)))
+                    .collect::<Vec<Vec<(usize, usize, usize)>>>();
+                assert_eq!(leaves, flat_leaves);
+                assert_eq!(
+                    BVHNode::point_inside_with_stats(bvh.clone(), &pt, &mut lhs),
+                    flat.point_inside_with_stats(&pt, &mut rhs)
+                );
+                assert_eq!(lhs, rhs);
+            }
+
+            let spheres = (0..20)
+                .map(|_| Sphere::new(&random_pt(), 10.0))
+                .collect::<Vec<Sphere>>();
+            let mut probes = vec![BVHProbe::default(); spheres.len()];
+            let mut flat_probes = vec![BVHProbe::default(); spheres.len()];
+            BVHNode::probe_batch(&bvh, &spheres, &mut probes);
+            flat.probe_batch(&spheres, &mut flat_probes);
+            assert_eq!(probes, flat_probes);
+            let (mut tris, mut flat_tris) = (Vec::<TriIndex>::new(), Vec::<TriIndex>::new());
+            let mut ranges = vec![0..0; spheres.len()];
+            let mut flat_ranges = vec![0..0; spheres.len()];
+            BVHNode::get_overlapped_tris_batch(&bvh, &spheres, &mut tris, &mut ranges);
+            flat.get_overlapped_tris_batch(&spheres, &mut flat_tris, &mut flat_ranges);
+            assert_eq!(keys(&tris), keys(&flat_tris));
+            assert_eq!(ranges, flat_ranges);
+
+            let (mut lhs, mut rhs) = (QueryStats::default(), QueryStats::default());
+            let transform = Transform::from_euler(&Vec3::new(20.0, -10.0, 5.0), 0.2, 0.4, 0.6);
+            assert_eq!(
+                BVHNode::get_overlapped_leaf_pairs_with_stats(
+                    bvh.clone(),
+                    other.clone(),
+                    Some(&transform),
+                    &mut lhs
+                )
+                .len(),
+                flat.get_overlapped_leaf_pairs_with_stats(&other_flat, Some(&transform), &mut rhs)
+                    .len()
+            );
+            let pairs = |pairs: Vec<(TriIndex, TriIndex)>| {
+                pairs
+                    .iter()
+                    .map(|(lhs, rhs)| (key(lhs), key(rhs)))
+                    .collect::<Vec<((usize, usize, usize), (usize, usize, usize))>>()
+            };
+            assert_eq!(
+                pairs(BVHNode::get_intersected_tri_pairs_with_stats(
+                    bvh.clone(),
+                    other.clone(),
+                    Some(&transform),
+                    &mut lhs
+                )),
+                pairs(flat.get_intersected_tri_pairs_with_stats(
+                    &other_flat,
+                    Some(&transform),
+                    &mut rhs
+                ))
+            );
+            assert_eq!(
+                BVHNode::probe_pair_with_stats(bvh.clone(), other.clone(), None, &mut lhs),
+                flat.probe_pair_with_stats(&other_flat, None, &mut rhs)
+            );
+            assert_eq!(
+                BVHNode::get_self_intersections_with_stats(bvh.clone(), &mut lhs).len(),
+                flat.get_self_intersections_with_stats(&mut rhs).len()
+            );
+
+            let frustum = Frustum::perspective(
+                &Vec3::new(150.0, 0.0, 0.0),
+                &Vec3::new(-1.0, 0.0, 0.0),
+                &Vec3::new(0.0, 0.0, 1.0),
+                1.0,
+                16.0 / 9.0,
+                1.0,
+                200.0,
+            );
+            let result = BVHNode::frustum_cull_with_stats(bvh.clone(), &frustum, &mut lhs);
+            let flat_result = flat.frustum_cull_with_stats(&frustum, &mut rhs);
+            assert_eq!(result.leaves.len(), flat_result.leaves.len());
+            assert_eq!(result.nodes_inside, flat_result.nodes_inside);
+            assert_eq!(result.nodes_outside, flat_result.nodes_outside);
+            assert_eq!(result.visible_tris(), flat.visible_tris(&flat_result));
+            assert_eq!(lhs, rhs);
+
+            let pt = random_pt();
+            assert_eq!(
+                WindingNumberTree::new(bvh.clone()).winding_number(&pt),
+                WindingNumberTree::from_flat(&flat).winding_number(&pt)
+            );
+
+            let sphere = Sphere::new(&Vec3::default(), 20.0);
+            let block_size = Vec3::new(40.0, 40.0, 40.0);
+            assert_eq!(
+                BVHNode::block_overlap_tri_peak(bvh.clone(), 40.0),
+                flat.block_overlap_tri_peak(40.0)
+            );
+            assert_eq!(
+                BVHNode::block_overlap_shape_peak(bvh.clone(), &sphere, 40.0),
+                flat.block_overlap_shape_peak(&sphere, 40.0)
+            );
+            assert_eq!(
+                BVHNode::surface_shape_hit_peak(bvh.clone(), &sphere, 40.0),
+                flat.surface_shape_hit_peak(&sphere, 40.0)
+            );
+            assert_eq!(
+                BVHNode::block_overlap_peak_report(bvh.clone(), 40.0, &CostModel::default()),
+                flat.block_overlap_peak_report(40.0, &CostModel::default())
+            );
+            assert_eq!(
+                BVHNode::surface_hit_distribution(bvh.clone(), 40.0, &block_size).percentile(0.9),
+                flat.surface_hit_distribution(40.0, &block_size)
+                    .percentile(0.9)
+            );
+            assert_eq!(
+                BVHNode::block_overlap_obb_peak_worst(bvh.clone(), &block_size, 40.0, 4, 1).0,
+                flat.block_overlap_obb_peak_worst(&block_size, 40.0, 4, 1).0
+            );
+        }
+    }
+
+    #[test]
+    fn test_bvh_subdivide_parallel() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<BVHNode>();
+        assert_send_sync::<FlatBVH>();
+        assert_send_sync::<IndexedPoly>();
+
+        let (vtx_buf, idx_buf) = random_mesh(20000);
+        let mut serial = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        let cfg = BVHSubdivideConfig {
+            split_method: BVHSplitMethod::BinnedSAH,
+            ..BVHSubdivideConfig::default()
+        };
+        serial.subdivide(cfg);
+        let mut parallel = BVHNode::new(vtx_buf, idx_buf);
+        parallel.subdivide(BVHSubdivideConfig {
+            num_threads: 8,
+            ..cfg
+        });
+
+        // 多线程只改变子树的构建顺序，整棵树应该和串行的逐节点一致
+        fn assert_same_tree(lhs: &BVHNode, rhs: &BVHNode) {
+            let corners = |node: &BVHNode| {
+                let (min, max) = (node.aabb.min, node.aabb.max);
+                [min.x, min.y, min.z, max.x, max.y, max.z]
+            };
+            let tris = |node: &BVHNode| {
+                node.idx_buf
+                    .iter()
+                    .map(|tri_index| (tri_index.pt0, tri_index.pt1, tri_index.pt2))
+                    .collect::<Vec<(usize, usize, usize)>>()
+            };
+            assert_eq!(corners(lhs), corners(rhs));
+            assert_eq!(tris(lhs), tris(rhs));
+            assert_eq!(lhs.children.len(), rhs.children.len());
+            for (lhs, rhs) in lhs.children.iter().zip(rhs.children.iter()) {
+                assert_same_tree(lhs, rhs);
+            }
+        }
+        assert_same_tree(&serial, &parallel);
+    }
+
+    #[test]
+    fn test_peak_parallel() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        let block_size = Vec3::new(30.0, 30.0, 30.0);
+        assert_eq!(
+            BVHNode::block_overlap_peak(bvh.clone(), 30.0),
+            BVHNode::block_overlap_peak_parallel(bvh.clone(), 30.0, 4)
+        );
+        assert_eq!(
+            BVHNode::surface_hit_peak(bvh.clone(), 30.0, &block_size),
+            BVHNode::surface_hit_peak_parallel(bvh.clone(), 30.0, &block_size, 4)
+        );
+    }
+
+    #[test]
+    fn test_raycast() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        for _ in 0..100 {
+            let origin = Vec3::new(
+                rand::random_range(-150.0..150.0),
+                rand::random_range(-150.0..150.0),
+                rand::random_range(-150.0..150.0),
+            );
+            let direction = Vec3::new(
+                rand::random_range(-1.0..1.0),
+                rand::random_range(-1.0..1.0),
+                rand::random_range(-1.0..1.0),
+            );
+            let ray = Ray::new(&origin, &direction, 500.0);
+
+            // 和暴力遍历所有三角形的结果对比
+            let brute = idx_buf
+                .iter()
+                .filter_map(|tri_index| {
+                    ray.intersect_tri(&tri_index.to_tri(&vtx_buf), ray.max_distance)
+                })
+                .map(|(t, _, _)| t)
+                .min_by(|a, b| a.total_cmp(b));
+            let closest = BVHNode::raycast_closest(bvh.clone(), &ray);
+            assert_eq!(closest.as_ref().map(|hit| hit.distance), brute);
+            assert_eq!(
+                BVHNode::raycast_any(bvh.clone(), &ray).is_some(),
+                brute.is_some()
+            );
+            if let Some(hit) = closest {
+                let tri = hit.tri_index.to_tri(&vtx_buf);
+                let w = 1.0 - hit.u - hit.v;
+                let pt = tri.pt0 * Vec3::new(w, w, w)
+                    + tri.pt1 * Vec3::new(hit.u, hit.u, hit.u)
+                    + tri.pt2 * Vec3::new(hit.v, hit.v, hit.v);
+                assert!(pt.distance_to(&ray.point_at(hit.distance)) < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tri_aabb_overlap() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+
+        // 完全包含的三角形一定相交，包围盒不相交的一定不相交
+        let tri = idx_buf[0].to_tri(&vtx_buf);
+        assert!(tri.intersect_with_aabb(&tri.to_aabb()));
+        let far = AABB::new(
+            &Vec3::new(1e4, 1e4, 1e4),
+            &Vec3::new(1e4 + 1.0, 1e4 + 1.0, 1e4 + 1.0),
+        );
+        assert!(!tri.intersect_with_aabb(&far));
+
+        // 空间切分的同一个三角形会出现在多个叶子里，结果要去重
+        for split_method in [BVHSplitMethod::Naive, BVHSplitMethod::Spatial] {
+            let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+            bvh.subdivide(BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            });
+            let bvh = Arc::new(bvh);
+
+            for _ in 0..100 {
+                let center = Vec3::new(
+                    rand::random_range(-100.0..100.0),
+                    rand::random_range(-100.0..100.0),
+                    rand::random_range(-100.0..100.0),
+                );
+                let half = Vec3::new(10.0, 10.0, 10.0);
+                let aabb = AABB::new(&(center - half), &(center + half));
+
+                // 和暴力遍历所有三角形的结果对比
+                let brute = idx_buf
+                    .iter()
+                    .filter(|tri_index| tri_index.to_tri(&vtx_buf).intersect_with_aabb(&aabb))
+                    .count();
+                let tris = BVHNode::get_intersected_tris(bvh.clone(), &aabb);
+                assert_eq!(tris.len(), brute);
+                let probe = BVHNode::probe(bvh.clone(), &aabb);
+                assert_eq!(probe.hit_tris, brute);
+                assert!(probe.hit_tris <= probe.candidate_tris);
+                assert_eq!(
+                    probe.leaves,
+                    BVHNodeIntersectionResult::to_leaves(BVHNode::get_interseced_leaves(
+                        bvh.clone(),
+                        &aabb
+                    ))
+                    .len()
+                );
+            }
+            assert_eq!(
+                BVHNode::block_overlap_tri_peak(bvh.clone(), 30.0),
+                BVHNode::block_overlap_tri_peak_parallel(bvh.clone(), 30.0, 4)
+            );
+        }
+    }
+
+    #[test]
+    fn test_shape_overlap() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+
+        // 三角形顶点在球里一定相交，重心处的极小胶囊一定相交
+        let tri = idx_buf[0].to_tri(&vtx_buf);
+        assert!(Sphere::new(&tri.pt0, 0.5).intersect_with_tri(&tri));
+        assert!(Capsule::new(&tri.centroid(), &tri.centroid(), 1e-9).intersect_with_tri(&tri));
+
+        for _ in 0..100 {
+            let center = Vec3::new(
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+            );
+            let sphere = Sphere::new(&center, 10.0);
+            let capsule = Capsule::upright(&center, 10.0, 5.0);
+
+            // 和暴力遍历所有三角形的结果对比
+            let brute = |shape: &dyn Fn(&Tri) -> bool| {
+                idx_buf
+                    .iter()
+                    .filter(|tri_index| shape(&tri_index.to_tri(&vtx_buf)))
+                    .count()
+            };
+            let sphere_brute = brute(&|tri| sphere.intersect_with_tri(tri));
+            let capsule_brute = brute(&|tri| capsule.intersect_with_tri(tri));
+            assert_eq!(
+                BVHNode::get_overlapped_tris(bvh.clone(), &sphere).len(),
+                sphere_brute
+            );
+            assert_eq!(
+                BVHNode::get_overlapped_tris(bvh.clone(), &capsule).len(),
+                capsule_brute
+            );
+            let probe = BVHNode::probe_shape(bvh.clone(), &capsule);
+            assert_eq!(probe.hit_tris, capsule_brute);
+            assert!(probe.hit_tris <= probe.candidate_tris);
+
+            // 球包含在它的包围盒里，精确命中数不会超过盒子
+            assert!(sphere_brute <= BVHNode::probe(bvh.clone(), &sphere.to_aabb()).hit_tris);
+        }
+
+        let sphere = Sphere::new(&Vec3::default(), 15.0);
+        assert_eq!(
+            BVHNode::surface_shape_hit_peak(bvh.clone(), &sphere, 30.0),
+            BVHNode::surface_shape_hit_peak_parallel(bvh.clone(), &sphere, 30.0, 4)
+        );
+        assert!(
+            BVHNode::directional_shape_hit(
+                bvh.clone(),
+                &sphere,
+                &Vec3::new(0.0, 0.0, -150.0),
+                &Vec3::new(0.0, 0.0, 150.0),
+                15.0,
+                false,
+            ) <= BVHNode::get_all_leaves(bvh.clone()).len()
+        );
+    }
+
+    #[test]
+    fn test_obb_overlap() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        let half_extents = Vec3::new(20.0, 5.0, 10.0);
+
+        // 不旋转的OBB和同尺寸的AABB结果一致
+        let center = Vec3::new(10.0, -20.0, 30.0);
+        let obb = OBB::from_euler(&center, &half_extents, 0.0, 0.0, 0.0);
+        let aabb = AABB::new(&(center - half_extents), &(center + half_extents));
+        assert_eq!(
+            BVHNode::get_overlapped_tris(bvh.clone(), &obb).len(),
+            BVHNode::get_intersected_tris(bvh.clone(), &aabb).len()
+        );
+
+        for obb in OBB::sample_orientations(&half_extents, 32) {
+            // 采样出的朝向是正交基
+            for (i, a) in obb.axes.iter().enumerate() {
+                assert!((a.length() - 1.0).abs() < 1e-9);
+                for b in obb.axes.iter().skip(i + 1) {
+                    assert!(a.dot(b).abs() < 1e-9);
+                }
+            }
+            let obb = obb.centered_at(&Vec3::new(
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+            ));
+
+            // 和暴力遍历所有三角形的结果对比
+            let brute = idx_buf
+                .iter()
+                .filter(|tri_index| obb.intersect_with_tri(&tri_index.to_tri(&vtx_buf)))
+                .count();
+            assert_eq!(BVHNode::get_overlapped_tris(bvh.clone(), &obb).len(), brute);
+
+            // SAT筛选的叶子不会比外包AABB筛选的多
+            assert!(
+                BVHNode::get_overlapped_leaves(bvh.clone(), &obb).len()
+                    <= BVHNode::get_overlapped_leaves(
+                        bvh.clone(),
+                        &Sphere::new(&obb.center, half_extents.length())
+                    )
+                    .len()
+            );
+        }
+
+        let (peak, worst) =
+            BVHNode::surface_obb_hit_peak_worst(bvh.clone(), &half_extents, 30.0, 4, 4);
+        assert_eq!(
+            peak,
+            BVHNode::surface_shape_hit_peak(bvh.clone(), &worst, 30.0)
+        );
+        let (peak, worst) =
+            BVHNode::block_overlap_obb_peak_worst(bvh.clone(), &half_extents, 30.0, 4, 4);
+        assert_eq!(
+            peak,
+            BVHNode::block_overlap_shape_peak(bvh.clone(), &worst, 30.0)
+        );
+    }
+
+    #[test]
+    fn test_sweep() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        let scale = |v: Vec3, t: f64| v * Vec3::new(t, t, t);
+
+        for _ in 0..50 {
+            let center = Vec3::new(
+                rand::random_range(-150.0..150.0),
+                rand::random_range(-150.0..150.0),
+                rand::random_range(-150.0..150.0),
+            );
+            let mut direction = Vec3::new(
+                rand::random_range(-1.0..1.0),
+                rand::random_range(-1.0..1.0),
+                rand::random_range(-1.0..1.0),
+            );
+            direction.normalize();
+            let sphere = Sphere::new(&center, 3.0);
+            let obb = OBB::from_euler(&center, &Vec3::new(4.0, 2.0, 3.0), 0.3, 0.7, 1.1);
+
+            // 和暴力遍历所有三角形的结果对比
+            let brute = |toi: &dyn Fn(&Tri) -> Option<f64>| {
+                idx_buf
+                    .iter()
+                    .filter_map(|tri_index| toi(&tri_index.to_tri(&vtx_buf)))
+                    .min_by(|a, b| a.total_cmp(b))
+            };
+            let same = |lhs: Option<f64>, rhs: Option<f64>| match (lhs, rhs) {
+                (Some(lhs), Some(rhs)) => (lhs - rhs).abs() < 1e-9,
+                (lhs, rhs) => lhs.is_none() && rhs.is_none(),
+            };
+            let sphere_hit = BVHNode::sweep_sphere(bvh.clone(), &sphere, &direction, 300.0);
+            let box_hit = BVHNode::sweep_box(bvh.clone(), &obb, &direction, 300.0);
+            assert!(same(
+                sphere_hit.as_ref().map(|hit| hit.distance),
+                brute(&|tri| sphere.sweep_tri(&direction, 300.0, tri))
+            ));
+            assert!(same(
+                box_hit.as_ref().map(|hit| hit.distance),
+                brute(&|tri| obb.sweep_tri(&direction, 300.0, tri))
+            ));
+
+            // 接触时刻刚好贴上，稍早一点还没碰到
+            if let Some(hit) = sphere_hit {
+                let tri = hit.tri_index.to_tri(&vtx_buf);
+                let moved = center + scale(direction, hit.distance);
+                if hit.distance > 0.0 {
+                    assert!((tri.closest_point(&moved).distance_to(&moved) - 3.0).abs() < 1e-6);
+                }
+            }
+            if let Some(hit) = box_hit {
+                let tri = hit.tri_index.to_tri(&vtx_buf);
+                let at = |t: f64| obb.centered_at(&(center + scale(direction, t)));
+                assert!(at(hit.distance + 1e-6).intersect_with_tri(&tri));
+                if hit.distance > 1e-6 {
+                    assert!(!at(hit.distance - 1e-6).intersect_with_tri(&tri));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_closest_point() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+
+        // 三角形顶点到网格的距离为0
+        let tri = idx_buf[0].to_tri(&vtx_buf);
+        let hit = BVHNode::closest_point(bvh.clone(), &tri.pt1, f64::INFINITY).unwrap();
+        assert!(hit.distance < 1e-9);
+
+        for _ in 0..100 {
+            let pt = Vec3::new(
+                rand::random_range(-200.0..200.0),
+                rand::random_range(-200.0..200.0),
+                rand::random_range(-200.0..200.0),
+            );
+
+            // 和暴力遍历所有三角形的结果对比
+            let brute = idx_buf
+                .iter()
+                .map(|tri_index| {
+                    tri_index
+                        .to_tri(&vtx_buf)
+                        .closest_point(&pt)
+                        .distance_to(&pt)
+                })
+                .min_by(|a, b| a.total_cmp(b))
+                .unwrap();
+            let hit = BVHNode::closest_point(bvh.clone(), &pt, f64::INFINITY).unwrap();
+            assert_eq!(hit.distance, brute);
+            assert!((hit.point.distance_to(&pt) - hit.distance).abs() < 1e-9);
+            let tri = hit.tri_index.to_tri(&vtx_buf);
+            assert!(tri.closest_point(&hit.point).distance_to(&hit.point) < 1e-9);
+
+            // 搜索半径比最近距离小时找不到
+            assert!(
+                BVHNode::closest_point(bvh.clone(), &pt, brute * 0.5).is_none() || brute == 0.0
+            );
+        }
+    }
+
+    #[test]
+    fn test_point_inside() {
+        // 6x6x6个互不重叠的封闭立方体，三角形法线朝外
+        let mut vtx_buf = Vec::<Vec3>::new();
+        let mut idx_buf = Vec::<TriIndex>::new();
+        let mut cubes = Vec::<AABB>::new();
+        for i in 0..216 {
+            let min = Vec3::new(
+                (i % 6) as f64 * 20.0,
+                (i / 6 % 6) as f64 * 20.0,
+                (i / 36) as f64 * 20.0,
+            );
+            let cube = AABB::new(&min, &(min + Vec3::new(10.0, 10.0, 10.0)));
+            let base = vtx_buf.len();
+            for corner in 0..8 {
+                vtx_buf.push(Vec3::new(
+                    if corner & 1 == 0 {
+                        cube.min.x
+                    } else {
+                        cube.max.x
+                    },
+                    if corner & 2 == 0 {
+                        cube.min.y
+                    } else {
+                        cube.max.y
+                    },
+                    if corner & 4 == 0 {
+                        cube.min.z
+                    } else {
+                        cube.max.z
+                    },
+                ));
+            }
+            for quad in [
+                [0, 2, 6, 4],
+                [1, 3, 7, 5],
+                [0, 1, 5, 4],
+                [2, 3, 7, 6],
+                [0, 1, 3, 2],
+                [4, 5, 7, 6],
+            ] {
+                for [a, b, c] in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                    let (pa, pb, pc) = (vtx_buf[base + a], vtx_buf[base + b], vtx_buf[base + c]);
+                    let outward = (pa + pb + pc) / Vec3::new(3.0, 3.0, 3.0) - cube.center();
+                    if (pb - pa).cross(&(pc - pa)).dot(&outward) > 0.0 {
+                        idx_buf.push(TriIndex::new(base + a, base + b, base + c));
+                    } else {
+                        idx_buf.push(TriIndex::new(base + a, base + c, base + b));
+                    }
+                }
+            }
+            cubes.push(cube);
+        }
+        let vtx_buf = Arc::new(vtx_buf);
+
+        for split_method in [BVHSplitMethod::SAH, BVHSplitMethod::Spatial] {
+            let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+            bvh.subdivide(BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            });
+            let bvh = Arc::new(bvh);
+            let tree = WindingNumberTree::new(bvh.clone());
+            for _ in 0..200 {
+                let pt = Vec3::new(
+                    rand::random_range(-10.0..120.0),
+                    rand::random_range(-10.0..120.0),
+                    rand::random_range(-10.0..120.0),
+                );
+                // 太靠近表面的点两种方法都可能有数值误差，跳过
+                if cubes.iter().any(|cube| {
+                    let mut outer = cube.clone();
+                    outer.expand(&Vec3::new(1.0, 1.0, 1.0));
+                    let mut inner = cube.clone();
+                    inner.expand(&Vec3::new(-1.0, -1.0, -1.0));
+                    outer.distance_to_point(&pt) == 0.0 && inner.distance_to_point(&pt) > 0.0
+                }) {
+                    continue;
+                }
+                let expected = cubes.iter().any(|cube| cube.distance_to_point(&pt) == 0.0);
+                assert_eq!(BVHNode::point_inside(bvh.clone(), &pt), expected);
+                assert_eq!(tree.point_inside(&pt), expected);
+                let winding = tree.winding_number(&pt);
+                assert!((winding - if expected { 1.0 } else { 0.0 }).abs() < 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pair_overlap() {
+        let (lhs_vtx, lhs_idx) = random_mesh(1000);
+        let (rhs_vtx, rhs_idx) = random_mesh(500);
+
+        // 三角形和自己、和平移后错开的自己
+        let tri = lhs_idx[0].to_tri(&lhs_vtx);
+        assert!(tri.intersect_with_tri(&tri));
+        let far = Transform::from_euler(&Vec3::new(1e4, 0.0, 0.0), 0.0, 0.0, 0.0);
+        assert!(!tri.intersect_with_tri(&far.apply_tri(&tri)));
+
+        // 空间切分的同一对三角形会出现在多个叶子对里，结果要去重
+        for split_method in [BVHSplitMethod::Naive, BVHSplitMethod::Spatial] {
+            let cfg = BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            };
+            let mut lhs = BVHNode::new(lhs_vtx.clone(), lhs_idx.clone());
+            lhs.subdivide(cfg);
+            let lhs = Arc::new(lhs);
+            let mut rhs = BVHNode::new(rhs_vtx.clone(), rhs_idx.clone());
+            rhs.subdivide(cfg);
+            let rhs = Arc::new(rhs);
+
+            for transform in [
+                None,
+                Some(Transform::from_euler(
+                    &Vec3::new(30.0, -20.0, 10.0),
+                    0.4,
+                    1.2,
+                    -0.7,
+                )),
+            ] {
+                let transform = transform.as_ref();
+                let identity = Transform::default();
+                let applied = transform.unwrap_or(&identity);
+
+                // 和暴力遍历所有三角形对的结果对比
+                let rhs_tris = rhs_idx
+                    .iter()
+                    .map(|tri_index| applied.apply_tri(&tri_index.to_tri(&rhs_vtx)))
+                    .collect::<Vec<Tri>>();
+                let brute = lhs_idx
+                    .iter()
+                    .map(|tri_index| {
+                        let tri = tri_index.to_tri(&lhs_vtx);
+                        rhs_tris
+                            .iter()
+                            .filter(|rhs_tri| tri.intersect_with_tri(rhs_tri))
+                            .count()
+                    })
+                    .sum::<usize>();
+                let pairs = BVHNode::get_intersected_tri_pairs(lhs.clone(), rhs.clone(), transform);
+                assert_eq!(pairs.len(), brute);
+                let probe = BVHNode::probe_pair(lhs.clone(), rhs.clone(), transform);
+                assert_eq!(probe.hit_tris, brute);
+                assert!(probe.candidate_tris <= lhs_idx.len() * rhs_idx.len());
+                assert_eq!(
+                    probe.leaves,
+                    BVHNode::get_overlapped_leaf_pairs(lhs.clone(), rhs.clone(), transform).len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_self_intersection() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+
+        // 和暴力遍历所有三角形对的结果对比，random_mesh的三角形互不共用顶点
+        let mut brute = 0_usize;
+        for (idx, lhs) in idx_buf.iter().enumerate() {
+            let lhs_tri = lhs.to_tri(&vtx_buf);
+            for rhs in idx_buf[idx + 1..].iter() {
+                if lhs_tri.intersect_with_tri(&rhs.to_tri(&vtx_buf)) {
+                    brute += 1;
+                }
+            }
+        }
+        assert!(brute > 0);
+
+        for split_method in [BVHSplitMethod::SAH, BVHSplitMethod::Spatial] {
+            let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+            bvh.subdivide(BVHSubdivideConfig {
+                split_method,
+                ..BVHSubdivideConfig::default()
+            });
+            let hits = BVHNode::get_self_intersections(Arc::new(bvh));
+            assert_eq!(hits.len(), brute);
+            for hit in hits.iter() {
+                // 交线段的两端同时在两个三角形上
+                let lhs = hit.lhs.to_tri(&vtx_buf);
+                let rhs = hit.rhs.to_tri(&vtx_buf);
+                if let Some((p, q)) = hit.segment {
+                    for pt in [p, q] {
+                        assert!(lhs.closest_point(&pt).distance_to(&pt) < 1e-6);
+                        assert!(rhs.closest_point(&pt).distance_to(&pt) < 1e-6);
+                    }
+                }
+            }
+        }
+
+        // 共边的两个三角形不算自相交
+        let vtx_buf = Arc::new(vec![
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(10.0, 0.0, 0.0),
+            Vec3::new(0.0, 10.0, 0.0),
+            Vec3::new(0.0, 0.0, 10.0),
+        ]);
+        let idx_buf = vec![TriIndex::new(0, 1, 2), TriIndex::new(0, 1, 3)];
+        let bvh = Arc::new(BVHNode::new(vtx_buf, idx_buf));
+        assert!(BVHNode::get_self_intersections(bvh).is_empty());
+    }
+
+    #[test]
+    fn test_frustum_cull() {
+        let (vtx_buf, idx_buf) = random_mesh(5000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+
+        for _ in 0..20 {
+            let eye = Vec3::new(
+                rand::random_range(-150.0..150.0),
+                rand::random_range(-150.0..150.0),
+                rand::random_range(-150.0..150.0),
+            );
+            let forward = Vec3::default() - eye;
+            let frustum = Frustum::perspective(
+                &eye,
+                &forward,
+                &Vec3::new(0.0, 0.0, 1.0),
+                1.0,
+                16.0 / 9.0,
+                1.0,
+                rand::random_range(50.0..300.0),
+            );
+            assert!(frustum.point_inside(&(eye + forward * Vec3::new(0.1, 0.1, 0.1))));
+            assert!(!frustum.point_inside(&(eye - forward)));
+
+            // 和不做整棵子树跳过、逐个叶子分类的结果对比
+            let result = BVHNode::frustum_cull(bvh.clone(), &frustum);
+            let brute = BVHNode::get_all_leaves(bvh.clone())
+                .into_iter()
+                .filter(|leaf| frustum.classify_aabb(&leaf.aabb) != FrustumClass::Outside)
+                .collect::<Vec<Arc<BVHNode>>>();
+            assert_eq!(result.leaves.len(), brute.len());
+            for leaf in result.leaves.iter() {
+                assert!(brute.iter().any(|other| Arc::ptr_eq(leaf, other)));
+            }
+            assert_eq!(
+                result.nodes_tested,
+                result.nodes_inside + result.nodes_intersecting + result.nodes_outside
+            );
+
+            // 顶点全在视锥里的三角形一定在某个可见叶子里
+            for tri_index in idx_buf.iter() {
+                let tri = tri_index.to_tri(&vtx_buf);
+                if [tri.pt0, tri.pt1, tri.pt2]
+                    .iter()
+                    .all(|pt| frustum.point_inside(pt))
+                {
+                    assert!(result
+                        .leaves
+                        .iter()
+                        .any(|leaf| leaf.idx_buf.iter().any(|other| other.pt0 == tri_index.pt0)));
+                }
+            }
+        }
+
+        // 把整个网格框进去，空间切分的重复引用不会让可见三角形数超过网格本身
+        let mut sbvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        sbvh.subdivide(BVHSubdivideConfig {
+            split_method: BVHSplitMethod::Spatial,
+            ..BVHSubdivideConfig::default()
+        });
+        let sbvh = Arc::new(sbvh);
+        let frustum = Frustum::perspective(
+            &Vec3::new(-1000.0, 0.0, 0.0),
+            &Vec3::new(1.0, 0.0, 0.0),
+            &Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            1.0,
+            1.0,
+            2000.0,
+        );
+        let result = BVHNode::frustum_cull(sbvh.clone(), &frustum);
+        assert_eq!(result.visible_tris(), idx_buf.len());
+        assert_eq!(
+            result
+                .leaves
+                .iter()
+                .map(|leaf| leaf.idx_buf.len())
+                .sum::<usize>(),
+            BVHNode::reference_count(sbvh)
+        );
+    }
+
+    #[test]
+    fn test_batch_query() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        let flat = FlatBVH::from_tree(bvh.clone());
+
+        let aabbs = (0..64)
+            .map(|_| {
+                let center = Vec3::new(
+                    rand::random_range(-50.0..550.0),
+                    rand::random_range(-50.0..550.0),
+                    rand::random_range(-50.0..550.0),
+                );
+                let half = Vec3::new(20.0, 30.0, 40.0);
+                AABB::new(&(center - half), &(center + half))
+            })
+            .collect::<Vec<AABB>>();
+        let spheres = aabbs
+            .iter()
+            .map(|aabb| Sphere::new(&aabb.center(), 25.0))
+            .collect::<Vec<Sphere>>();
+
+        let mut counts = vec![0_usize; aabbs.len()];
+        let mut flat_counts = vec![0_usize; aabbs.len()];
+        let mut probes = vec![BVHProbe::default(); aabbs.len()];
+        let mut ranges = vec![0..0; aabbs.len()];
+        let mut tris = Vec::<TriIndex>::new();
+        BVHNode::count_overlapped_leaves_batch(&bvh, &aabbs, &mut counts);
+        flat.count_interseced_leaves_batch(&aabbs, &mut flat_counts);
+        BVHNode::probe_batch(&bvh, &aabbs, &mut probes);
+        BVHNode::get_overlapped_tris_batch(&bvh, &aabbs, &mut tris, &mut ranges);
+        for (idx, aabb) in aabbs.iter().enumerate() {
+            let leaves = BVHNodeIntersectionResult::to_leaves(BVHNode::get_interseced_leaves(
+                bvh.clone(),
+                aabb,
+            ));
+            assert_eq!(counts[idx], leaves.len());
+            assert_eq!(flat_counts[idx], leaves.len());
+            assert!(probes[idx] == BVHNode::probe(bvh.clone(), aabb));
+            let expected = BVHNode::get_intersected_tris(bvh.clone(), aabb);
+            assert_eq!(ranges[idx].len(), expected.len());
+        }
+
+        // 复用同一组缓冲区做第二批，旧结果要被覆盖
+        let mut leaves = Vec::<&BVHNode>::new();
+        BVHNode::get_overlapped_leaves_batch(&bvh, &spheres, &mut leaves, &mut ranges);
+        BVHNode::probe_batch(&bvh, &spheres, &mut probes);
+        for (idx, sphere) in spheres.iter().enumerate() {
+            assert_eq!(
+                ranges[idx].len(),
+                BVHNode::get_overlapped_leaves(bvh.clone(), sphere).len()
+            );
+            assert!(probes[idx] == BVHNode::probe_shape(bvh.clone(), sphere));
+        }
+        assert_eq!(leaves.len(), ranges.last().unwrap().end);
+    }
+
+    #[test]
+    fn test_visitor() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        let num_nodes = BVHNode::get_all_nodes(bvh.clone()).len();
+        let num_leaves = BVHNode::get_all_leaves(bvh.clone()).len();
+
+        // 一直Continue时每个节点都访问一次
+        let mut nodes = 0_usize;
+        let mut leaves = 0_usize;
+        let stopped = BVHNode::traverse(
+            &bvh,
+            &mut FnVisitor::new(
+                |_| {
+                    nodes += 1;
+                    VisitResult::Continue
+                },
+                |_| {
+                    leaves += 1;
+                    VisitResult::Continue
+                },
+            ),
+        );
+        assert!(!stopped);
+        assert_eq!(nodes, num_nodes);
+        assert_eq!(leaves, num_leaves);
+
+        // 第一个叶子就Stop
+        let mut leaves = 0_usize;
+        let stopped = BVHNode::traverse(
+            &bvh,
+            &mut FnVisitor::new(
+                |_| VisitResult::Continue,
+                |_| {
+                    leaves += 1;
+                    VisitResult::Stop
+                },
+            ),
+        );
+        assert!(stopped);
+        assert_eq!(leaves, 1);
+
+        // 根节点Skip时什么都不访问
+        let mut leaves = 0_usize;
+        BVHNode::traverse(
+            &bvh,
+            &mut FnVisitor::new(
+                |_| VisitResult::Skip,
+                |_| {
+                    leaves += 1;
+                    VisitResult::Continue
+                },
+            ),
+        );
+        assert_eq!(leaves, 0);
+
+        // FlatBVH用同一个访问者接口，节点是下标
+        let flat = FlatBVH::from_tree(bvh.clone());
+        let mut leaves = 0_usize;
+        flat.traverse(&mut FnVisitor::new(
+            |idx: usize| {
+                assert!(idx < flat.nodes.len());
+                VisitResult::Continue
+            },
+            |_| {
+                leaves += 1;
+                VisitResult::Continue
+            },
+        ));
+        assert_eq!(leaves, num_leaves);
+
+        // 按离查询点的距离排序，叶子出栈的顺序就是由近到远
+        struct NearestFirst {
+            pt: Vec3,
+            distances: Vec<f64>,
+        }
+        impl<'a> BVHVisitor<'a> for NearestFirst {
+            fn visit_node(&mut self, _node: &'a Arc<BVHNode>) -> VisitResult {
+                VisitResult::Continue
+            }
+            fn visit_leaf(&mut self, leaf: &'a Arc<BVHNode>) -> VisitResult {
+                self.distances.push(leaf.aabb.distance_to_point(&self.pt));
+                VisitResult::Continue
+            }
+            fn priority(&mut self, node: &'a Arc<BVHNode>) -> Option<f64> {
+                Some(node.aabb.distance_to_point(&self.pt))
+            }
+        }
+        let mut visitor = NearestFirst {
+            pt: Vec3::new(250.0, 250.0, 250.0),
+            distances: vec![],
+        };
+        BVHNode::traverse_ordered(&bvh, &mut visitor);
+        assert_eq!(visitor.distances.len(), num_leaves);
+        // 深度优先，第一个叶子是每层都挑最近孩子一路走下去得到的
+        let mut node = bvh.clone();
+        while !node.is_leaf() {
+            node = node
+                .children
+                .iter()
+                .min_by(|a, b| {
+                    let da = a.aabb.distance_to_point(&visitor.pt);
+                    let db = b.aabb.distance_to_point(&visitor.pt);
+                    da.total_cmp(&db)
+                })
+                .unwrap()
+                .clone();
+        }
+        assert_eq!(
+            visitor.distances[0],
+            node.aabb.distance_to_point(&visitor.pt)
+        );
+    }
+
+    #[test]
+    fn test_query_stats() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let mut bvh = BVHNode::new(vtx_buf.clone(), idx_buf.clone());
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+
+        // 计数和probe的结果对得上，重复查询会累加
+        let aabb = AABB::new(
+            &Vec3::new(100.0, 100.0, 100.0),
+            &Vec3::new(200.0, 180.0, 160.0),
+        );
+        let mut stats = QueryStats::default();
+        let probe = BVHNode::probe_with_stats(bvh.clone(), &aabb, &mut stats);
+        assert!(probe == BVHNode::probe(bvh.clone(), &aabb));
+        assert_eq!(stats.leaves, probe.leaves);
+        assert_eq!(stats.leaf_tris, probe.candidate_tris);
+        assert_eq!(stats.tri_tests, probe.candidate_tris);
+        assert_eq!(stats.hits, probe.hit_tris);
+        assert_eq!(stats.nodes_visited, stats.aabb_tests);
+        assert!(stats.nodes_visited >= stats.leaves);
+        let once = stats;
+        BVHNode::probe_with_stats(bvh.clone(), &aabb, &mut stats);
+        let mut twice = once;
+        twice += once;
+        assert!(stats == twice);
+
+        let ray = Ray::new(
+            &Vec3::new(-50.0, 250.0, 250.0),
+            &Vec3::new(1.0, 0.1, 0.0),
+            1000.0,
+        );
+        let mut stats = QueryStats::default();
+        let hit = BVHNode::raycast_closest_with_stats(bvh.clone(), &ray, &mut stats);
+        assert_eq!(
+            hit.as_ref().map(|hit| hit.distance),
+            BVHNode::raycast_closest(bvh.clone(), &ray).map(|hit| hit.distance)
+        );
+        assert!(stats.aabb_tests >= stats.nodes_visited);
+        assert!(stats.tri_tests >= stats.hits);
+        // hits按射线自身的最大距离判定，被最近命中剪掉的节点里的三角形不算
+        let crossed = idx_buf
+            .iter()
+            .filter(|tri_index| {
+                ray.intersect_tri(&tri_index.to_tri(&vtx_buf), ray.max_distance)
+                    .is_some()
+            })
+            .count();
+        assert!(stats.hits <= crossed);
+        assert_eq!(stats.hits > 0, hit.is_some());
+
+        let pt = Vec3::new(10.0, 20.0, 30.0);
+        let mut stats = QueryStats::default();
+        let closest = BVHNode::closest_point_with_stats(bvh.clone(), &pt, 40.0, &mut stats);
+        let within = idx_buf
+            .iter()
+            .filter(|tri_index| {
+                tri_index
+                    .to_tri(&vtx_buf)
+                    .closest_point(&pt)
+                    .distance_to(&pt)
+                    <= 40.0
+            })
+            .count();
+        assert!(stats.hits <= within);
+        assert_eq!(stats.hits > 0, closest.is_some());
+
+        // 叶子查询只测包围盒
+        let mut stats = QueryStats::default();
+        let leaves = BVHNodeIntersectionResult::to_leaves(
+            BVHNode::get_interseced_leaves_with_stats(bvh.clone(), &aabb, &mut stats),
+        );
+        assert_eq!(stats.leaves, leaves.len());
+        assert_eq!(stats.aabb_tests, stats.nodes_visited);
+        assert_eq!(stats.tri_tests, 0);
+
+        // FlatBVH和指针树的遍历计数一致
+        let flat = FlatBVH::from_tree(bvh.clone());
+        let mut flat_stats = QueryStats::default();
+        assert_eq!(
+            flat.count_interseced_leaves_with_stats(&aabb, &mut flat_stats),
+            leaves.len()
+        );
+        assert!(flat_stats == stats);
+
+        // 批量查询的stats是逐个查询的总和
+        let shapes = [aabb.clone(), aabb.centered_at(&Vec3::new(-50.0, 0.0, 50.0))];
+        let mut probes = [BVHProbe::default(); 2];
+        let mut batch_stats = QueryStats::default();
+        BVHNode::probe_batch_with_stats(&bvh, &shapes, &mut probes, &mut batch_stats);
+        let mut single_stats = QueryStats::default();
+        for shape in shapes.iter() {
+            BVHNode::probe_shape_with_stats(bvh.clone(), shape, &mut single_stats);
+        }
+        assert!(batch_stats == single_stats);
+
+        // 完全在视锥内的子树不遍历，但它的叶子要算上
+        let frustum = Frustum::perspective(
+            &Vec3::new(0.0, -300.0, 0.0),
+            &Vec3::new(0.0, 1.0, 0.0),
+            &Vec3::new(0.0, 0.0, 1.0),
+            1.0,
+            1.5,
+            1.0,
+            1000.0,
+        );
+        let mut stats = QueryStats::default();
+        let cull = BVHNode::frustum_cull_with_stats(bvh.clone(), &frustum, &mut stats);
+        assert_eq!(stats.aabb_tests, cull.nodes_tested);
+        assert_eq!(stats.leaves, cull.leaves.len());
+
+        let mut stats = QueryStats::default();
+        let probe = BVHNode::probe_pair_with_stats(bvh.clone(), bvh.clone(), None, &mut stats);
+        assert_eq!(stats.leaves, probe.leaves);
+        assert_eq!(stats.tri_tests, probe.candidate_tris);
+        assert_eq!(stats.hits, probe.hit_tris);
+
+        let mut stats = QueryStats::default();
+        let inside = BVHNode::point_inside_with_stats(bvh.clone(), &pt, &mut stats);
+        assert_eq!(inside, BVHNode::point_inside(bvh.clone(), &pt));
+        assert!(stats.tri_tests >= stats.hits);
+
+        // 只给叶子计权时，代价峰值就是原来的叶子峰值
+        let leaves_only = CostModel {
+            node_visit: 0.0,
+            leaf_visit: 1.0,
+            tri_test: 0.0,
+            tri_per_leaf: 0.0,
+        };
+        let block_size = Vec3::new(30.0, 30.0, 30.0);
+        let report = BVHNode::block_overlap_peak_report(bvh.clone(), 30.0, &leaves_only);
+        assert_eq!(
+            report.cost,
+            BVHNode::block_overlap_peak(bvh.clone(), 30.0) as f64
+        );
+        assert_eq!(report.leaves, report.peak_leaves);
+        let report = BVHNode::surface_hit_peak_report_parallel(
+            bvh.clone(),
+            30.0,
+            &block_size,
+            &leaves_only,
+            4,
+        );
+        assert_eq!(
+            report.cost,
+            BVHNode::surface_hit_peak(bvh.clone(), 30.0, &block_size) as f64
+        );
+        let start = Vec3::new(0.0, 250.0, 250.0);
+        let end = Vec3::new(500.0, 250.0, 250.0);
+        let report = BVHNode::directional_hit_report(
+            bvh.clone(),
+            &block_size,
+            &start,
+            &end,
+            15.0,
+            false,
+            &leaves_only,
+        );
+        assert_eq!(
+            report.cost,
+            BVHNode::directional_hit(bvh.clone(), &block_size, &start, &end, 15.0, false) as f64
+        );
+
+        // 算上内部节点后代价只会更大
+        let full_cost = CostModel {
+            node_visit: 1.0,
+            tri_test: 1.0,
+            ..leaves_only
+        };
+        assert!(
+            BVHNode::block_overlap_peak_report(bvh.clone(), 30.0, &full_cost).cost
+                > BVHNode::block_overlap_peak(bvh.clone(), 30.0) as f64
+        );
+    }
+
+    #[test]
+    fn test_cost_model() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        let block_size = Vec3::new(30.0, 30.0, 30.0);
+        let model = CostModel::default();
+        assert!(model == CostModel::physx_bvh34());
+
+        // 计数和代价来自同一个探测盒，叶子峰值单独记
+        let report = BVHNode::block_overlap_peak_report(bvh.clone(), 30.0, &model);
+        assert_eq!(model.cost(&report.stats()), report.cost);
+        assert_eq!(
+            report.peak_leaves,
+            BVHNode::block_overlap_peak(bvh.clone(), 30.0)
+        );
+        assert!(report.leaves <= report.peak_leaves);
+        assert_eq!(report.leaf_tris, report.tri_tests);
+        assert!(report.nodes_visited >= report.leaves);
+        assert!(report.tri_tests >= report.leaves);
+        assert!(
+            report == BVHNode::block_overlap_peak_report_parallel(bvh.clone(), 30.0, &model, 4)
+        );
+
+        let report = BVHNode::surface_hit_peak_report_parallel(
+            bvh.clone(),
+            30.0,
+            &block_size,
+            &CostModel::physx_bvh33(),
+            4,
+        );
+        assert_eq!(CostModel::physx_bvh33().cost(&report.stats()), report.cost);
+        assert_eq!(
+            report.peak_leaves,
+            BVHNode::surface_hit_peak(bvh.clone(), 30.0, &block_size)
+        );
+        assert!(report.cost > 0.0);
+
+        // 只给叶子计权时代价就是叶子数
+        let leaves_only = CostModel {
+            node_visit: 0.0,
+            leaf_visit: 1.0,
+            tri_test: 0.0,
+            tri_per_leaf: 0.0,
+        };
+        let report = BVHNode::surface_hit_peak_report(bvh.clone(), 30.0, &block_size, &leaves_only);
+        assert_eq!(report.cost, report.leaves as f64);
+
+        // 用已知权重生成的耗时能原样拟合回来
+        let truth = CostModel {
+            node_visit: 0.007,
+            leaf_visit: 0.011,
+            tri_test: 0.04,
+            tri_per_leaf: 0.002,
+        };
+        let mut samples = Vec::<(QueryStats, f64)>::new();
+        for _ in 0..50 {
+            let stats = QueryStats {
+                nodes_visited: rand::random_range(10..200),
+                leaves: rand::random_range(1..40),
+                tri_tests: rand::random_range(0..300),
+                leaf_tris: rand::random_range(1..300),
+                ..QueryStats::default()
+            };
+            samples.push((stats, truth.cost(&stats)));
+        }
+        let fitted = CostModel::fit(&samples).unwrap();
+        for (lhs, rhs) in [
+            (fitted.node_visit, truth.node_visit),
+            (fitted.leaf_visit, truth.leaf_visit),
+            (fitted.tri_test, truth.tri_test),
+            (fitted.tri_per_leaf, truth.tri_per_leaf),
+        ] {
+            assert!((lhs - rhs).abs() < 1e-9);
+        }
+
+        // 没有空间切分的树上probe的tri_tests总等于leaf_tris，两项权重分不开，
+        // 合并到tri_test上，拟合出的代价仍然和实测一致
+        let mut samples = Vec::<(QueryStats, f64)>::new();
+        for _ in 0..50 {
+            let center = Vec3::new(
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+                rand::random_range(-100.0..100.0),
+            );
+            let half = Vec3::new(
+                rand::random_range(1.0..30.0),
+                rand::random_range(1.0..30.0),
+                rand::random_range(1.0..30.0),
+            );
+            let mut stats = QueryStats::default();
+            BVHNode::probe_with_stats(
+                bvh.clone(),
+                &AABB::new(&(center - half), &(center + half)),
+                &mut stats,
+            );
+            samples.push((stats, truth.cost(&stats)));
+        }
+        let fitted = CostModel::fit(&samples).unwrap();
+        assert_eq!(fitted.tri_per_leaf, 0.0);
+        assert!((fitted.tri_test - (truth.tri_test + truth.tri_per_leaf)).abs() < 1e-9);
+        for (stats, micros) in samples.iter() {
+            assert!((fitted.cost(stats) - micros).abs() < 1e-9);
+        }
+        assert!(CostModel::fit(&[]).is_none());
+    }
+
+    #[test]
+    fn test_peak_distribution() {
+        let (vtx_buf, idx_buf) = random_mesh(2000);
+        let mut bvh = BVHNode::new(vtx_buf, idx_buf);
+        bvh.subdivide(BVHSubdivideConfig::default());
+        let bvh = Arc::new(bvh);
+        let block_size = Vec3::new(30.0, 30.0, 30.0);
+
+        let serial = BVHNode::block_overlap_distribution(bvh.clone(), 30.0);
+        assert_eq!(serial.peak, BVHNode::block_overlap_peak(bvh.clone(), 30.0));
+        assert_eq!(serial.samples, serial.histogram.iter().sum::<usize>());
+        assert_eq!(serial.empty_samples, serial.histogram[0]);
+        assert!(serial.empty_samples > 0 && serial.empty_samples < serial.samples);
+        assert!(serial.median >= 1);
+        assert_eq!(serial.histogram.len(), serial.peak + 1);
+        assert!(serial.median <= serial.p90 && serial.p90 <= serial.p99);
+        assert!(serial.p99 <= serial.peak);
+        assert!(serial.mean <= serial.peak as f64);
+        // 最坏的探测盒重新查一遍还是峰值
+        assert_eq!(
+            BVHNode::count_overlapped_leaves(&bvh, &serial.worst_aabb),
+            serial.peak
+        );
+        assert!(serial.worst_center.distance_to(&serial.worst_aabb.center()) < 1e-9);
+
+        // 多线程只改变列的计算顺序，合并后完全一致
+        let parallel = BVHNode::block_overlap_distribution_parallel(bvh.clone(), 30.0, 4);
+        assert_eq!(parallel.histogram, serial.histogram);
+        assert_eq!(parallel.mean, serial.mean);
+        assert_eq!(parallel.worst_center.x, serial.worst_center.x);
+        assert_eq!(parallel.worst_center.y, serial.worst_center.y);
+        assert_eq!(parallel.worst_center.z, serial.worst_center.z);
+
+        let surface = BVHNode::surface_hit_distribution_parallel(bvh.clone(), 30.0, &block_size, 4);
+        assert_eq!(
+            surface.peak,
+            BVHNode::surface_hit_peak(bvh.clone(), 30.0, &block_size)
+        );
+        assert!(surface.samples > 0);
+
+        // 10个采样：5个0，3个1，2个3。分位数只看后面5个非空的
+        let dist = PeakDistribution {
+            samples: 10,
+            empty_samples: 5,
+            peak: 3,
+            histogram: vec![5, 3, 0, 2],
+            ..PeakDistribution::default()
+        };
+        assert_eq!(dist.percentile(0.0), 1);
+        assert_eq!(dist.percentile(0.6), 1);
+        assert_eq!(dist.percentile(0.7), 3);
+        assert_eq!(dist.percentile(1.0), 3);
+        let empty = PeakDistribution {
+            samples: 4,
+            empty_samples: 4,
+            histogram: vec![4],
+            ..PeakDistribution::default()
+        };
+        assert_eq!(empty.percentile(0.5), 0);
+    }
+
     #[test]
     fn test_trireduce() {
         use super::prelude::*;
-        use std::rc::Rc;
+        use std::sync::Arc;
 
-        let vtx_buf = Rc::new(vec![
+        let vtx_buf = Arc::new(vec![
             Vec3::new(-1.0, 1.0, 1.0),
             Vec3::new(1.0, 1.0, 1.0),
             Vec3::new(2.0, 0.0, 1.0),
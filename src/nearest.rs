@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use crate::visit::traverse_node_ordered;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::ClosestPoint;
+}
+
+#[derive(Clone, Debug)]
+pub struct ClosestPoint {
+    pub tri_index: TriIndex,
+    pub point: Vec3,
+    pub distance: f64,
+}
+
+impl BVHNode {
+    // 超过max_distance的三角形不考虑，传f64::INFINITY表示不限
+    pub fn closest_point(bvh: Arc<Self>, pt: &Vec3, max_distance: f64) -> Option<ClosestPoint> {
+        Self::closest_point_with_stats(bvh, pt, max_distance, &mut QueryStats::default())
+    }
+
+    // hits记的是在max_distance之内的三角形数
+    pub fn closest_point_with_stats(
+        bvh: Arc<Self>,
+        pt: &Vec3,
+        max_distance: f64,
+        stats: &mut QueryStats,
+    ) -> Option<ClosestPoint> {
+        closest_point(&bvh, pt, max_distance, stats)
+    }
+}
+
+impl FlatBVH {
+    pub fn closest_point(&self, pt: &Vec3, max_distance: f64) -> Option<ClosestPoint> {
+        self.closest_point_with_stats(pt, max_distance, &mut QueryStats::default())
+    }
+
+    pub fn closest_point_with_stats(
+        &self,
+        pt: &Vec3,
+        max_distance: f64,
+        stats: &mut QueryStats,
+    ) -> Option<ClosestPoint> {
+        closest_point(self.node_ref(0), pt, max_distance, stats)
+    }
+}
+
+fn closest_point<'a, N: BVHNodeRef<'a>>(
+    root: N,
+    pt: &Vec3,
+    max_distance: f64,
+    stats: &mut QueryStats,
+) -> Option<ClosestPoint> {
+    let mut visitor = ClosestPointVisitor {
+        pt,
+        search_radius: max_distance,
+        max_distance,
+        best: None,
+        stats: QueryStats::default(),
+    };
+    traverse_node_ordered(root, &mut visitor, stats);
+    *stats += visitor.stats;
+    visitor.best
+}
+
+struct ClosestPointVisitor<'p> {
+    pt: &'p Vec3,
+    search_radius: f64,
+    // 当前最近点的距离，用来剪枝
+    max_distance: f64,
+    best: Option<ClosestPoint>,
+    stats: QueryStats,
+}
+
+impl<'a, N: BVHNodeRef<'a>> BVHVisitor<'a, N> for ClosestPointVisitor<'_> {
+    // 搜索半径已经缩到比这个节点还近了
+    fn visit_node(&mut self, node: N) -> VisitResult {
+        self.stats.aabb_tests += 1;
+        if node.aabb().distance_to_point(self.pt) <= self.max_distance {
+            VisitResult::Continue
+        } else {
+            VisitResult::Skip
+        }
+    }
+
+    fn visit_leaf(&mut self, leaf: N) -> VisitResult {
+        for tri_index in leaf.tris() {
+            let point = tri_index.to_tri(leaf.vtx_buf()).closest_point(self.pt);
+            let distance = point.distance_to(self.pt);
+            self.stats.tri_tests += 1;
+            if distance > self.search_radius {
+                continue;
+            }
+            self.stats.hits += 1;
+            if distance < self.max_distance || self.best.is_none() {
+                self.max_distance = distance;
+                self.best = Some(ClosestPoint {
+                    tri_index: tri_index.clone(),
+                    point,
+                    distance,
+                });
+            }
+        }
+        VisitResult::Continue
+    }
+
+    fn visit_ordered(&mut self, _node: N, priority: f64) -> VisitResult {
+        if priority <= self.max_distance {
+            VisitResult::Continue
+        } else {
+            VisitResult::Skip
+        }
+    }
+
+    fn priority(&mut self, node: N) -> Option<f64> {
+        self.stats.aabb_tests += 1;
+        Some(node.aabb().distance_to_point(self.pt))
+            .filter(|distance| *distance <= self.max_distance)
+    }
+}
@@ -0,0 +1,211 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::OBB;
+}
+
+// axes是旋转后的局部x/y/z轴，两两正交且为单位长度
+#[derive(Copy, Clone, Debug)]
+pub struct OBB {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub axes: [Vec3; 3],
+}
+
+impl OBB {
+    pub fn new(center: &Vec3, half_extents: &Vec3, axes: &[Vec3; 3]) -> Self {
+        Self {
+            center: *center,
+            half_extents: *half_extents,
+            axes: *axes,
+        }
+    }
+
+    // 欧拉角(弧度)，先绕x转roll，再绕y转pitch，最后绕z转yaw
+    pub fn from_euler(center: &Vec3, half_extents: &Vec3, roll: f64, pitch: f64, yaw: f64) -> Self {
+        let transform = Transform::from_euler(center, roll, pitch, yaw);
+        Self::new(center, half_extents, &transform.axes)
+    }
+
+    // 确定性地取num个朝向：x轴按斐波那契球面均匀分布，再按黄金角绕x轴滚转
+    pub fn sample_orientations(half_extents: &Vec3, num: usize) -> Vec<Self> {
+        let golden_angle = std::f64::consts::PI * (3.0 - 5.0f64.sqrt());
+        (0..num)
+            .map(|idx| {
+                let z = 1.0 - 2.0 * (idx as f64 + 0.5) / num as f64;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let theta = golden_angle * idx as f64;
+                let x_axis = Vec3::new(r * theta.cos(), r * theta.sin(), z);
+
+                // 找一个不和x轴平行的向量构造正交基
+                let helper = if x_axis.z.abs() < 0.9 {
+                    Vec3::new(0.0, 0.0, 1.0)
+                } else {
+                    Vec3::new(1.0, 0.0, 0.0)
+                };
+                let mut u = helper.cross(&x_axis);
+                u.normalize();
+                let v = x_axis.cross(&u);
+                let (s, c) = theta.sin_cos();
+                let y_axis = u * Vec3::new(c, c, c) + v * Vec3::new(s, s, s);
+                let z_axis = x_axis.cross(&y_axis);
+                Self::new(&Vec3::default(), half_extents, &[x_axis, y_axis, z_axis])
+            })
+            .collect()
+    }
+
+    fn projected_radius(&self, axis: &Vec3) -> f64 {
+        self.half_extents.x * self.axes[0].dot(axis).abs()
+            + self.half_extents.y * self.axes[1].dot(axis).abs()
+            + self.half_extents.z * self.axes[2].dot(axis).abs()
+    }
+
+    // 把点变换到OBB的局部坐标系
+    fn local_point(&self, pt: &Vec3) -> Vec3 {
+        let d = *pt - self.center;
+        Vec3::new(
+            d.dot(&self.axes[0]),
+            d.dot(&self.axes[1]),
+            d.dot(&self.axes[2]),
+        )
+    }
+}
+
+impl Shape for OBB {
+    fn to_aabb(&self) -> AABB {
+        let r = Vec3::new(
+            self.projected_radius(&Vec3::new(1.0, 0.0, 0.0)),
+            self.projected_radius(&Vec3::new(0.0, 1.0, 0.0)),
+            self.projected_radius(&Vec3::new(0.0, 0.0, 1.0)),
+        );
+        AABB::new(&(self.center - r), &(self.center + r))
+    }
+
+    // 分离轴测试：两个盒子各3个面法线，以及两两叉积的9个轴
+    fn intersect_with_aabb(&self, aabb: &AABB) -> bool {
+        let aabb_center = aabb.center();
+        let aabb_half = aabb.extent() / Vec3::new(2.0, 2.0, 2.0);
+        let t = aabb_center - self.center;
+        let world_axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+
+        let separated = |axis: &Vec3| -> bool {
+            let ra = self.projected_radius(axis);
+            let rb = aabb_half.x * axis.x.abs()
+                + aabb_half.y * axis.y.abs()
+                + aabb_half.z * axis.z.abs();
+            t.dot(axis).abs() > ra + rb
+        };
+
+        for axis in self.axes.iter().chain(world_axes.iter()) {
+            if separated(axis) {
+                return false;
+            }
+        }
+        for a in self.axes.iter() {
+            for b in world_axes.iter() {
+                let axis = a.cross(b);
+                // 两轴平行时叉积为0，已被面法线覆盖
+                if axis.dot(&axis) > f64::EPSILON && separated(&axis) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // 变换到局部坐标系后就是三角形和AABB的分离轴测试
+    fn intersect_with_tri(&self, tri: &Tri) -> bool {
+        let local = Tri {
+            pt0: self.local_point(&tri.pt0),
+            pt1: self.local_point(&tri.pt1),
+            pt2: self.local_point(&tri.pt2),
+        };
+        local.intersect_with_aabb(&AABB::new(
+            &(Vec3::default() - self.half_extents),
+            &self.half_extents,
+        ))
+    }
+
+    fn centered_at(&self, center: &Vec3) -> Self {
+        Self::new(center, &self.half_extents, &self.axes)
+    }
+}
+
+impl BVHNode {
+    // 对每个采样朝向跑一遍峰值扫描，返回最坏的峰值和对应朝向
+    pub fn block_overlap_obb_peak_worst(
+        bvh: Arc<Self>,
+        half_extents: &Vec3,
+        step: f64,
+        num_orientations: usize,
+        num_workers: usize,
+    ) -> (usize, OBB) {
+        worst_orientation(half_extents, num_orientations, &|obb| {
+            Self::block_overlap_shape_peak_parallel(bvh.clone(), obb, step, num_workers)
+        })
+    }
+
+    pub fn surface_obb_hit_peak_worst(
+        bvh: Arc<Self>,
+        half_extents: &Vec3,
+        step: f64,
+        num_orientations: usize,
+        num_workers: usize,
+    ) -> (usize, OBB) {
+        worst_orientation(half_extents, num_orientations, &|obb| {
+            Self::surface_shape_hit_peak_parallel(bvh.clone(), obb, step, num_workers)
+        })
+    }
+}
+
+impl FlatBVH {
+    pub fn block_overlap_obb_peak_worst(
+        &self,
+        half_extents: &Vec3,
+        step: f64,
+        num_orientations: usize,
+        num_workers: usize,
+    ) -> (usize, OBB) {
+        worst_orientation(half_extents, num_orientations, &|obb| {
+            self.block_overlap_shape_peak_parallel(obb, step, num_workers)
+        })
+    }
+
+    pub fn surface_obb_hit_peak_worst(
+        &self,
+        half_extents: &Vec3,
+        step: f64,
+        num_orientations: usize,
+        num_workers: usize,
+    ) -> (usize, OBB) {
+        worst_orientation(half_extents, num_orientations, &|obb| {
+            self.surface_shape_hit_peak_parallel(obb, step, num_workers)
+        })
+    }
+}
+
+// 峰值相同时保留先采到的朝向，结果是确定的
+fn worst_orientation(
+    half_extents: &Vec3,
+    num_orientations: usize,
+    peak: &dyn Fn(&OBB) -> usize,
+) -> (usize, OBB) {
+    let mut worst = (
+        0_usize,
+        OBB::from_euler(&Vec3::default(), half_extents, 0.0, 0.0, 0.0),
+    );
+    for obb in OBB::sample_orientations(half_extents, num_orientations.max(1)) {
+        let value = peak(&obb);
+        if value > worst.0 {
+            worst = (value, obb);
+        }
+    }
+    worst
+}
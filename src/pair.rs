@@ -0,0 +1,239 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use crate::tri::{tri_key, TriKey};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+impl BVHNode {
+    // 同时遍历两棵树，transform把rhs的局部坐标变到lhs所在的坐标系，None表示两者同一坐标系。
+    // 访问者只描述单棵树的遍历，两棵树交替下降的配对查询自己维护栈
+    pub fn get_overlapped_leaf_pairs(
+        lhs: Arc<Self>,
+        rhs: Arc<Self>,
+        transform: Option<&Transform>,
+    ) -> Vec<(Arc<Self>, Arc<Self>)> {
+        Self::get_overlapped_leaf_pairs_with_stats(lhs, rhs, transform, &mut QueryStats::default())
+    }
+
+    // 节点和叶子都按节点对计数，leaf_tris是每对叶子里两边三角形数之和
+    pub fn get_overlapped_leaf_pairs_with_stats(
+        lhs: Arc<Self>,
+        rhs: Arc<Self>,
+        transform: Option<&Transform>,
+        stats: &mut QueryStats,
+    ) -> Vec<(Arc<Self>, Arc<Self>)> {
+        leaf_pairs(&lhs, &rhs, transform, stats)
+            .into_iter()
+            .map(|(lhs, rhs)| (lhs.clone(), rhs.clone()))
+            .collect()
+    }
+
+    pub fn get_intersected_tri_pairs(
+        lhs: Arc<Self>,
+        rhs: Arc<Self>,
+        transform: Option<&Transform>,
+    ) -> Vec<(TriIndex, TriIndex)> {
+        Self::get_intersected_tri_pairs_with_stats(lhs, rhs, transform, &mut QueryStats::default())
+    }
+
+    pub fn get_intersected_tri_pairs_with_stats(
+        lhs: Arc<Self>,
+        rhs: Arc<Self>,
+        transform: Option<&Transform>,
+        stats: &mut QueryStats,
+    ) -> Vec<(TriIndex, TriIndex)> {
+        intersected_tri_pairs(&lhs, &rhs, transform, stats)
+    }
+
+    // leaves记重叠的叶子对数，candidate_tris记去重后做了精确测试的三角形对数
+    pub fn probe_pair(lhs: Arc<Self>, rhs: Arc<Self>, transform: Option<&Transform>) -> BVHProbe {
+        Self::probe_pair_with_stats(lhs, rhs, transform, &mut QueryStats::default())
+    }
+
+    pub fn probe_pair_with_stats(
+        lhs: Arc<Self>,
+        rhs: Arc<Self>,
+        transform: Option<&Transform>,
+        stats: &mut QueryStats,
+    ) -> BVHProbe {
+        probe_pair(&lhs, &rhs, transform, stats)
+    }
+}
+
+// other是rhs，叶子对用两棵树各自的节点下标表示
+impl FlatBVH {
+    pub fn get_overlapped_leaf_pairs(
+        &self,
+        other: &FlatBVH,
+        transform: Option<&Transform>,
+    ) -> Vec<(usize, usize)> {
+        self.get_overlapped_leaf_pairs_with_stats(other, transform, &mut QueryStats::default())
+    }
+
+    pub fn get_overlapped_leaf_pairs_with_stats(
+        &self,
+        other: &FlatBVH,
+        transform: Option<&Transform>,
+        stats: &mut QueryStats,
+    ) -> Vec<(usize, usize)> {
+        leaf_pairs(self.node_ref(0), other.node_ref(0), transform, stats)
+            .into_iter()
+            .map(|(lhs, rhs)| (lhs.idx, rhs.idx))
+            .collect()
+    }
+
+    pub fn get_intersected_tri_pairs(
+        &self,
+        other: &FlatBVH,
+        transform: Option<&Transform>,
+    ) -> Vec<(TriIndex, TriIndex)> {
+        self.get_intersected_tri_pairs_with_stats(other, transform, &mut QueryStats::default())
+    }
+
+    pub fn get_intersected_tri_pairs_with_stats(
+        &self,
+        other: &FlatBVH,
+        transform: Option<&Transform>,
+        stats: &mut QueryStats,
+    ) -> Vec<(TriIndex, TriIndex)> {
+        intersected_tri_pairs(self.node_ref(0), other.node_ref(0), transform, stats)
+    }
+
+    pub fn probe_pair(&self, other: &FlatBVH, transform: Option<&Transform>) -> BVHProbe {
+        self.probe_pair_with_stats(other, transform, &mut QueryStats::default())
+    }
+
+    pub fn probe_pair_with_stats(
+        &self,
+        other: &FlatBVH,
+        transform: Option<&Transform>,
+        stats: &mut QueryStats,
+    ) -> BVHProbe {
+        probe_pair(self.node_ref(0), other.node_ref(0), transform, stats)
+    }
+}
+
+pub(crate) fn leaf_pairs<'a, N: BVHNodeRef<'a>>(
+    lhs: N,
+    rhs: N,
+    transform: Option<&Transform>,
+    stats: &mut QueryStats,
+) -> Vec<(N, N)> {
+    let transform = transform.copied().unwrap_or_default();
+    let mut aabb_tests = 0_usize;
+    let mut overlap = |lhs: N, rhs: N| {
+        aabb_tests += 1;
+        transform
+            .apply_aabb(rhs.aabb())
+            .intersect_with_aabb(lhs.aabb())
+    };
+
+    let mut ret = Vec::<(N, N)>::new();
+    let mut stack = Vec::<(N, N)>::new();
+    if overlap(lhs, rhs) {
+        stack.push((lhs, rhs));
+    }
+    while let Some((lhs, rhs)) = stack.pop() {
+        stats.nodes_visited += 1;
+        if lhs.is_leaf() && rhs.is_leaf() {
+            stats.leaves += 1;
+            stats.leaf_tris += lhs.tris().len() + rhs.tris().len();
+            ret.push((lhs, rhs));
+            continue;
+        }
+
+        // 先拆表面积大的一边，两边的节点尺寸交替缩小
+        let split_lhs = rhs.is_leaf()
+            || (!lhs.is_leaf() && lhs.aabb().surface_area() >= rhs.aabb().surface_area());
+        if split_lhs {
+            for child in lhs.children().rev() {
+                if overlap(child, rhs) {
+                    stack.push((child, rhs));
+                }
+            }
+        } else {
+            for child in rhs.children().rev() {
+                if overlap(lhs, child) {
+                    stack.push((lhs, child));
+                }
+            }
+        }
+    }
+    stats.aabb_tests += aabb_tests;
+    ret
+}
+
+fn intersected_tri_pairs<'a, N: BVHNodeRef<'a>>(
+    lhs: N,
+    rhs: N,
+    transform: Option<&Transform>,
+    stats: &mut QueryStats,
+) -> Vec<(TriIndex, TriIndex)> {
+    let leaf_pairs = leaf_pairs(lhs, rhs, transform, stats);
+    let mut ret = Vec::<(TriIndex, TriIndex)>::new();
+    for_each_tri_pair(
+        &leaf_pairs,
+        transform,
+        stats,
+        &mut |lhs_index, rhs_index, hit| {
+            if hit {
+                ret.push((lhs_index.clone(), rhs_index.clone()));
+            }
+        },
+    );
+    ret
+}
+
+fn probe_pair<'a, N: BVHNodeRef<'a>>(
+    lhs: N,
+    rhs: N,
+    transform: Option<&Transform>,
+    stats: &mut QueryStats,
+) -> BVHProbe {
+    let leaf_pairs = leaf_pairs(lhs, rhs, transform, stats);
+    let mut ret = BVHProbe {
+        leaves: leaf_pairs.len(),
+        ..BVHProbe::default()
+    };
+    for_each_tri_pair(&leaf_pairs, transform, stats, &mut |_, _, hit| {
+        ret.candidate_tris += 1;
+        if hit {
+            ret.hit_tris += 1;
+        }
+    });
+    ret
+}
+
+// tri_tests和hits按三角形对计数。空间切分会把同一对三角形放进多个叶子对，
+// 每对只测一次，f也只收到一次
+fn for_each_tri_pair<'a, N: BVHNodeRef<'a>>(
+    leaf_pairs: &[(N, N)],
+    transform: Option<&Transform>,
+    stats: &mut QueryStats,
+    f: &mut dyn FnMut(&TriIndex, &TriIndex, bool),
+) {
+    let transform = transform.copied().unwrap_or_default();
+    let mut tested = HashSet::<(TriKey, TriKey)>::new();
+    for (lhs_leaf, rhs_leaf) in leaf_pairs.iter() {
+        let rhs_tris = rhs_leaf
+            .tris()
+            .iter()
+            .map(|tri_index| transform.apply_tri(&tri_index.to_tri(rhs_leaf.vtx_buf())))
+            .collect::<Vec<Tri>>();
+        for lhs_index in lhs_leaf.tris() {
+            let lhs_tri = lhs_index.to_tri(lhs_leaf.vtx_buf());
+            for (rhs_index, rhs_tri) in rhs_leaf.tris().iter().zip(rhs_tris.iter()) {
+                if !tested.insert((tri_key(lhs_index), tri_key(rhs_index))) {
+                    continue;
+                }
+                let hit = lhs_tri.intersect_with_tri(rhs_tri);
+                stats.tri_tests += 1;
+                if hit {
+                    stats.hits += 1;
+                }
+                f(lhs_index, rhs_index, hit);
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::RTreePage;
+}
+
+// PhysX的RTree和BV4都是4叉树
+pub const PHYSX_NODE_WIDTH: usize = 4;
+// BV4叶子最多15个三角形
+pub const BVH34_MAX_PRIMS_PER_LEAF: usize = 15;
+
+// 对应PhysX RTreePage，一页存一个节点下4个孩子的包围盒(SoA)
+// ptrs低位为1表示叶子，其余位是叶子序号；为0表示子页序号
+#[derive(Clone, Debug)]
+pub struct RTreePage {
+    pub minx: [f32; PHYSX_NODE_WIDTH],
+    pub miny: [f32; PHYSX_NODE_WIDTH],
+    pub minz: [f32; PHYSX_NODE_WIDTH],
+    pub maxx: [f32; PHYSX_NODE_WIDTH],
+    pub maxy: [f32; PHYSX_NODE_WIDTH],
+    pub maxz: [f32; PHYSX_NODE_WIDTH],
+    pub ptrs: [u32; PHYSX_NODE_WIDTH],
+}
+
+impl RTreePage {
+    // 空槽位用反向的包围盒，和PhysX一样任何查询都不会命中
+    fn empty() -> Self {
+        Self {
+            minx: [f32::MAX; PHYSX_NODE_WIDTH],
+            miny: [f32::MAX; PHYSX_NODE_WIDTH],
+            minz: [f32::MAX; PHYSX_NODE_WIDTH],
+            maxx: [-f32::MAX; PHYSX_NODE_WIDTH],
+            maxy: [-f32::MAX; PHYSX_NODE_WIDTH],
+            maxz: [-f32::MAX; PHYSX_NODE_WIDTH],
+            ptrs: [0; PHYSX_NODE_WIDTH],
+        }
+    }
+
+    pub fn build_pages(bvh: Arc<BVHNode>) -> Vec<RTreePage> {
+        let mut pages = Vec::<RTreePage>::new();
+        let mut nleaves = 0_u32;
+        if !bvh.is_leaf() {
+            pages.push(Self::empty());
+            let mut stack = vec![(bvh, 0_usize)];
+            while let Some((node, page_idx)) = stack.pop() {
+                for (slot, child) in node.children.iter().enumerate() {
+                    let page = &mut pages[page_idx];
+                    page.minx[slot] = child.aabb.min.x as f32;
+                    page.miny[slot] = child.aabb.min.y as f32;
+                    page.minz[slot] = child.aabb.min.z as f32;
+                    page.maxx[slot] = child.aabb.max.x as f32;
+                    page.maxy[slot] = child.aabb.max.y as f32;
+                    page.maxz[slot] = child.aabb.max.z as f32;
+                    if child.is_leaf() {
+                        page.ptrs[slot] = (nleaves << 1) | 1;
+                        nleaves += 1;
+                    } else {
+                        let child_page = pages.len();
+                        pages[page_idx].ptrs[slot] = (child_page as u32) << 1;
+                        pages.push(Self::empty());
+                        stack.push((child.clone(), child_page));
+                    }
+                }
+            }
+        }
+        pages
+    }
+}
+
+// PhysX meshSizePerformanceTradeoff越大，叶子越大，内存越省但查询越慢
+pub fn bvh33_leaf_size(mesh_size_performance_tradeoff: f64) -> usize {
+    let tradeoff = mesh_size_performance_tradeoff.clamp(0.0, 1.0);
+    2 + (tradeoff * 14.0).round() as usize
+}
+
+pub(crate) fn subdivide_bvh33(bvh: &mut BVHNode, cfg: BVHSubdivideConfig) {
+    let leaf_size = bvh33_leaf_size(cfg.mesh_size_performance_tradeoff);
+    let binary_cfg = BVHSubdivideConfig {
+        num_tris_per_leaf: leaf_size,
+        max_tris_per_leaf: leaf_size,
+        split_method: BVHSplitMethod::SAH,
+        ..cfg
+    };
+    bvh.subdivide(binary_cfg);
+    let collapsed = BVHNode::collapse(Arc::new(bvh.clone()), PHYSX_NODE_WIDTH);
+
+    // RTree用f32存包围盒，往外取整保证仍然包住三角形
+    let aabb = round_to_f32(&collapsed.aabb);
+    *bvh = requantize(&collapsed, aabb, &|_, child| round_to_f32(child));
+}
+
+pub(crate) fn subdivide_bvh34(bvh: &mut BVHNode, cfg: BVHSubdivideConfig) {
+    let leaf_size = cfg.num_prims_per_leaf.clamp(1, BVH34_MAX_PRIMS_PER_LEAF);
+    let binary_cfg = BVHSubdivideConfig {
+        num_tris_per_leaf: leaf_size,
+        max_tris_per_leaf: leaf_size,
+        split_method: BVHSplitMethod::SAH,
+        ..cfg
+    };
+    bvh.subdivide(binary_cfg);
+    let collapsed = BVHNode::collapse(Arc::new(bvh.clone()), PHYSX_NODE_WIDTH);
+
+    // BV4把孩子包围盒相对父节点量化成16位
+    let aabb = collapsed.aabb.clone();
+    *bvh = requantize(&collapsed, aabb, &quantize_u16);
+}
+
+fn requantize(node: &BVHNode, aabb: AABB, quantize: &dyn Fn(&AABB, &AABB) -> AABB) -> BVHNode {
+    let children = node
+        .children
+        .iter()
+        .map(|child| {
+            let child_aabb = quantize(&aabb, &child.aabb);
+            Arc::new(requantize(child, child_aabb, quantize))
+        })
+        .collect();
+    BVHNode {
+        vtx_buf: node.vtx_buf.clone(),
+        idx_buf: node.idx_buf.clone(),
+        aabb,
+        children,
+    }
+}
+
+fn round_to_f32(aabb: &AABB) -> AABB {
+    let down = |v: f64| {
+        let f = v as f32;
+        if (f as f64) > v {
+            f.next_down() as f64
+        } else {
+            f as f64
+        }
+    };
+    let up = |v: f64| {
+        let f = v as f32;
+        if (f as f64) < v {
+            f.next_up() as f64
+        } else {
+            f as f64
+        }
+    };
+    AABB::new(
+        &Vec3::new(down(aabb.min.x), down(aabb.min.y), down(aabb.min.z)),
+        &Vec3::new(up(aabb.max.x), up(aabb.max.y), up(aabb.max.z)),
+    )
+}
+
+fn quantize_u16(parent: &AABB, child: &AABB) -> AABB {
+    let scale = 65535.0f64;
+    let quantize_axis = |lo: f64, hi: f64, min: f64, max: f64| -> (f64, f64) {
+        let span = hi - lo;
+        if span <= 0.0 {
+            return (min, max);
+        }
+        let qmin = ((min - lo) / span * scale).floor().clamp(0.0, scale);
+        let qmax = ((max - lo) / span * scale).ceil().clamp(0.0, scale);
+
+        // 反量化有舍入误差，夹在原包围盒和父节点之间保证仍然保守
+        let dqmin = (lo + qmin / scale * span).max(lo).min(min);
+        let dqmax = (lo + qmax / scale * span).min(hi).max(max);
+        (dqmin, dqmax)
+    };
+    let (minx, maxx) = quantize_axis(parent.min.x, parent.max.x, child.min.x, child.max.x);
+    let (miny, maxy) = quantize_axis(parent.min.y, parent.max.y, child.min.y, child.max.y);
+    let (minz, maxz) = quantize_axis(parent.min.z, parent.max.z, child.min.z, child.max.z);
+    AABB::new(&Vec3::new(minx, miny, minz), &Vec3::new(maxx, maxy, maxz))
+}
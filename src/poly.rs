@@ -2,7 +2,7 @@
 #![allow(unused_imports)]
 
 use crate::prelude::*;
-use std::{fmt::Error, rc::Rc};
+use std::{fmt::Error, sync::Arc};
 
 pub mod prelude {
     pub use super::IndexedPoly;
@@ -23,12 +23,12 @@ impl Poly {
 
 #[derive(Clone, Debug)]
 pub struct IndexedPoly {
-    pub vtx_buf: Rc<Vec<Vec3>>,
+    pub vtx_buf: Arc<Vec<Vec3>>,
     pub idx_buf: Vec<usize>,
 }
 
 impl IndexedPoly {
-    pub fn new(vtx_buf: Rc<Vec<Vec3>>, idx_buf: Vec<usize>) -> Self {
+    pub fn new(vtx_buf: Arc<Vec<Vec3>>, idx_buf: Vec<usize>) -> Self {
         Self { vtx_buf, idx_buf }
     }
 
@@ -63,11 +63,11 @@ impl PolyIndex {
         Self { idx_buf: pts }
     }
 
-    pub fn to_poly(&self, vtx_buf: Rc<Vec<Vec3>>) -> Poly {
+    pub fn to_poly(&self, vtx_buf: Arc<Vec<Vec3>>) -> Poly {
         Poly::new(self.idx_buf.iter().map(|idx| vtx_buf[*idx]).collect())
     }
 
-    pub fn to_indexed_poly(&self, vtx_buf: Rc<Vec<Vec3>>) -> IndexedPoly {
+    pub fn to_indexed_poly(&self, vtx_buf: Arc<Vec<Vec3>>) -> IndexedPoly {
         let idx_buf = self.idx_buf.clone();
         IndexedPoly::new(vtx_buf, idx_buf)
     }
@@ -0,0 +1,223 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use crate::visit::traverse_node_ordered;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::Ray;
+    pub use super::RayHit;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+    pub max_distance: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct RayHit {
+    pub tri_index: TriIndex,
+    pub distance: f64,
+    // 重心坐标，命中点 = (1 - u - v) * pt0 + u * pt1 + v * pt2
+    pub u: f64,
+    pub v: f64,
+}
+
+impl Ray {
+    pub fn new(origin: &Vec3, direction: &Vec3, max_distance: f64) -> Self {
+        let mut direction = *direction;
+        direction.normalize();
+        Self {
+            origin: *origin,
+            direction,
+            max_distance,
+        }
+    }
+
+    pub fn point_at(&self, distance: f64) -> Vec3 {
+        self.origin + self.direction * Vec3::new(distance, distance, distance)
+    }
+
+    // slab测试，返回射线进入包围盒的距离
+    pub fn intersect_aabb(&self, aabb: &AABB, max_distance: f64) -> Option<f64> {
+        let mut tmin = 0.0f64;
+        let mut tmax = max_distance;
+        for axis in [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z] {
+            let origin = self.origin.component(axis);
+            let dir = self.direction.component(axis);
+            let lo = aabb.min.component(axis);
+            let hi = aabb.max.component(axis);
+            if dir == 0.0 {
+                if origin < lo || origin > hi {
+                    return None;
+                }
+                continue;
+            }
+            let inv = 1.0 / dir;
+            let mut t0 = (lo - origin) * inv;
+            let mut t1 = (hi - origin) * inv;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            tmin = tmin.max(t0);
+            tmax = tmax.min(t1);
+            if tmin > tmax {
+                return None;
+            }
+        }
+        Some(tmin)
+    }
+
+    // Möller-Trumbore，返回(距离, u, v)
+    pub fn intersect_tri(&self, tri: &Tri, max_distance: f64) -> Option<(f64, f64, f64)> {
+        let edge1 = tri.pt1 - tri.pt0;
+        let edge2 = tri.pt2 - tri.pt0;
+        let pvec = self.direction.cross(&edge2);
+        let det = edge1.dot(&pvec);
+        if det.abs() < f64::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = self.origin - tri.pt0;
+        let u = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let qvec = tvec.cross(&edge1);
+        let v = self.direction.dot(&qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(&qvec) * inv_det;
+        if t < 0.0 || t > max_distance {
+            return None;
+        }
+        Some((t, u, v))
+    }
+}
+
+impl BVHNode {
+    pub fn raycast_closest(bvh: Arc<Self>, ray: &Ray) -> Option<RayHit> {
+        Self::raycast(bvh, ray, false, &mut QueryStats::default())
+    }
+
+    pub fn raycast_any(bvh: Arc<Self>, ray: &Ray) -> Option<RayHit> {
+        Self::raycast(bvh, ray, true, &mut QueryStats::default())
+    }
+
+    pub fn raycast_closest_with_stats(
+        bvh: Arc<Self>,
+        ray: &Ray,
+        stats: &mut QueryStats,
+    ) -> Option<RayHit> {
+        Self::raycast(bvh, ray, false, stats)
+    }
+
+    pub fn raycast_any_with_stats(
+        bvh: Arc<Self>,
+        ray: &Ray,
+        stats: &mut QueryStats,
+    ) -> Option<RayHit> {
+        Self::raycast(bvh, ray, true, stats)
+    }
+
+    fn raycast(bvh: Arc<Self>, ray: &Ray, any_hit: bool, stats: &mut QueryStats) -> Option<RayHit> {
+        raycast(&bvh, ray, any_hit, stats)
+    }
+}
+
+impl FlatBVH {
+    pub fn raycast_closest(&self, ray: &Ray) -> Option<RayHit> {
+        raycast(self.node_ref(0), ray, false, &mut QueryStats::default())
+    }
+
+    pub fn raycast_any(&self, ray: &Ray) -> Option<RayHit> {
+        raycast(self.node_ref(0), ray, true, &mut QueryStats::default())
+    }
+
+    pub fn raycast_closest_with_stats(&self, ray: &Ray, stats: &mut QueryStats) -> Option<RayHit> {
+        raycast(self.node_ref(0), ray, false, stats)
+    }
+
+    pub fn raycast_any_with_stats(&self, ray: &Ray, stats: &mut QueryStats) -> Option<RayHit> {
+        raycast(self.node_ref(0), ray, true, stats)
+    }
+}
+
+fn raycast<'a, N: BVHNodeRef<'a>>(
+    root: N,
+    ray: &Ray,
+    any_hit: bool,
+    stats: &mut QueryStats,
+) -> Option<RayHit> {
+    let mut visitor = RaycastVisitor {
+        ray,
+        any_hit,
+        max_distance: ray.max_distance,
+        best: None,
+        stats: QueryStats::default(),
+    };
+    traverse_node_ordered(root, &mut visitor, stats);
+    *stats += visitor.stats;
+    visitor.best
+}
+
+struct RaycastVisitor<'r> {
+    ray: &'r Ray,
+    any_hit: bool,
+    // 当前最近命中的距离，用来剪枝
+    max_distance: f64,
+    best: Option<RayHit>,
+    // 只累加包围盒测试、三角形测试和命中数
+    stats: QueryStats,
+}
+
+impl<'a, N: BVHNodeRef<'a>> BVHVisitor<'a, N> for RaycastVisitor<'_> {
+    fn visit_node(&mut self, node: N) -> VisitResult {
+        self.stats.aabb_tests += 1;
+        match self.ray.intersect_aabb(node.aabb(), self.max_distance) {
+            Some(_) => VisitResult::Continue,
+            None => VisitResult::Skip,
+        }
+    }
+
+    // 入栈时已经做过slab测试，出栈时只要和当前最近命中比一下入射距离
+    fn visit_ordered(&mut self, _node: N, priority: f64) -> VisitResult {
+        if priority <= self.max_distance {
+            VisitResult::Continue
+        } else {
+            VisitResult::Skip
+        }
+    }
+
+    // 三角形按射线自身的最大距离测试，命中数不受当前最近命中的影响
+    fn visit_leaf(&mut self, leaf: N) -> VisitResult {
+        for tri_index in leaf.tris() {
+            let tri = tri_index.to_tri(leaf.vtx_buf());
+            self.stats.tri_tests += 1;
+            if let Some((distance, u, v)) = self.ray.intersect_tri(&tri, self.ray.max_distance) {
+                self.stats.hits += 1;
+                if self.best.is_none() || distance < self.max_distance {
+                    self.max_distance = distance;
+                    self.best = Some(RayHit {
+                        tri_index: tri_index.clone(),
+                        distance,
+                        u,
+                        v,
+                    });
+                }
+                if self.any_hit {
+                    return VisitResult::Stop;
+                }
+            }
+        }
+        VisitResult::Continue
+    }
+
+    fn priority(&mut self, node: N) -> Option<f64> {
+        self.stats.aabb_tests += 1;
+        self.ray.intersect_aabb(node.aabb(), self.max_distance)
+    }
+}
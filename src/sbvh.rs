@@ -0,0 +1,294 @@
+#![allow(dead_code)]
+
+use crate::bvh::sah_cost;
+use crate::prelude::*;
+use std::sync::Arc;
+
+// 空间切分时一个三角形可能被多个叶子引用，每个引用只记录被裁剪后的包围盒
+#[derive(Clone, Debug)]
+struct Reference {
+    tri_index: TriIndex,
+    aabb: AABB,
+}
+
+struct Split {
+    cost: f64,
+    pos: Vec<Reference>,
+    neg: Vec<Reference>,
+}
+
+pub(crate) fn subdivide(bvh: &mut BVHNode, cfg: BVHSubdivideConfig) {
+    let refs = bvh
+        .idx_buf
+        .iter()
+        .map(|tri_index| Reference {
+            tri_index: tri_index.clone(),
+            aabb: tri_index.to_tri(&bvh.vtx_buf).to_aabb(),
+        })
+        .collect::<Vec<Reference>>();
+    if refs.is_empty() {
+        return;
+    }
+    let root_area = bvh.aabb.surface_area();
+    let mut budget = ((refs.len() as f64) * cfg.spatial_split_budget) as usize;
+    *bvh = build(bvh.vtx_buf.clone(), refs, &cfg, root_area, &mut budget);
+}
+
+fn build(
+    vtx_buf: Arc<Vec<Vec3>>,
+    refs: Vec<Reference>,
+    cfg: &BVHSubdivideConfig,
+    root_area: f64,
+    budget: &mut usize,
+) -> BVHNode {
+    let aabb = refs_aabb(&refs);
+    let idx_buf = refs.iter().map(|r| r.tri_index.clone()).collect();
+    let mut node = BVHNode::with_aabb(vtx_buf.clone(), idx_buf, aabb);
+    if refs.len() <= cfg.num_tris_per_leaf {
+        return node;
+    }
+
+    let mut split = object_split(&refs, &node.aabb, cfg);
+
+    // 只有对象切分的两个孩子重叠明显时才尝试空间切分
+    if *budget > 0 {
+        let overlap = refs_aabb(&split.pos)
+            .intersection(&refs_aabb(&split.neg))
+            .map_or(0.0, |aabb| aabb.surface_area());
+        if overlap / root_area > cfg.spatial_split_alpha {
+            if let Some(spatial) = spatial_split(&vtx_buf, &refs, &node.aabb, cfg) {
+                let duplicated = spatial.pos.len() + spatial.neg.len() - refs.len();
+                if spatial.cost < split.cost && duplicated <= *budget {
+                    *budget -= duplicated;
+                    split = spatial;
+                }
+            }
+        }
+    }
+
+    let child_pos = build(vtx_buf.clone(), split.pos, cfg, root_area, budget);
+    let child_neg = build(vtx_buf, split.neg, cfg, root_area, budget);
+    node.children.push(Arc::new(child_pos));
+    node.children.push(Arc::new(child_neg));
+    node
+}
+
+fn refs_aabb(refs: &[Reference]) -> AABB {
+    let mut iter = refs.iter();
+    let mut ret = match iter.next() {
+        Some(r) => r.aabb.clone(),
+        None => return AABB::default(),
+    };
+    for r in iter {
+        ret = ret.merge(&r.aabb);
+    }
+    ret
+}
+
+fn grow(acc: &mut Option<AABB>, aabb: &AABB) {
+    *acc = Some(match acc {
+        Some(acc) => acc.merge(aabb),
+        None => aabb.clone(),
+    });
+}
+
+fn object_split(refs: &[Reference], aabb: &AABB, cfg: &BVHSubdivideConfig) -> Split {
+    let nrefs = refs.len();
+    let parent_area = aabb.surface_area();
+
+    let mut best: Option<(f64, Vec<usize>, usize)> = None;
+    for axis in [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z] {
+        let mut order = (0..nrefs).collect::<Vec<usize>>();
+        order.sort_by(|a, b| {
+            let ca = refs[*a].aabb.center().component(axis);
+            let cb = refs[*b].aabb.center().component(axis);
+            ca.total_cmp(&cb)
+        });
+
+        let mut right_areas = vec![0.0f64; nrefs];
+        let mut acc = refs[order[nrefs - 1]].aabb.clone();
+        for i in (1..nrefs).rev() {
+            acc = acc.merge(&refs[order[i]].aabb);
+            right_areas[i] = acc.surface_area();
+        }
+
+        let mut acc = refs[order[0]].aabb.clone();
+        let mut axis_best: Option<(f64, usize)> = None;
+        for i in 1..nrefs {
+            let cost = sah_cost(
+                cfg,
+                parent_area,
+                acc.surface_area(),
+                i,
+                right_areas[i],
+                nrefs - i,
+            );
+            if axis_best.is_none_or(|(best_cost, _)| cost < best_cost) {
+                axis_best = Some((cost, i));
+            }
+            acc = acc.merge(&refs[order[i]].aabb);
+        }
+
+        if let Some((cost, sep)) = axis_best {
+            if best
+                .as_ref()
+                .is_none_or(|(best_cost, _, _)| cost < *best_cost)
+            {
+                best = Some((cost, order, sep));
+            }
+        }
+    }
+
+    let (cost, order, sep) = best.unwrap();
+    Split {
+        cost,
+        pos: order[..sep].iter().map(|i| refs[*i].clone()).collect(),
+        neg: order[sep..].iter().map(|i| refs[*i].clone()).collect(),
+    }
+}
+
+fn spatial_split(
+    vtx_buf: &Arc<Vec<Vec3>>,
+    refs: &[Reference],
+    aabb: &AABB,
+    cfg: &BVHSubdivideConfig,
+) -> Option<Split> {
+    let nbins = cfg.num_bins.max(2);
+    let parent_area = aabb.surface_area();
+
+    let mut best: Option<(f64, AABBSplitAxis, f64)> = None;
+    for axis in [AABBSplitAxis::X, AABBSplitAxis::Y, AABBSplitAxis::Z] {
+        let lo = aabb.min.component(axis);
+        let span = aabb.extent().component(axis);
+        if span <= 0.0 {
+            continue;
+        }
+        let plane_of = |bin: usize| lo + span * (bin as f64) / (nbins as f64);
+        let bin_of = |pos: f64| (((pos - lo) / span * (nbins as f64)) as usize).min(nbins - 1);
+
+        // 把每个引用按桶边界逐段裁开，记录进入和离开的桶
+        let mut bins: Vec<Option<AABB>> = vec![None; nbins];
+        let mut entries = vec![0_usize; nbins];
+        let mut exits = vec![0_usize; nbins];
+        for r in refs.iter() {
+            let first = bin_of(r.aabb.min.component(axis));
+            let last = bin_of(r.aabb.max.component(axis));
+            let tri = r.tri_index.to_tri(vtx_buf);
+            let mut rest = r.aabb.clone();
+            for (bin, bin_aabb) in bins.iter_mut().enumerate().take(last).skip(first) {
+                let (neg, pos) = split_reference(&tri, &rest, axis, plane_of(bin + 1));
+                grow(bin_aabb, &neg);
+                rest = pos;
+            }
+            grow(&mut bins[last], &rest);
+            entries[first] += 1;
+            exits[last] += 1;
+        }
+
+        let mut right_areas = vec![0.0f64; nbins];
+        let mut right_counts = vec![0_usize; nbins];
+        let mut acc: Option<AABB> = None;
+        let mut count = 0_usize;
+        for b in (1..nbins).rev() {
+            if let Some(bin_aabb) = &bins[b] {
+                grow(&mut acc, bin_aabb);
+            }
+            count += exits[b];
+            right_areas[b] = acc.as_ref().map_or(0.0, |acc| acc.surface_area());
+            right_counts[b] = count;
+        }
+
+        let mut acc: Option<AABB> = None;
+        let mut count = 0_usize;
+        for b in 1..nbins {
+            if let Some(bin_aabb) = &bins[b - 1] {
+                grow(&mut acc, bin_aabb);
+            }
+            count += entries[b - 1];
+            if count == 0 || right_counts[b] == 0 {
+                continue;
+            }
+            let left_area = acc.as_ref().map_or(0.0, |acc| acc.surface_area());
+            let cost = sah_cost(
+                cfg,
+                parent_area,
+                left_area,
+                count,
+                right_areas[b],
+                right_counts[b],
+            );
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, plane_of(b)));
+            }
+        }
+    }
+
+    let (cost, axis, plane) = best?;
+    let mut pos = Vec::<Reference>::new();
+    let mut neg = Vec::<Reference>::new();
+    for r in refs.iter() {
+        if r.aabb.max.component(axis) <= plane {
+            neg.push(r.clone());
+        } else if r.aabb.min.component(axis) >= plane {
+            pos.push(r.clone());
+        } else {
+            let tri = r.tri_index.to_tri(vtx_buf);
+            let (neg_aabb, pos_aabb) = split_reference(&tri, &r.aabb, axis, plane);
+            neg.push(Reference {
+                tri_index: r.tri_index.clone(),
+                aabb: neg_aabb,
+            });
+            pos.push(Reference {
+                tri_index: r.tri_index.clone(),
+                aabb: pos_aabb,
+            });
+        }
+    }
+
+    // 切不动的话就放弃，否则会无限递归
+    if pos.len() >= refs.len() || neg.len() >= refs.len() {
+        return None;
+    }
+    Some(Split { cost, pos, neg })
+}
+
+// 用切分面裁剪三角形，返回两侧多边形各自的包围盒，再和原引用的包围盒求交
+fn split_reference(tri: &Tri, aabb: &AABB, axis: AABBSplitAxis, plane: f64) -> (AABB, AABB) {
+    let pts = [tri.pt0, tri.pt1, tri.pt2];
+    let mut neg: Option<AABB> = None;
+    let mut pos: Option<AABB> = None;
+    for i in 0..3 {
+        let v0 = pts[i];
+        let v1 = pts[(i + 1) % 3];
+        let a0 = v0.component(axis);
+        let a1 = v1.component(axis);
+        if a0 <= plane {
+            grow(&mut neg, &AABB::new(&v0, &v0));
+        }
+        if a0 >= plane {
+            grow(&mut pos, &AABB::new(&v0, &v0));
+        }
+        if (a0 < plane && a1 > plane) || (a0 > plane && a1 < plane) {
+            let t = (plane - a0) / (a1 - a0);
+            let mut pt = v0 + (v1 - v0) * Vec3::new(t, t, t);
+            pt.set_component(axis, plane);
+            grow(&mut neg, &AABB::new(&pt, &pt));
+            grow(&mut pos, &AABB::new(&pt, &pt));
+        }
+    }
+
+    let clamp = |side: Option<AABB>, is_neg: bool| -> AABB {
+        let mut ret = side
+            .and_then(|side| side.intersection(aabb))
+            .unwrap_or_else(|| aabb.clone());
+        if is_neg {
+            let max = ret.max.component(axis).min(plane);
+            ret.max.set_component(axis, max);
+        } else {
+            let min = ret.min.component(axis).max(plane);
+            ret.min.set_component(axis, min);
+        }
+        ret
+    };
+    (clamp(neg, true), clamp(pos, false))
+}
@@ -0,0 +1,119 @@
+#![allow(dead_code)]
+
+use crate::pair::leaf_pairs;
+use crate::prelude::*;
+use crate::tri::{tri_key, TriKey};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::SelfIntersection;
+}
+
+#[derive(Clone, Debug)]
+pub struct SelfIntersection {
+    pub lhs: TriIndex,
+    pub rhs: TriIndex,
+    // 共面重叠时交集不是线段，为None
+    pub segment: Option<(Vec3, Vec3)>,
+}
+
+// 按顶点序号判断，共用顶点或边的相邻三角形在接缝处本来就接触
+fn adjacent(lhs: &TriIndex, rhs: &TriIndex) -> bool {
+    let rhs = [rhs.pt0, rhs.pt1, rhs.pt2];
+    [lhs.pt0, lhs.pt1, lhs.pt2]
+        .iter()
+        .any(|idx| rhs.contains(idx))
+}
+
+impl BVHNode {
+    pub fn get_self_intersections(bvh: Arc<Self>) -> Vec<SelfIntersection> {
+        Self::get_self_intersections_with_stats(bvh, &mut QueryStats::default())
+    }
+
+    // 和get_overlapped_leaf_pairs一样按节点对计数，tri_tests不含相邻和重复的三角形对
+    pub fn get_self_intersections_with_stats(
+        bvh: Arc<Self>,
+        stats: &mut QueryStats,
+    ) -> Vec<SelfIntersection> {
+        self_intersections(&bvh, stats)
+    }
+}
+
+impl FlatBVH {
+    pub fn get_self_intersections(&self) -> Vec<SelfIntersection> {
+        self.get_self_intersections_with_stats(&mut QueryStats::default())
+    }
+
+    pub fn get_self_intersections_with_stats(
+        &self,
+        stats: &mut QueryStats,
+    ) -> Vec<SelfIntersection> {
+        self_intersections(self.node_ref(0), stats)
+    }
+}
+
+fn self_intersections<'a, N: BVHNodeRef<'a>>(
+    root: N,
+    stats: &mut QueryStats,
+) -> Vec<SelfIntersection> {
+    let mut leaf_pairs = Vec::<(N, N)>::new();
+    collect_self_leaf_pairs(root, &mut leaf_pairs, stats);
+
+    // 空间切分会把同一个三角形放进多个叶子，同一对三角形只测一次、只报一次
+    let mut tested = HashSet::<(TriKey, TriKey)>::new();
+    let mut ret = Vec::<SelfIntersection>::new();
+    for (lhs_leaf, rhs_leaf) in leaf_pairs.iter() {
+        let same_leaf = lhs_leaf.same_node(*rhs_leaf);
+        for (lhs_pos, lhs_index) in lhs_leaf.tris().iter().enumerate() {
+            let rhs_start = if same_leaf { lhs_pos + 1 } else { 0 };
+            for rhs_index in rhs_leaf.tris()[rhs_start..].iter() {
+                if adjacent(lhs_index, rhs_index) {
+                    continue;
+                }
+                let (lhs_key, rhs_key) = (tri_key(lhs_index), tri_key(rhs_index));
+                let key = if lhs_key <= rhs_key {
+                    (lhs_key, rhs_key)
+                } else {
+                    (rhs_key, lhs_key)
+                };
+                if !tested.insert(key) {
+                    continue;
+                }
+                let lhs_tri = lhs_index.to_tri(lhs_leaf.vtx_buf());
+                let rhs_tri = rhs_index.to_tri(rhs_leaf.vtx_buf());
+                stats.tri_tests += 1;
+                if lhs_tri.intersect_with_tri(&rhs_tri) {
+                    stats.hits += 1;
+                    ret.push(SelfIntersection {
+                        lhs: lhs_index.clone(),
+                        rhs: rhs_index.clone(),
+                        segment: lhs_tri.intersection_segment_unchecked(&rhs_tri),
+                    });
+                }
+            }
+        }
+    }
+    ret
+}
+
+// 一个节点和自己：每个孩子和自己，再加上每两个不同孩子之间
+fn collect_self_leaf_pairs<'a, N: BVHNodeRef<'a>>(
+    node: N,
+    ret: &mut Vec<(N, N)>,
+    stats: &mut QueryStats,
+) {
+    stats.nodes_visited += 1;
+    if node.is_leaf() {
+        stats.leaves += 1;
+        stats.leaf_tris += node.tris().len();
+        ret.push((node, node));
+        return;
+    }
+    for (idx, lhs) in node.children().enumerate() {
+        collect_self_leaf_pairs(lhs, ret, stats);
+        for rhs in node.children().skip(idx + 1) {
+            ret.extend(leaf_pairs(lhs, rhs, None, stats));
+        }
+    }
+}
@@ -0,0 +1,402 @@
+#![allow(dead_code)]
+
+use crate::bvh::{block_overlap_peak_with, directional_hit_with, surface_hit_peak_with};
+use crate::prelude::*;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::Capsule;
+    pub use super::Shape;
+    pub use super::Sphere;
+}
+
+// 探测用的凸体，先用包围盒粗筛节点，再对叶子里的三角形做精确测试
+pub trait Shape: Sync {
+    fn to_aabb(&self) -> AABB;
+
+    fn intersect_with_aabb(&self, aabb: &AABB) -> bool {
+        self.to_aabb().intersect_with_aabb(aabb)
+    }
+
+    fn intersect_with_tri(&self, tri: &Tri) -> bool;
+
+    // 扫描时把同一个形状平移到每个采样点
+    fn centered_at(&self, center: &Vec3) -> Self
+    where
+        Self: Sized;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f64,
+}
+
+// 两端半球中心分别是pt0和pt1
+#[derive(Copy, Clone, Debug)]
+pub struct Capsule {
+    pub pt0: Vec3,
+    pub pt1: Vec3,
+    pub radius: f64,
+}
+
+// 探测盒本身也是形状，批量查询和峰值扫描可以和球、胶囊走同一套接口
+impl Shape for AABB {
+    fn to_aabb(&self) -> AABB {
+        self.clone()
+    }
+
+    fn intersect_with_aabb(&self, aabb: &AABB) -> bool {
+        AABB::intersect_with_aabb(self, aabb)
+    }
+
+    fn intersect_with_tri(&self, tri: &Tri) -> bool {
+        tri.intersect_with_aabb(self)
+    }
+
+    fn centered_at(&self, center: &Vec3) -> Self {
+        let half = self.extent() / Vec3::new(2.0, 2.0, 2.0);
+        AABB::new(&(*center - half), &(*center + half))
+    }
+}
+
+impl Sphere {
+    pub fn new(center: &Vec3, radius: f64) -> Self {
+        Self {
+            center: *center,
+            radius,
+        }
+    }
+}
+
+impl Shape for Sphere {
+    fn to_aabb(&self) -> AABB {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        AABB::new(&(self.center - r), &(self.center + r))
+    }
+
+    fn intersect_with_aabb(&self, aabb: &AABB) -> bool {
+        aabb.distance_to_point(&self.center) <= self.radius
+    }
+
+    fn intersect_with_tri(&self, tri: &Tri) -> bool {
+        tri.closest_point(&self.center).distance_to(&self.center) <= self.radius
+    }
+
+    fn centered_at(&self, center: &Vec3) -> Self {
+        Self::new(center, self.radius)
+    }
+}
+
+impl Capsule {
+    pub fn new(pt0: &Vec3, pt1: &Vec3, radius: f64) -> Self {
+        Self {
+            pt0: *pt0,
+            pt1: *pt1,
+            radius,
+        }
+    }
+
+    // 角色控制器常用的竖直胶囊，half_height是中段的一半，不含半球
+    pub fn upright(center: &Vec3, half_height: f64, radius: f64) -> Self {
+        let offset = Vec3::new(0.0, 0.0, half_height);
+        Self::new(&(*center - offset), &(*center + offset), radius)
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.pt0 + self.pt1) / Vec3::new(2.0, 2.0, 2.0)
+    }
+}
+
+impl Shape for Capsule {
+    fn to_aabb(&self) -> AABB {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        AABB::new(
+            &(Vec3::min(&self.pt0, &self.pt1) - r),
+            &(Vec3::max(&self.pt0, &self.pt1) + r),
+        )
+    }
+
+    fn intersect_with_tri(&self, tri: &Tri) -> bool {
+        segment_tri_distance(&self.pt0, &self.pt1, tri) <= self.radius
+    }
+
+    fn centered_at(&self, center: &Vec3) -> Self {
+        let offset = *center - self.center();
+        Self::new(&(self.pt0 + offset), &(self.pt1 + offset), self.radius)
+    }
+}
+
+// 线段穿过三角形时距离为0，否则最近点对一定在线段端点或三角形的某条边上
+fn segment_tri_distance(pt0: &Vec3, pt1: &Vec3, tri: &Tri) -> f64 {
+    let length = pt0.distance_to(pt1);
+    if length > 0.0 {
+        let ray = Ray::new(pt0, &(*pt1 - *pt0), length);
+        if ray.intersect_tri(tri, length).is_some() {
+            return 0.0;
+        }
+    }
+    let mut dist = tri
+        .closest_point(pt0)
+        .distance_to(pt0)
+        .min(tri.closest_point(pt1).distance_to(pt1));
+    for (e0, e1) in [(tri.pt0, tri.pt1), (tri.pt1, tri.pt2), (tri.pt2, tri.pt0)] {
+        dist = dist.min(segment_segment_distance(pt0, pt1, &e0, &e1));
+    }
+    dist
+}
+
+fn segment_segment_distance(p1: &Vec3, q1: &Vec3, p2: &Vec3, q2: &Vec3) -> f64 {
+    let scale = |v: Vec3, t: f64| v * Vec3::new(t, t, t);
+    let d1 = *q1 - *p1;
+    let d2 = *q2 - *p2;
+    let r = *p1 - *p2;
+    let a = d1.dot(&d1);
+    let e = d2.dot(&d2);
+    let f = d2.dot(&r);
+
+    let (s, t) = if a <= f64::EPSILON && e <= f64::EPSILON {
+        (0.0, 0.0)
+    } else if a <= f64::EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(&r);
+        if e <= f64::EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(&d2);
+            let denom = a * e - b * b;
+            // 平行时随便取一端，后面会再夹一次
+            let mut s = if denom > f64::EPSILON {
+                ((b * f - c * e) / denom).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let mut t = (b * s + f) / e;
+            if t < 0.0 {
+                t = 0.0;
+                s = (-c / a).clamp(0.0, 1.0);
+            } else if t > 1.0 {
+                t = 1.0;
+                s = ((b - c) / a).clamp(0.0, 1.0);
+            }
+            (s, t)
+        }
+    };
+    (*p1 + scale(d1, s)).distance_to(&(*p2 + scale(d2, t)))
+}
+
+impl BVHNode {
+    pub fn get_overlapped_leaves<S: Shape>(bvh: Arc<Self>, shape: &S) -> Vec<Arc<Self>> {
+        Self::get_overlapped_leaves_with_stats(bvh, shape, &mut QueryStats::default())
+    }
+
+    pub fn get_overlapped_leaves_with_stats<S: Shape>(
+        bvh: Arc<Self>,
+        shape: &S,
+        stats: &mut QueryStats,
+    ) -> Vec<Arc<Self>> {
+        let mut ret = Vec::<Arc<Self>>::new();
+        Self::traverse_shape(
+            &bvh,
+            shape,
+            |leaf, _| {
+                ret.push(leaf.clone());
+                VisitResult::Continue
+            },
+            stats,
+        );
+        ret
+    }
+
+    pub fn get_overlapped_tris<S: Shape>(bvh: Arc<Self>, shape: &S) -> Vec<TriIndex> {
+        Self::get_overlapped_tris_with_stats(bvh, shape, &mut QueryStats::default())
+    }
+
+    pub fn get_overlapped_tris_with_stats<S: Shape>(
+        bvh: Arc<Self>,
+        shape: &S,
+        stats: &mut QueryStats,
+    ) -> Vec<TriIndex> {
+        let mut ret = Vec::<TriIndex>::new();
+        Self::collect_overlapped_tris_with_stats(&bvh, shape, &mut ret, stats);
+        ret
+    }
+
+    pub fn probe_shape<S: Shape>(bvh: Arc<Self>, shape: &S) -> BVHProbe {
+        Self::probe_shape_with_stats(bvh, shape, &mut QueryStats::default())
+    }
+
+    pub fn probe_shape_with_stats<S: Shape>(
+        bvh: Arc<Self>,
+        shape: &S,
+        stats: &mut QueryStats,
+    ) -> BVHProbe {
+        let mut ret = BVHProbe::default();
+        Self::probe_into_with_stats(&bvh, shape, &mut ret, stats);
+        ret
+    }
+
+    // 和directional_hit一样沿start到end扫描，探测盒换成以采样点为中心的shape
+    pub fn directional_shape_hit<S: Shape>(
+        bvh: Arc<Self>,
+        shape: &S,
+        start: &Vec3,
+        end: &Vec3,
+        step_into: f64,
+        break_on_hit: bool,
+    ) -> usize {
+        let block_size = shape.to_aabb().extent();
+        let leaf_count = |aabb: &AABB| {
+            let probe = shape.centered_at(&aabb.center());
+            Self::count_overlapped_leaves(&bvh, &probe)
+        };
+        directional_hit_with(
+            &leaf_count,
+            &block_size,
+            start,
+            end,
+            step_into,
+            break_on_hit,
+        )
+    }
+
+    pub fn block_overlap_shape_peak<S: Shape>(bvh: Arc<Self>, shape: &S, step: f64) -> usize {
+        Self::block_overlap_shape_peak_parallel(bvh, shape, step, 1)
+    }
+
+    pub fn block_overlap_shape_peak_parallel<S: Shape>(
+        bvh: Arc<Self>,
+        shape: &S,
+        step: f64,
+        num_workers: usize,
+    ) -> usize {
+        let leaf_count = |aabb: &AABB| {
+            let probe = shape.centered_at(&aabb.center());
+            Self::count_overlapped_leaves(&bvh, &probe)
+        };
+        block_overlap_peak_with(&bvh.aabb, &leaf_count, step, num_workers)
+    }
+
+    pub fn surface_shape_hit_peak<S: Shape>(bvh: Arc<Self>, shape: &S, step: f64) -> usize {
+        Self::surface_shape_hit_peak_parallel(bvh, shape, step, 1)
+    }
+
+    pub fn surface_shape_hit_peak_parallel<S: Shape>(
+        bvh: Arc<Self>,
+        shape: &S,
+        step: f64,
+        num_workers: usize,
+    ) -> usize {
+        let block_size = shape.to_aabb().extent();
+        let leaf_count = |aabb: &AABB| {
+            let probe = shape.centered_at(&aabb.center());
+            Self::count_overlapped_leaves(&bvh, &probe)
+        };
+        surface_hit_peak_with(&bvh.aabb, &leaf_count, step, &block_size, num_workers)
+    }
+}
+
+impl FlatBVH {
+    pub fn get_overlapped_leaves<S: Shape>(&self, shape: &S) -> Vec<usize> {
+        self.get_overlapped_leaves_with_stats(shape, &mut QueryStats::default())
+    }
+
+    pub fn get_overlapped_leaves_with_stats<S: Shape>(
+        &self,
+        shape: &S,
+        stats: &mut QueryStats,
+    ) -> Vec<usize> {
+        let mut ret = Vec::<usize>::new();
+        self.collect_overlapped_leaves_with_stats(shape, &mut ret, stats);
+        ret
+    }
+
+    pub fn get_overlapped_tris<S: Shape>(&self, shape: &S) -> Vec<TriIndex> {
+        self.get_overlapped_tris_with_stats(shape, &mut QueryStats::default())
+    }
+
+    pub fn get_overlapped_tris_with_stats<S: Shape>(
+        &self,
+        shape: &S,
+        stats: &mut QueryStats,
+    ) -> Vec<TriIndex> {
+        let mut ret = Vec::<TriIndex>::new();
+        self.collect_overlapped_tris_with_stats(shape, &mut ret, stats);
+        ret
+    }
+
+    pub fn probe_shape<S: Shape>(&self, shape: &S) -> BVHProbe {
+        self.probe_shape_with_stats(shape, &mut QueryStats::default())
+    }
+
+    pub fn probe_shape_with_stats<S: Shape>(&self, shape: &S, stats: &mut QueryStats) -> BVHProbe {
+        let mut ret = BVHProbe::default();
+        self.probe_into_with_stats(shape, &mut ret, stats);
+        ret
+    }
+
+    pub fn directional_shape_hit<S: Shape>(
+        &self,
+        shape: &S,
+        start: &Vec3,
+        end: &Vec3,
+        step_into: f64,
+        break_on_hit: bool,
+    ) -> usize {
+        let block_size = shape.to_aabb().extent();
+        let leaf_count = |aabb: &AABB| {
+            let probe = shape.centered_at(&aabb.center());
+            self.count_overlapped_leaves(&probe)
+        };
+        directional_hit_with(
+            &leaf_count,
+            &block_size,
+            start,
+            end,
+            step_into,
+            break_on_hit,
+        )
+    }
+
+    pub fn block_overlap_shape_peak<S: Shape>(&self, shape: &S, step: f64) -> usize {
+        self.block_overlap_shape_peak_parallel(shape, step, 1)
+    }
+
+    pub fn block_overlap_shape_peak_parallel<S: Shape>(
+        &self,
+        shape: &S,
+        step: f64,
+        num_workers: usize,
+    ) -> usize {
+        let leaf_count = |aabb: &AABB| {
+            let probe = shape.centered_at(&aabb.center());
+            self.count_overlapped_leaves(&probe)
+        };
+        block_overlap_peak_with(&self.root().aabb, &leaf_count, step, num_workers)
+    }
+
+    pub fn surface_shape_hit_peak<S: Shape>(&self, shape: &S, step: f64) -> usize {
+        self.surface_shape_hit_peak_parallel(shape, step, 1)
+    }
+
+    pub fn surface_shape_hit_peak_parallel<S: Shape>(
+        &self,
+        shape: &S,
+        step: f64,
+        num_workers: usize,
+    ) -> usize {
+        let block_size = shape.to_aabb().extent();
+        let leaf_count = |aabb: &AABB| {
+            let probe = shape.centered_at(&aabb.center());
+            self.count_overlapped_leaves(&probe)
+        };
+        surface_hit_peak_with(
+            &self.root().aabb,
+            &leaf_count,
+            step,
+            &block_size,
+            num_workers,
+        )
+    }
+}
@@ -0,0 +1,33 @@
+#![allow(dead_code)]
+
+pub mod prelude {
+    pub use super::QueryStats;
+}
+
+// 一次查询的遍历开销，需要时传给各个_with_stats查询，结果会累加上去
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    // 调用visit_node的节点数，包括叶子
+    pub nodes_visited: usize,
+    // 访问者真正做的节点包围盒测试，有序遍历里被剪掉没入栈的孩子也算，
+    // 所以可能比nodes_visited多；不测包围盒的自定义访问者不计
+    pub aabb_tests: usize,
+    pub leaves: usize,
+    // 到达的叶子里存放的三角形总数，访问者不一定逐个测试
+    pub leaf_tris: usize,
+    pub tri_tests: usize,
+    // 测试过的三角形里，在查询自身的范围(探测形状、射线或扫掠的最大距离、
+    // 最近点的搜索半径)内通过精确测试的个数，不管有没有刷新当前最好的结果
+    pub hits: usize,
+}
+
+impl std::ops::AddAssign for QueryStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.nodes_visited += rhs.nodes_visited;
+        self.aabb_tests += rhs.aabb_tests;
+        self.leaves += rhs.leaves;
+        self.leaf_tris += rhs.leaf_tris;
+        self.tri_tests += rhs.tri_tests;
+        self.hits += rhs.hits;
+    }
+}
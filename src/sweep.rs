@@ -0,0 +1,413 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use crate::visit::traverse_node_ordered;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::SweepHit;
+}
+
+#[derive(Clone, Debug)]
+pub struct SweepHit {
+    pub tri_index: TriIndex,
+    // 沿单位方向移动多远时第一次接触，一开始就重叠时为0
+    pub distance: f64,
+}
+
+impl Sphere {
+    // 先看球面最前端能否落在三角形面内，不能的话接触点一定在边或顶点上，
+    // 等价于射线打到边为轴的圆柱或顶点为心的球
+    pub fn sweep_tri(&self, direction: &Vec3, max_distance: f64, tri: &Tri) -> Option<f64> {
+        let scale = |v: Vec3, t: f64| v * Vec3::new(t, t, t);
+        let r = self.radius;
+        let c = self.center;
+        if tri.closest_point(&c).distance_to(&c) <= r {
+            return Some(0.0);
+        }
+
+        let mut face_normal = (tri.pt1 - tri.pt0).cross(&(tri.pt2 - tri.pt0));
+        if face_normal.dot(&face_normal) > f64::EPSILON {
+            face_normal.normalize();
+            // 朝向球心一侧的法线
+            let mut normal = face_normal;
+            let mut side = (c - tri.pt0).dot(&normal);
+            if side < 0.0 {
+                normal = Vec3::default() - normal;
+                side = -side;
+            }
+            let dn = direction.dot(&normal);
+            if dn < 0.0 {
+                let t = (side - r) / -dn;
+                let contact = c + scale(*direction, t) - scale(normal, r);
+                if (0.0..=max_distance).contains(&t) && point_in_tri(&contact, tri, &face_normal) {
+                    return Some(t);
+                }
+            }
+        }
+
+        let mut best: Option<f64> = None;
+        let mut keep = |t: Option<f64>| {
+            if let Some(t) = t.filter(|t| *t <= max_distance) {
+                best = Some(best.map_or(t, |b: f64| b.min(t)));
+            }
+        };
+        for vtx in [tri.pt0, tri.pt1, tri.pt2] {
+            keep(ray_sphere(&c, direction, &vtx, r));
+        }
+        for (e0, e1) in [(tri.pt0, tri.pt1), (tri.pt1, tri.pt2), (tri.pt2, tri.pt0)] {
+            keep(ray_cylinder(&c, direction, &e0, &e1, r));
+        }
+        best
+    }
+}
+
+impl OBB {
+    // 平移的分离轴测试：每个轴上求两段投影重叠的时间区间，
+    // 13个区间的交集就是盒子和三角形相交的时间段
+    pub fn sweep_tri(&self, direction: &Vec3, max_distance: f64, tri: &Tri) -> Option<f64> {
+        let local = |pt: &Vec3| {
+            let d = *pt - self.center;
+            Vec3::new(
+                d.dot(&self.axes[0]),
+                d.dot(&self.axes[1]),
+                d.dot(&self.axes[2]),
+            )
+        };
+        let v0 = local(&tri.pt0);
+        let v1 = local(&tri.pt1);
+        let v2 = local(&tri.pt2);
+        let dir = Vec3::new(
+            direction.dot(&self.axes[0]),
+            direction.dot(&self.axes[1]),
+            direction.dot(&self.axes[2]),
+        );
+        let half = self.half_extents;
+
+        let box_axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        let edges = [v1 - v0, v2 - v1, v0 - v2];
+        let mut axes = box_axes.to_vec();
+        axes.push(edges[0].cross(&edges[1]));
+        for edge in edges.iter() {
+            for axis in box_axes.iter() {
+                axes.push(edge.cross(axis));
+            }
+        }
+
+        let mut t_enter = 0.0f64;
+        let mut t_exit = max_distance;
+        for axis in axes.iter() {
+            // 叉积为0说明和已有的轴平行，跳过
+            if axis.dot(axis) <= f64::EPSILON {
+                continue;
+            }
+            let p0 = v0.dot(axis);
+            let p1 = v1.dot(axis);
+            let p2 = v2.dot(axis);
+            let r = half.x * axis.x.abs() + half.y * axis.y.abs() + half.z * axis.z.abs();
+            let lo = p0.min(p1).min(p2) - r;
+            let hi = p0.max(p1).max(p2) + r;
+            let speed = dir.dot(axis);
+            if speed.abs() <= f64::EPSILON {
+                if lo > 0.0 || hi < 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let mut t0 = lo / speed;
+            let mut t1 = hi / speed;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_enter = t_enter.max(t0);
+            t_exit = t_exit.min(t1);
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+        Some(t_enter)
+    }
+}
+
+// pt已经在三角形平面上，看它是否在三条边的内侧
+fn point_in_tri(pt: &Vec3, tri: &Tri, normal: &Vec3) -> bool {
+    [(tri.pt0, tri.pt1), (tri.pt1, tri.pt2), (tri.pt2, tri.pt0)]
+        .iter()
+        .all(|(e0, e1)| (*e1 - *e0).cross(&(*pt - *e0)).dot(normal) >= 0.0)
+}
+
+fn ray_sphere(origin: &Vec3, direction: &Vec3, center: &Vec3, radius: f64) -> Option<f64> {
+    let m = *origin - *center;
+    let b = m.dot(direction);
+    let c = m.dot(&m) - radius * radius;
+    if c > 0.0 && b > 0.0 {
+        return None;
+    }
+    let disc = b * b - c;
+    if disc < 0.0 {
+        return None;
+    }
+    Some((-b - disc.sqrt()).max(0.0))
+}
+
+// 射线和以pt0-pt1为轴的有限圆柱侧面求交，两端由顶点的球负责
+fn ray_cylinder(
+    origin: &Vec3,
+    direction: &Vec3,
+    pt0: &Vec3,
+    pt1: &Vec3,
+    radius: f64,
+) -> Option<f64> {
+    let axis = *pt1 - *pt0;
+    let axis_len2 = axis.dot(&axis);
+    if axis_len2 <= f64::EPSILON {
+        return None;
+    }
+    let m = *origin - *pt0;
+    let reject = |v: Vec3| {
+        let k = v.dot(&axis) / axis_len2;
+        v - axis * Vec3::new(k, k, k)
+    };
+    let d_perp = reject(*direction);
+    let m_perp = reject(m);
+    let a = d_perp.dot(&d_perp);
+    if a <= f64::EPSILON {
+        return None;
+    }
+    let b = m_perp.dot(&d_perp);
+    let c = m_perp.dot(&m_perp) - radius * radius;
+    let disc = b * b - a * c;
+    if disc < 0.0 {
+        return None;
+    }
+    let t = (-b - disc.sqrt()) / a;
+    if t < 0.0 {
+        return None;
+    }
+    let s = (m + *direction * Vec3::new(t, t, t)).dot(&axis) / axis_len2;
+    if (0.0..=1.0).contains(&s) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+impl BVHNode {
+    pub fn sweep_sphere(
+        bvh: Arc<Self>,
+        sphere: &Sphere,
+        direction: &Vec3,
+        max_distance: f64,
+    ) -> Option<SweepHit> {
+        Self::sweep_sphere_with_stats(
+            bvh,
+            sphere,
+            direction,
+            max_distance,
+            &mut QueryStats::default(),
+        )
+    }
+
+    pub fn sweep_sphere_with_stats(
+        bvh: Arc<Self>,
+        sphere: &Sphere,
+        direction: &Vec3,
+        max_distance: f64,
+        stats: &mut QueryStats,
+    ) -> Option<SweepHit> {
+        sweep_sphere(&bvh, sphere, direction, max_distance, stats)
+    }
+
+    pub fn sweep_box(
+        bvh: Arc<Self>,
+        obb: &OBB,
+        direction: &Vec3,
+        max_distance: f64,
+    ) -> Option<SweepHit> {
+        Self::sweep_box_with_stats(
+            bvh,
+            obb,
+            direction,
+            max_distance,
+            &mut QueryStats::default(),
+        )
+    }
+
+    pub fn sweep_box_with_stats(
+        bvh: Arc<Self>,
+        obb: &OBB,
+        direction: &Vec3,
+        max_distance: f64,
+        stats: &mut QueryStats,
+    ) -> Option<SweepHit> {
+        sweep_box(&bvh, obb, direction, max_distance, stats)
+    }
+}
+
+impl FlatBVH {
+    pub fn sweep_sphere(
+        &self,
+        sphere: &Sphere,
+        direction: &Vec3,
+        max_distance: f64,
+    ) -> Option<SweepHit> {
+        self.sweep_sphere_with_stats(sphere, direction, max_distance, &mut QueryStats::default())
+    }
+
+    pub fn sweep_sphere_with_stats(
+        &self,
+        sphere: &Sphere,
+        direction: &Vec3,
+        max_distance: f64,
+        stats: &mut QueryStats,
+    ) -> Option<SweepHit> {
+        sweep_sphere(self.node_ref(0), sphere, direction, max_distance, stats)
+    }
+
+    pub fn sweep_box(&self, obb: &OBB, direction: &Vec3, max_distance: f64) -> Option<SweepHit> {
+        self.sweep_box_with_stats(obb, direction, max_distance, &mut QueryStats::default())
+    }
+
+    pub fn sweep_box_with_stats(
+        &self,
+        obb: &OBB,
+        direction: &Vec3,
+        max_distance: f64,
+        stats: &mut QueryStats,
+    ) -> Option<SweepHit> {
+        sweep_box(self.node_ref(0), obb, direction, max_distance, stats)
+    }
+}
+
+fn sweep_sphere<'a, N: BVHNodeRef<'a>>(
+    root: N,
+    sphere: &Sphere,
+    direction: &Vec3,
+    max_distance: f64,
+    stats: &mut QueryStats,
+) -> Option<SweepHit> {
+    let mut direction = *direction;
+    direction.normalize();
+    sweep(
+        root,
+        &sphere.to_aabb(),
+        &direction,
+        max_distance,
+        &|tri, max_distance| sphere.sweep_tri(&direction, max_distance, tri),
+        stats,
+    )
+}
+
+fn sweep_box<'a, N: BVHNodeRef<'a>>(
+    root: N,
+    obb: &OBB,
+    direction: &Vec3,
+    max_distance: f64,
+    stats: &mut QueryStats,
+) -> Option<SweepHit> {
+    let mut direction = *direction;
+    direction.normalize();
+    sweep(
+        root,
+        &obb.to_aabb(),
+        &direction,
+        max_distance,
+        &|tri, max_distance| obb.sweep_tri(&direction, max_distance, tri),
+        stats,
+    )
+}
+
+// 节点包围盒按形状的半尺寸膨胀后，形状中心的射线打到它就说明扫掠体可能碰到节点，
+// 入射距离同时用来排序和剪枝，和raycast一样
+fn sweep<'a, N: BVHNodeRef<'a>>(
+    root: N,
+    shape_aabb: &AABB,
+    direction: &Vec3,
+    max_distance: f64,
+    tri_toi: &dyn Fn(&Tri, f64) -> Option<f64>,
+    stats: &mut QueryStats,
+) -> Option<SweepHit> {
+    let half = shape_aabb.extent() / Vec3::new(2.0, 2.0, 2.0);
+    let ray = Ray::new(&shape_aabb.center(), direction, max_distance);
+    let offset = ray.point_at(max_distance) - ray.origin;
+    let swept_aabb = shape_aabb.merge(&AABB::new(
+        &(shape_aabb.min + offset),
+        &(shape_aabb.max + offset),
+    ));
+    let mut visitor = SweepVisitor {
+        ray,
+        half,
+        swept_aabb,
+        max_distance,
+        tri_toi,
+        best: None,
+        stats: QueryStats::default(),
+    };
+    traverse_node_ordered(root, &mut visitor, stats);
+    *stats += visitor.stats;
+    visitor.best
+}
+
+struct SweepVisitor<'f> {
+    ray: Ray,
+    half: Vec3,
+    swept_aabb: AABB,
+    max_distance: f64,
+    tri_toi: &'f dyn Fn(&Tri, f64) -> Option<f64>,
+    best: Option<SweepHit>,
+    stats: QueryStats,
+}
+
+impl SweepVisitor<'_> {
+    fn node_entry(&mut self, aabb: &AABB) -> Option<f64> {
+        self.stats.aabb_tests += 1;
+        if !self.swept_aabb.intersect_with_aabb(aabb) {
+            return None;
+        }
+        let inflated = AABB::new(&(aabb.min - self.half), &(aabb.max + self.half));
+        self.ray.intersect_aabb(&inflated, self.max_distance)
+    }
+}
+
+impl<'a, N: BVHNodeRef<'a>> BVHVisitor<'a, N> for SweepVisitor<'_> {
+    fn visit_node(&mut self, node: N) -> VisitResult {
+        match self.node_entry(node.aabb()) {
+            Some(_) => VisitResult::Continue,
+            None => VisitResult::Skip,
+        }
+    }
+
+    fn visit_ordered(&mut self, _node: N, priority: f64) -> VisitResult {
+        if priority <= self.max_distance {
+            VisitResult::Continue
+        } else {
+            VisitResult::Skip
+        }
+    }
+
+    fn visit_leaf(&mut self, leaf: N) -> VisitResult {
+        for tri_index in leaf.tris() {
+            let tri = tri_index.to_tri(leaf.vtx_buf());
+            self.stats.tri_tests += 1;
+            if let Some(distance) = (self.tri_toi)(&tri, self.ray.max_distance) {
+                self.stats.hits += 1;
+                if self.best.is_none() || distance < self.max_distance {
+                    self.max_distance = distance;
+                    self.best = Some(SweepHit {
+                        tri_index: tri_index.clone(),
+                        distance,
+                    });
+                }
+            }
+        }
+        VisitResult::Continue
+    }
+
+    fn priority(&mut self, node: N) -> Option<f64> {
+        self.node_entry(node.aabb())
+    }
+}
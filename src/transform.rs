@@ -0,0 +1,76 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+
+pub mod prelude {
+    pub use super::Transform;
+}
+
+// 刚体变换，先旋转再平移，axes是旋转后的x/y/z轴
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    pub axes: [Vec3; 3],
+    pub translation: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            axes: [
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ],
+            translation: Vec3::default(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn new(axes: &[Vec3; 3], translation: &Vec3) -> Self {
+        Self {
+            axes: *axes,
+            translation: *translation,
+        }
+    }
+
+    // 欧拉角(弧度)，先绕x转roll，再绕y转pitch，最后绕z转yaw
+    pub fn from_euler(translation: &Vec3, roll: f64, pitch: f64, yaw: f64) -> Self {
+        let (sr, cr) = roll.sin_cos();
+        let (sp, cp) = pitch.sin_cos();
+        let (sy, cy) = yaw.sin_cos();
+        let axes = [
+            Vec3::new(cy * cp, sy * cp, -sp),
+            Vec3::new(cy * sp * sr - sy * cr, sy * sp * sr + cy * cr, cp * sr),
+            Vec3::new(cy * sp * cr + sy * sr, sy * sp * cr - cy * sr, cp * cr),
+        ];
+        Self::new(&axes, translation)
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        self.axes[0] * Vec3::new(v.x, v.x, v.x)
+            + self.axes[1] * Vec3::new(v.y, v.y, v.y)
+            + self.axes[2] * Vec3::new(v.z, v.z, v.z)
+    }
+
+    pub fn apply(&self, pt: &Vec3) -> Vec3 {
+        self.rotate(pt) + self.translation
+    }
+
+    pub fn apply_tri(&self, tri: &Tri) -> Tri {
+        Tri {
+            pt0: self.apply(&tri.pt0),
+            pt1: self.apply(&tri.pt1),
+            pt2: self.apply(&tri.pt2),
+        }
+    }
+
+    // 变换后的包围盒不再轴对齐，用OBB精确表示
+    pub fn apply_aabb(&self, aabb: &AABB) -> OBB {
+        OBB::new(
+            &self.apply(&aabb.center()),
+            &(aabb.extent() / Vec3::new(2.0, 2.0, 2.0)),
+            &self.axes,
+        )
+    }
+}
@@ -2,7 +2,7 @@
 #![allow(unused_imports)]
 
 use crate::prelude::*;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub mod prelude {
     pub use super::IndexedTri;
@@ -25,16 +25,210 @@ impl Tri {
             pt2: *pt2,
         }
     }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.pt0 + self.pt1 + self.pt2) / Vec3::new(3.0, 3.0, 3.0)
+    }
+
+    pub fn to_aabb(&self) -> AABB {
+        AABB::from_point3(&self.pt0, &self.pt1, &self.pt2)
+    }
+
+    // 三角形上离pt最近的点，按pt落在哪个顶点/边/面的Voronoi区域分情况
+    pub fn closest_point(&self, pt: &Vec3) -> Vec3 {
+        let scale = |v: Vec3, t: f64| v * Vec3::new(t, t, t);
+        let ab = self.pt1 - self.pt0;
+        let ac = self.pt2 - self.pt0;
+        let ap = *pt - self.pt0;
+        let d1 = ab.dot(&ap);
+        let d2 = ac.dot(&ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return self.pt0;
+        }
+
+        let bp = *pt - self.pt1;
+        let d3 = ab.dot(&bp);
+        let d4 = ac.dot(&bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return self.pt1;
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            return self.pt0 + scale(ab, d1 / (d1 - d3));
+        }
+
+        let cp = *pt - self.pt2;
+        let d5 = ab.dot(&cp);
+        let d6 = ac.dot(&cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return self.pt2;
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            return self.pt0 + scale(ac, d2 / (d2 - d6));
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            return self.pt1 + scale(self.pt2 - self.pt1, (d4 - d3) / ((d4 - d3) + (d5 - d6)));
+        }
+
+        // 退化三角形面积为0，上面的边区域已经覆盖了，这里只处理落在面内的情况
+        let denom = va + vb + vc;
+        if denom.abs() < f64::EPSILON {
+            return self.pt0;
+        }
+        let v = vb / denom;
+        let w = vc / denom;
+        self.pt0 + scale(ab, v) + scale(ac, w)
+    }
+
+    // 分离轴测试：两个法线和9个边叉积，共面时改用各自平面内的边法线
+    pub fn intersect_with_tri(&self, other: &Self) -> bool {
+        let lhs = [self.pt0, self.pt1, self.pt2];
+        let rhs = [other.pt0, other.pt1, other.pt2];
+        let lhs_edges = [lhs[1] - lhs[0], lhs[2] - lhs[1], lhs[0] - lhs[2]];
+        let rhs_edges = [rhs[1] - rhs[0], rhs[2] - rhs[1], rhs[0] - rhs[2]];
+        let separated = |axis: &Vec3| -> bool {
+            // 退化的轴没有分离能力
+            if axis.dot(axis) <= f64::EPSILON {
+                return false;
+            }
+            let project = |pts: &[Vec3; 3]| {
+                let p = pts.map(|pt| pt.dot(axis));
+                (p[0].min(p[1]).min(p[2]), p[0].max(p[1]).max(p[2]))
+            };
+            let (lmin, lmax) = project(&lhs);
+            let (rmin, rmax) = project(&rhs);
+            lmin > rmax || rmin > lmax
+        };
+
+        let lhs_normal = lhs_edges[0].cross(&lhs_edges[1]);
+        let rhs_normal = rhs_edges[0].cross(&rhs_edges[1]);
+        if separated(&lhs_normal) || separated(&rhs_normal) {
+            return false;
+        }
+        let parallel = lhs_normal.cross(&rhs_normal);
+        if parallel.dot(&parallel)
+            <= f64::EPSILON * lhs_normal.dot(&lhs_normal) * rhs_normal.dot(&rhs_normal)
+        {
+            for edge in lhs_edges.iter() {
+                if separated(&lhs_normal.cross(edge)) {
+                    return false;
+                }
+            }
+            for edge in rhs_edges.iter() {
+                if separated(&rhs_normal.cross(edge)) {
+                    return false;
+                }
+            }
+            return true;
+        }
+        for lhs_edge in lhs_edges.iter() {
+            for rhs_edge in rhs_edges.iter() {
+                if separated(&lhs_edge.cross(rhs_edge)) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // 不共面时两个三角形相交的部分是一条线段，由一方的边穿过另一方的点组成；
+    // 不相交或共面重叠(交集是多边形)时返回None
+    pub fn intersection_segment(&self, other: &Self) -> Option<(Vec3, Vec3)> {
+        if !self.intersect_with_tri(other) {
+            return None;
+        }
+        self.intersection_segment_unchecked(other)
+    }
+
+    // 调用方已经确认两个三角形相交，省掉一次分离轴测试
+    pub(crate) fn intersection_segment_unchecked(&self, other: &Self) -> Option<(Vec3, Vec3)> {
+        let mut points = Vec::<Vec3>::new();
+        for (edge_tri, tri) in [(self, other), (other, self)] {
+            for (p, q) in [
+                (edge_tri.pt0, edge_tri.pt1),
+                (edge_tri.pt1, edge_tri.pt2),
+                (edge_tri.pt2, edge_tri.pt0),
+            ] {
+                let length = p.distance_to(&q);
+                if length <= 0.0 {
+                    continue;
+                }
+                let ray = Ray::new(&p, &(q - p), length);
+                if let Some((t, _, _)) = ray.intersect_tri(tri, length) {
+                    points.push(ray.point_at(t));
+                }
+            }
+        }
+        // 取相距最远的两个点作为端点
+        let mut ret: Option<(Vec3, Vec3)> = None;
+        let mut longest = -1.0f64;
+        for (idx, lhs) in points.iter().enumerate() {
+            for rhs in points[idx..].iter() {
+                let length = lhs.distance_to(rhs);
+                if length > longest {
+                    longest = length;
+                    ret = Some((*lhs, *rhs));
+                }
+            }
+        }
+        ret
+    }
+
+    // 分离轴测试：包围盒3个面法线、三角形法线、以及9个边叉积
+    pub fn intersect_with_aabb(&self, aabb: &AABB) -> bool {
+        let center = aabb.center();
+        let half = aabb.extent() / Vec3::new(2.0, 2.0, 2.0);
+        let v0 = self.pt0 - center;
+        let v1 = self.pt1 - center;
+        let v2 = self.pt2 - center;
+
+        let separated = |axis: &Vec3| -> bool {
+            let p0 = v0.dot(axis);
+            let p1 = v1.dot(axis);
+            let p2 = v2.dot(axis);
+            let r = half.x * axis.x.abs() + half.y * axis.y.abs() + half.z * axis.z.abs();
+            p0.min(p1).min(p2) > r || p0.max(p1).max(p2) < -r
+        };
+
+        let box_axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ];
+        for axis in box_axes.iter() {
+            if separated(axis) {
+                return false;
+            }
+        }
+
+        let edges = [v1 - v0, v2 - v1, v0 - v2];
+        if separated(&edges[0].cross(&edges[1])) {
+            return false;
+        }
+        for edge in edges.iter() {
+            for axis in box_axes.iter() {
+                if separated(&edge.cross(axis)) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct IndexedTri {
-    pub vtx_buf: Rc<Vec<Vec3>>,
+    pub vtx_buf: Arc<Vec<Vec3>>,
     pub indices: [usize; 3],
 }
 
 impl IndexedTri {
-    pub fn new(vtx_buf: Rc<Vec<Vec3>>, idxpt0: usize, idxpt1: usize, idxpt2: usize) -> Self {
+    pub fn new(vtx_buf: Arc<Vec<Vec3>>, idxpt0: usize, idxpt1: usize, idxpt2: usize) -> Self {
         Self {
             vtx_buf,
             indices: [idxpt0, idxpt1, idxpt2],
@@ -53,6 +247,13 @@ impl IndexedTri {
     }
 }
 
+// 空间切分会把同一个三角形放进多个叶子，查询按(pt0, pt1, pt2)去重
+pub(crate) type TriKey = (usize, usize, usize);
+
+pub(crate) fn tri_key(tri_index: &TriIndex) -> TriKey {
+    (tri_index.pt0, tri_index.pt1, tri_index.pt2)
+}
+
 #[derive(Clone, Debug)]
 pub struct TriIndex {
     pub pt0: usize,
@@ -65,14 +266,15 @@ impl TriIndex {
         Self { pt0, pt1, pt2 }
     }
 
-    pub fn to_tri(&self, pts: Rc<Vec<Vec3>>) -> Tri {
+    // 只借用顶点，逐个三角形测试时不碰Arc的引用计数
+    pub fn to_tri(&self, pts: &[Vec3]) -> Tri {
         let pt0 = pts[self.pt0];
         let pt1 = pts[self.pt1];
         let pt2 = pts[self.pt2];
         Tri::new(&pt0, &pt1, &pt2)
     }
 
-    pub fn to_indexed_tri(&self, pts: Rc<Vec<Vec3>>) -> IndexedTri {
+    pub fn to_indexed_tri(&self, pts: Arc<Vec<Vec3>>) -> IndexedTri {
         IndexedTri::new(pts, self.pt0, self.pt1, self.pt2)
     }
 }
@@ -53,6 +53,22 @@ impl Vec3 {
         }
     }
 
+    pub fn component(&self, axis: AABBSplitAxis) -> f64 {
+        match axis {
+            AABBSplitAxis::X => self.x,
+            AABBSplitAxis::Y => self.y,
+            AABBSplitAxis::Z => self.z,
+        }
+    }
+
+    pub fn set_component(&mut self, axis: AABBSplitAxis, value: f64) {
+        match axis {
+            AABBSplitAxis::X => self.x = value,
+            AABBSplitAxis::Y => self.y = value,
+            AABBSplitAxis::Z => self.z = value,
+        }
+    }
+
     pub fn length(&self) -> f64 {
         self.dot(self).sqrt()
     }
@@ -61,6 +77,14 @@ impl Vec3 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
+    pub fn cross(&self, other: &Self) -> Self {
+        Self {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
     pub fn distance_to(&self, other: &Self) -> f64 {
         let tmp = Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z);
         tmp.length()
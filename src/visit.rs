@@ -0,0 +1,210 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use std::sync::Arc;
+
+pub mod prelude {
+    pub use super::BVHNodeRef;
+    pub use super::BVHVisitor;
+    pub use super::FnVisitor;
+    pub use super::VisitResult;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VisitResult {
+    Continue,
+    // 跳过这个节点的整棵子树，在visit_leaf里和Continue一样
+    Skip,
+    // 立刻结束整个遍历
+    Stop,
+}
+
+// 每个节点先经过visit_node，返回Continue的叶子再交给visit_leaf。
+// T是遍历交给访问者的节点句柄，BVHNode是节点引用，FlatBVH是节点在nodes里的下标
+pub trait BVHVisitor<'a, T = &'a Arc<BVHNode>> {
+    fn visit_node(&mut self, node: T) -> VisitResult;
+
+    fn visit_leaf(&mut self, _leaf: T) -> VisitResult {
+        VisitResult::Continue
+    }
+
+    // 只有traverse_ordered会用，值小的孩子先访问，返回None的孩子直接剪掉不入栈
+    fn priority(&mut self, _node: T) -> Option<f64> {
+        Some(0.0)
+    }
+
+    // traverse_ordered出栈时代替visit_node调用，priority是入栈时算好的值。
+    // 排序和剪枝用同一个包围盒测试的访问者重写它，每个节点就只测一次
+    fn visit_ordered(&mut self, node: T, _priority: f64) -> VisitResult {
+        self.visit_node(node)
+    }
+}
+
+// 用两个闭包拼出访问者，不需要排序的查询不用单独定义结构体
+pub struct FnVisitor<N, L> {
+    pub node: N,
+    pub leaf: L,
+}
+
+impl<N, L> FnVisitor<N, L> {
+    pub fn new(node: N, leaf: L) -> Self {
+        Self { node, leaf }
+    }
+}
+
+impl<'a, T, N, L> BVHVisitor<'a, T> for FnVisitor<N, L>
+where
+    N: FnMut(T) -> VisitResult,
+    L: FnMut(T) -> VisitResult,
+{
+    fn visit_node(&mut self, node: T) -> VisitResult {
+        (self.node)(node)
+    }
+
+    fn visit_leaf(&mut self, leaf: T) -> VisitResult {
+        (self.leaf)(leaf)
+    }
+}
+
+// 查询读节点用的句柄。BVHNode是节点引用，FlatBVH是带着树的下标，
+// 查询的访问者按它实现一次，两种树共用同一份遍历和测试代码
+pub trait BVHNodeRef<'a>: Copy {
+    fn aabb(self) -> &'a AABB;
+
+    fn is_leaf(self) -> bool;
+
+    // 叶子里的三角形
+    fn tris(self) -> &'a [TriIndex];
+
+    fn vtx_buf(self) -> &'a [Vec3];
+
+    fn children(self) -> impl DoubleEndedIterator<Item = Self>;
+
+    // 是不是同一个节点，自相交查询靠它区分叶子和自己配对
+    fn same_node(self, other: Self) -> bool;
+}
+
+impl<'a> BVHNodeRef<'a> for &'a Arc<BVHNode> {
+    fn aabb(self) -> &'a AABB {
+        &self.aabb
+    }
+
+    fn is_leaf(self) -> bool {
+        BVHNode::is_leaf(self)
+    }
+
+    fn tris(self) -> &'a [TriIndex] {
+        &self.idx_buf
+    }
+
+    fn vtx_buf(self) -> &'a [Vec3] {
+        &self.vtx_buf
+    }
+
+    fn children(self) -> impl DoubleEndedIterator<Item = Self> {
+        self.children.iter()
+    }
+
+    fn same_node(self, other: Self) -> bool {
+        Arc::ptr_eq(self, other)
+    }
+}
+
+// 深度优先，孩子按原顺序访问，整个过程不分配。被Stop中止时返回true。
+// 节点、叶子及叶子里三角形的计数由遍历负责。visit_node不一定测包围盒，
+// 所以包围盒测试数、三角形测试数和命中数都由访问者自己累加
+pub(crate) fn traverse_node<'a, N: BVHNodeRef<'a>, V: BVHVisitor<'a, N>>(
+    node: N,
+    visitor: &mut V,
+    stats: &mut QueryStats,
+) -> bool {
+    stats.nodes_visited += 1;
+    match visitor.visit_node(node) {
+        VisitResult::Stop => return true,
+        VisitResult::Skip => return false,
+        VisitResult::Continue => {}
+    }
+    if node.is_leaf() {
+        stats.leaves += 1;
+        stats.leaf_tris += node.tris().len();
+        return visitor.visit_leaf(node) == VisitResult::Stop;
+    }
+    node.children()
+        .any(|child| traverse_node(child, visitor, stats))
+}
+
+// priority小的先访问。visit_ordered在出栈时才调用，
+// 访问者在遍历中途收紧的条件(比如最近命中距离)可以剪掉早已入栈的节点
+pub(crate) fn traverse_node_ordered<'a, N: BVHNodeRef<'a>, V: BVHVisitor<'a, N>>(
+    root: N,
+    visitor: &mut V,
+    stats: &mut QueryStats,
+) -> bool {
+    let mut stack = match visitor.priority(root) {
+        Some(priority) => vec![(root, priority)],
+        None => return false,
+    };
+    while let Some((node, priority)) = stack.pop() {
+        stats.nodes_visited += 1;
+        match visitor.visit_ordered(node, priority) {
+            VisitResult::Stop => return true,
+            VisitResult::Skip => continue,
+            VisitResult::Continue => {}
+        }
+        if node.is_leaf() {
+            stats.leaves += 1;
+            stats.leaf_tris += node.tris().len();
+            if visitor.visit_leaf(node) == VisitResult::Stop {
+                return true;
+            }
+            continue;
+        }
+
+        // 近的孩子后入栈，先出栈。距离相同时和traverse一样按原顺序访问
+        let first = stack.len();
+        for child in node.children().rev() {
+            if let Some(priority) = visitor.priority(child) {
+                stack.push((child, priority));
+            }
+        }
+        stack[first..].sort_by(|a, b| b.1.total_cmp(&a.1));
+    }
+    false
+}
+
+// 子树里的所有叶子，深度优先按原顺序
+pub(crate) fn for_each_leaf<'a, N: BVHNodeRef<'a>, F: FnMut(N)>(node: N, f: &mut F) {
+    if node.is_leaf() {
+        f(node);
+        return;
+    }
+    for child in node.children() {
+        for_each_leaf(child, f);
+    }
+}
+
+impl BVHNode {
+    pub fn traverse<'a, V: BVHVisitor<'a>>(bvh: &'a Arc<Self>, visitor: &mut V) -> bool {
+        Self::traverse_with_stats(bvh, visitor, &mut QueryStats::default())
+    }
+
+    pub fn traverse_with_stats<'a, V: BVHVisitor<'a>>(
+        bvh: &'a Arc<Self>,
+        visitor: &mut V,
+        stats: &mut QueryStats,
+    ) -> bool {
+        traverse_node(bvh, visitor, stats)
+    }
+
+    pub fn traverse_ordered<'a, V: BVHVisitor<'a>>(bvh: &'a Arc<Self>, visitor: &mut V) -> bool {
+        Self::traverse_ordered_with_stats(bvh, visitor, &mut QueryStats::default())
+    }
+
+    pub fn traverse_ordered_with_stats<'a, V: BVHVisitor<'a>>(
+        bvh: &'a Arc<Self>,
+        visitor: &mut V,
+        stats: &mut QueryStats,
+    ) -> bool {
+        traverse_node_ordered(bvh, visitor, stats)
+    }
+}
@@ -0,0 +1,50 @@
+#![allow(dead_code)]
+
+use crate::prelude::*;
+use std::sync::Arc;
+
+impl BVHNode {
+    // 把二叉树压成width叉树，叶子不变，只是把孙子提上来减少内部节点
+    pub fn collapse(bvh: Arc<Self>, width: usize) -> Self {
+        let width = width.max(2);
+        let mut children = bvh.children.clone();
+
+        // 提起一个内部孩子省掉的是一次包围盒测试，按SAH它的代价和表面积成正比，
+        // 所以每次贪心地展开表面积最大、且展开后不超宽的那个
+        loop {
+            let candidate = children
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| {
+                    !child.is_leaf() && children.len() - 1 + child.children.len() <= width
+                })
+                .max_by(|(_, a), (_, b)| a.aabb.surface_area().total_cmp(&b.aabb.surface_area()))
+                .map(|(idx, _)| idx);
+            match candidate {
+                Some(idx) => {
+                    let child = children.remove(idx);
+                    children.extend(child.children.iter().cloned());
+                }
+                None => break,
+            }
+        }
+
+        Self {
+            vtx_buf: bvh.vtx_buf.clone(),
+            idx_buf: bvh.idx_buf.clone(),
+            aabb: bvh.aabb.clone(),
+            children: children
+                .into_iter()
+                .map(|child| Arc::new(Self::collapse(child, width)))
+                .collect(),
+        }
+    }
+
+    pub fn max_width(bvh: Arc<Self>) -> usize {
+        Self::get_all_nodes(bvh)
+            .iter()
+            .map(|node| node.children.len())
+            .max()
+            .unwrap_or(0)
+    }
+}